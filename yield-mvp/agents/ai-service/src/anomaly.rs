@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use crate::sentiment::SentimentData;
+
+/// Observations kept for the volume and jump baselines.
+const HISTORY_LEN: usize = 48;
+
+/// `safety.anomaly` in the workflow config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnomalyConfig {
+    /// Tweet volume this many times the recent median counts as a spike.
+    pub volume_spike_ratio: f64,
+    /// Largest allowed gap (points) between the highest and lowest source score.
+    pub max_source_spread: f32,
+    /// Score moves beyond this many standard deviations of recent moves are improbable...
+    pub jump_z_score: f64,
+    /// ...as long as they are also at least this many points.
+    pub min_jump: f64,
+    /// Observations needed before volume and jump checks apply.
+    pub min_baseline: usize,
+    /// How long rebalances stay suppressed after an anomaly.
+    pub cooldown_secs: i64,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            volume_spike_ratio: 5.0,
+            max_source_spread: 40.0,
+            jump_z_score: 4.0,
+            min_jump: 15.0,
+            min_baseline: 6,
+            cooldown_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnomalyKind {
+    VolumeSpike { volume: u64, baseline: f64 },
+    SourceDisagreement { scores: Vec<(String, f32)>, spread: f32 },
+    ImprobableJump { from: f64, to: f64, z_score: f64 },
+}
+
+/// One reading as the detector sees it.
+#[derive(Debug, Clone)]
+pub struct Observation {
+    pub timestamp: i64,
+    pub score: f64,
+    /// Tweets behind the reading, if Twitter contributed.
+    pub volume: Option<u64>,
+    pub source_scores: Vec<(String, f32)>,
+}
+
+impl Observation {
+    pub fn from_sentiment(data: &SentimentData) -> Self {
+        Self {
+            timestamp: data.timestamp,
+            score: data.score as f64,
+            volume: data.filter_stats.map(|stats| stats.received as u64),
+            source_scores: data
+                .breakdown
+                .iter()
+                .filter_map(|b| b.score.map(|score| (b.source.clone(), score)))
+                .collect(),
+        }
+    }
+}
+
+/// Flags readings that look manipulated or broken: tweet volume spikes, sources
+/// that disagree wildly, and score jumps far outside recent volatility.
+pub struct AnomalyDetector {
+    config: AnomalyConfig,
+    scores: VecDeque<f64>,
+    volumes: VecDeque<u64>,
+    active_until: Option<i64>,
+}
+
+impl AnomalyDetector {
+    pub fn new(config: AnomalyConfig) -> Self {
+        Self {
+            config,
+            scores: VecDeque::new(),
+            volumes: VecDeque::new(),
+            active_until: None,
+        }
+    }
+
+    /// Checks `observation` against the baselines, then adds it to them. Any anomaly
+    /// (re)starts the cooldown.
+    pub fn observe(&mut self, observation: &Observation) -> Vec<AnomalyKind> {
+        let mut anomalies = Vec::new();
+
+        if let Some(volume) = observation.volume {
+            if self.volumes.len() >= self.config.min_baseline {
+                let baseline = median(self.volumes.iter().map(|&v| v as f64).collect());
+                if baseline > 0.0 && volume as f64 > baseline * self.config.volume_spike_ratio {
+                    anomalies.push(AnomalyKind::VolumeSpike { volume, baseline });
+                }
+            }
+            push_bounded(&mut self.volumes, volume);
+        }
+
+        if observation.source_scores.len() >= 2 {
+            let (min, max) = observation
+                .source_scores
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), (_, s)| (min.min(*s), max.max(*s)));
+            if max - min > self.config.max_source_spread {
+                anomalies.push(AnomalyKind::SourceDisagreement {
+                    scores: observation.source_scores.clone(),
+                    spread: max - min,
+                });
+            }
+        }
+
+        if let Some(&last) = self.scores.back() {
+            let jump = observation.score - last;
+            if jump.abs() >= self.config.min_jump && self.scores.len() > self.config.min_baseline {
+                let deviation = std_dev(&self.moves());
+                let z_score = if deviation > 0.0 { jump.abs() / deviation } else { f64::INFINITY };
+                if z_score > self.config.jump_z_score {
+                    anomalies.push(AnomalyKind::ImprobableJump {
+                        from: last,
+                        to: observation.score,
+                        z_score,
+                    });
+                }
+            }
+        }
+        push_bounded(&mut self.scores, observation.score);
+
+        if !anomalies.is_empty() {
+            self.active_until = Some(observation.timestamp + self.config.cooldown_secs);
+        }
+        anomalies
+    }
+
+    /// Whether an anomaly seen within the cooldown is still in effect at `now`.
+    pub fn is_active(&self, now: i64) -> bool {
+        self.active_until.map_or(false, |until| now < until)
+    }
+
+    pub fn active_until(&self) -> Option<i64> {
+        self.active_until
+    }
+
+    fn moves(&self) -> Vec<f64> {
+        self.scores
+            .iter()
+            .zip(self.scores.iter().skip(1))
+            .map(|(a, b)| b - a)
+            .collect()
+    }
+}
+
+fn push_bounded<T>(values: &mut VecDeque<T>, value: T) {
+    values.push_back(value);
+    if values.len() > HISTORY_LEN {
+        values.pop_front();
+    }
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+fn std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(timestamp: i64, score: f64, volume: Option<u64>) -> Observation {
+        Observation {
+            timestamp,
+            score,
+            volume,
+            source_scores: Vec::new(),
+        }
+    }
+
+    fn warmed_up() -> AnomalyDetector {
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        for (i, score) in [50.0, 52.0, 49.0, 51.0, 50.0, 53.0, 51.0].into_iter().enumerate() {
+            assert!(detector.observe(&observation(i as i64 * 300, score, Some(100))).is_empty());
+        }
+        detector
+    }
+
+    #[test]
+    fn flags_volume_spikes_and_improbable_jumps() {
+        let mut detector = warmed_up();
+        let anomalies = detector.observe(&observation(3000, 85.0, Some(900)));
+
+        assert_eq!(anomalies.len(), 2);
+        assert_eq!(anomalies[0], AnomalyKind::VolumeSpike { volume: 900, baseline: 100.0 });
+        assert!(matches!(anomalies[1], AnomalyKind::ImprobableJump { from, to, .. } if from == 51.0 && to == 85.0));
+        assert!(detector.is_active(3000 + 1800));
+        assert!(!detector.is_active(3000 + 3600));
+    }
+
+    #[test]
+    fn ordinary_moves_are_not_anomalies() {
+        let mut detector = warmed_up();
+        assert!(detector.observe(&observation(3000, 54.0, Some(180))).is_empty());
+        // Moves under min_jump are never improbable
+        assert!(detector.observe(&observation(3300, 62.0, None)).is_empty());
+        assert!(!detector.is_active(3300));
+    }
+
+    #[test]
+    fn flags_sources_that_disagree() {
+        let mut detector = AnomalyDetector::new(AnomalyConfig::default());
+        let mut reading = observation(0, 55.0, None);
+        reading.source_scores = vec![
+            ("twitter".to_string(), 92.0),
+            ("cryptopanic".to_string(), 40.0),
+            ("onchain".to_string(), 48.0),
+        ];
+
+        let anomalies = detector.observe(&reading);
+        assert!(matches!(&anomalies[..], [AnomalyKind::SourceDisagreement { spread, .. }] if *spread == 52.0));
+        assert_eq!(detector.active_until(), Some(3600));
+    }
+}
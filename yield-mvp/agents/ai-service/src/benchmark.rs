@@ -0,0 +1,200 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::config::PoolMetrics;
+use crate::protocols::STABLECOINS;
+
+const SECONDS_PER_YEAR: f64 = 365.0 * 86_400.0;
+
+/// Market observation used to value the benchmark portfolios.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketSnapshot {
+    pub timestamp: DateTime<Utc>,
+    /// NEAR price in USD.
+    pub near_price: f64,
+    /// Native staking APY, in percent.
+    pub staking_apy: f64,
+    /// Stablecoin lending APY, in percent.
+    pub stable_apy: f64,
+}
+
+impl MarketSnapshot {
+    /// Snapshot at `near_price` with the mean known APY of the tracked staking pools
+    /// and stablecoin lending markets. A kind with no known APY keeps its rate from
+    /// `previous`; `None` until both have been seen once.
+    pub fn from_pools(
+        timestamp: DateTime<Utc>,
+        near_price: f64,
+        pools: &[PoolMetrics],
+        previous: Option<&MarketSnapshot>,
+    ) -> Option<Self> {
+        let staking_apy = mean_apy(pools, |pool| pool.tags.iter().any(|t| t == "staking"))
+            .or(previous.map(|p| p.staking_apy))?;
+        let stable_apy = mean_apy(pools, |pool| {
+            pool.tags.iter().any(|t| t == "lending") && pool.tags.iter().any(|t| STABLECOINS.contains(&t.as_str()))
+        })
+        .or(previous.map(|p| p.stable_apy))?;
+
+        Some(Self {
+            timestamp,
+            near_price,
+            staking_apy,
+            stable_apy,
+        })
+    }
+}
+
+fn mean_apy(pools: &[PoolMetrics], include: impl Fn(&PoolMetrics) -> bool) -> Option<f64> {
+    let apys: Vec<f64> = pools.iter().filter(|pool| include(pool)).filter_map(|pool| pool.apy).collect();
+    if apys.is_empty() {
+        None
+    } else {
+        Some(apys.iter().sum::<f64>() / apys.len() as f64)
+    }
+}
+
+/// USD value of each benchmark portfolio, all started with the same capital.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkValues {
+    /// Buy-and-hold NEAR.
+    pub hold_near: f64,
+    /// 100% native staking, rewards compounded.
+    pub staking: f64,
+    /// Static 50% native staking / 50% stablecoin lending, never rebalanced.
+    pub balanced: f64,
+}
+
+/// Values the benchmark portfolios at every snapshot from `start` on, starting from
+/// `initial_value` USD at the last snapshot at or before `start` (or the first one
+/// after it). `market` must be sorted by timestamp.
+pub fn benchmark_curve(
+    market: &[MarketSnapshot],
+    start: DateTime<Utc>,
+    initial_value: f64,
+) -> Vec<(DateTime<Utc>, BenchmarkValues)> {
+    let first = market.partition_point(|s| s.timestamp <= start).saturating_sub(1);
+    let snapshots = match market.get(first..) {
+        Some(snapshots) if !snapshots.is_empty() && snapshots[0].near_price > 0.0 => snapshots,
+        _ => return Vec::new(),
+    };
+
+    let near_held = initial_value / snapshots[0].near_price;
+    let mut near_staked = near_held;
+    let mut stable = initial_value / 2.0;
+
+    let mut curve = Vec::with_capacity(snapshots.len());
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        if i > 0 {
+            // Accrue at the rates observed at the start of each interval
+            let previous = &snapshots[i - 1];
+            let years = (snapshot.timestamp - previous.timestamp).num_seconds() as f64 / SECONDS_PER_YEAR;
+            near_staked *= 1.0 + previous.staking_apy / 100.0 * years;
+            stable *= 1.0 + previous.stable_apy / 100.0 * years;
+        }
+
+        let staking = near_staked * snapshot.near_price;
+        curve.push((
+            start.max(snapshot.timestamp),
+            BenchmarkValues {
+                hold_near: near_held * snapshot.near_price,
+                staking,
+                balanced: staking / 2.0 + stable,
+            },
+        ));
+    }
+    curve
+}
+
+/// Benchmark values at the last curve point at or before `timestamp`.
+pub fn value_at(curve: &[(DateTime<Utc>, BenchmarkValues)], timestamp: DateTime<Utc>) -> Option<BenchmarkValues> {
+    let idx = curve.partition_point(|(t, _)| *t <= timestamp);
+    idx.checked_sub(1).map(|i| curve[i].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn snapshot(day: i64, near_price: f64) -> MarketSnapshot {
+        MarketSnapshot {
+            timestamp: Utc.timestamp_opt(day * 86_400, 0).unwrap(),
+            near_price,
+            staking_apy: 10.0,
+            stable_apy: 5.0,
+        }
+    }
+
+    #[test]
+    fn values_benchmarks_from_the_same_capital() {
+        let market = vec![snapshot(0, 2.0), snapshot(365, 3.0)];
+        let curve = benchmark_curve(&market, market[0].timestamp, 1000.0);
+
+        assert_eq!(curve.len(), 2);
+        assert_eq!(curve[0].1.hold_near, 1000.0);
+        assert_eq!(curve[0].1.balanced, 1000.0);
+
+        let end = curve[1].1;
+        assert!((end.hold_near - 1500.0).abs() < 1e-9);
+        // 500 NEAR staked at 10% for a year, worth $3 each
+        assert!((end.staking - 1650.0).abs() < 1e-9);
+        // Half of that plus $500 lent at 5%
+        assert!((end.balanced - (825.0 + 525.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn starts_from_the_snapshot_in_effect() {
+        let market = vec![snapshot(0, 2.0), snapshot(10, 4.0), snapshot(20, 5.0)];
+        let start = Utc.timestamp_opt(15 * 86_400, 0).unwrap();
+        let curve = benchmark_curve(&market, start, 100.0);
+
+        assert_eq!(curve.len(), 2);
+        assert_eq!(curve[0].0, start);
+        assert_eq!(value_at(&curve, start).unwrap().hold_near, 100.0);
+        assert_eq!(value_at(&curve, market[2].timestamp).unwrap().hold_near, 125.0);
+        assert!(value_at(&curve, market[0].timestamp).is_none());
+    }
+
+    fn pool(pool_id: &str, apy: Option<f64>, tags: &[&str]) -> PoolMetrics {
+        PoolMetrics {
+            protocol: "test".to_string(),
+            pool_id: pool_id.to_string(),
+            apy,
+            tvl: 1_000_000,
+            risk_score: 0.5,
+            audit_status: true,
+            token_pair: ("near".to_string(), "usdc".to_string()),
+            chain_factor: 1.0,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            risk_bucket: None,
+        }
+    }
+
+    #[test]
+    fn snapshot_averages_staking_and_stablecoin_lending_apys() {
+        let pools = vec![
+            pool("linear", Some(9.0), &["staking", "liquid_staking"]),
+            pool("usdc", Some(6.0), &["lending", "usdc"]),
+            pool("usdt", Some(4.0), &["lending", "usdt"]),
+            pool("near", Some(30.0), &["lending", "near"]),
+            pool("79", None, &["near_usdt", "near_stable"]),
+        ];
+        let now = Utc.timestamp_opt(86_400, 0).unwrap();
+        let market = MarketSnapshot::from_pools(now, 3.0, &pools, None).unwrap();
+
+        assert_eq!(market.staking_apy, 9.0);
+        assert_eq!(market.stable_apy, 5.0);
+        assert_eq!(market.near_price, 3.0);
+    }
+
+    #[test]
+    fn snapshot_keeps_the_previous_rate_for_unknown_apys() {
+        let pools = vec![pool("linear", None, &["staking"]), pool("usdc", Some(6.0), &["lending", "usdc"])];
+        let now = Utc.timestamp_opt(86_400, 0).unwrap();
+        assert!(MarketSnapshot::from_pools(now, 3.0, &pools, None).is_none());
+
+        let previous = snapshot(0, 2.0);
+        let market = MarketSnapshot::from_pools(now, 3.0, &pools, Some(&previous)).unwrap();
+        assert_eq!(market.staking_apy, 10.0);
+        assert_eq!(market.stable_apy, 6.0);
+    }
+}
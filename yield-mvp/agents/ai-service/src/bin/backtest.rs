@@ -0,0 +1,332 @@
+//! Replays historical sentiment and pool series through the live strategy logic
+//! and reports how the resulting portfolio would have performed (APY, Sharpe,
+//! Sortino, volatility, max drawdown, Calmar and win rate).
+//!
+//! ```text
+//! backtest --sentiment data/backtest/sentiment.csv --pools data/backtest/pools.csv \
+//!     [--config ../../config/workflow-config.yaml] [--bearish 25] [--bullish 75] \
+//!     [--emergency 20] [--risk low|moderate|high] \
+//!     [--mode heuristic|mean_variance|max_sharpe] [--initial-value 10000] \
+//!     [--market data/backtest/market.csv] [--period-days 7] [--output report.json]
+//! ```
+//!
+//! Inputs are CSV or JSON (picked by file extension). Sentiment rows carry
+//! `timestamp` (unix seconds) and `score` (0-100); pool rows carry `timestamp`,
+//! `pool_id`, `protocol`, `category` (a `regime_allocation` bucket such as
+//! `staking` or `stable_pools`), `apy` (percent), `tvl` and `risk_score`. Values
+//! are in USD. The optional market rows carry `timestamp`, `near_price` (USD),
+//! `staking_apy` and `stable_apy` (percent) and enable the hold-NEAR, staking and
+//! 50/50 benchmarks.
+
+use ai_service::benchmark::MarketSnapshot;
+use ai_service::config::{
+    load_config, OptimizationMode, PoolMetrics, RiskLevel, SentimentThresholds, YieldConfig,
+};
+use ai_service::optimizer::YieldOptimizer;
+use ai_service::performance::{ComparisonMetrics, PerformanceTracker, PeriodComparison, StrategyStats};
+use ai_service::workflow_manager::{classify_sentiment, regime_allocation, MarketRegime};
+use anyhow::{anyhow, Context};
+use chrono::{TimeZone, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::broadcast;
+
+const STRATEGY_ID: &str = "backtest";
+const YEAR_SECS: f64 = 365.0 * 86_400.0;
+const NEUTRAL_SCORE: f64 = 50.0;
+
+#[derive(Debug, Deserialize)]
+struct SentimentPoint {
+    timestamp: i64,
+    score: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct MarketPoint {
+    timestamp: i64,
+    near_price: f64,
+    staking_apy: f64,
+    stable_apy: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct PoolPoint {
+    timestamp: i64,
+    pool_id: String,
+    protocol: String,
+    category: String,
+    apy: f64,
+    tvl: u64,
+    risk_score: f64,
+}
+
+impl PoolPoint {
+    fn to_metrics(&self) -> PoolMetrics {
+        PoolMetrics {
+            protocol: self.protocol.clone(),
+            pool_id: self.pool_id.clone(),
+            apy: Some(self.apy),
+            tvl: self.tvl,
+            risk_score: self.risk_score,
+            audit_status: true,
+            token_pair: (String::new(), String::new()),
+            chain_factor: 1.0,
+            // The category doubles as the tag the mean-variance modes use to find staking pools
+            tags: vec![self.category.clone()],
+            risk_bucket: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BacktestReport {
+    start: i64,
+    end: i64,
+    steps: usize,
+    initial_value: f64,
+    final_value: f64,
+    rebalances: u32,
+    regime_steps: HashMap<MarketRegime, usize>,
+    #[serde(flatten)]
+    stats: StrategyStats,
+    benchmarks: ComparisonMetrics,
+    periods: Vec<PeriodComparison>,
+}
+
+struct Args {
+    values: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse() -> anyhow::Result<Self> {
+        let mut values = HashMap::new();
+        let mut args = std::env::args().skip(1);
+        while let Some(key) = args.next() {
+            let key = key
+                .strip_prefix("--")
+                .ok_or_else(|| anyhow!("Unexpected argument '{}'", key))?
+                .to_string();
+            let value = args.next().ok_or_else(|| anyhow!("Missing value for --{}", key))?;
+            values.insert(key, value);
+        }
+        Ok(Self { values })
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    fn required(&self, key: &str) -> anyhow::Result<&str> {
+        self.get(key).ok_or_else(|| anyhow!("--{} is required", key))
+    }
+
+    fn parsed<T: std::str::FromStr>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        self.get(key)
+            .map(|v| v.parse().map_err(|_| anyhow!("Invalid value for --{}: {}", key, v)))
+            .transpose()
+    }
+}
+
+fn load_series<T: DeserializeOwned>(path: &str) -> anyhow::Result<Vec<T>> {
+    if path.ends_with(".json") {
+        let contents = std::fs::read_to_string(path).with_context(|| format!("reading {}", path))?;
+        return Ok(serde_json::from_str(&contents)?);
+    }
+
+    let mut reader = csv::Reader::from_path(path).with_context(|| format!("reading {}", path))?;
+    reader
+        .deserialize()
+        .collect::<Result<Vec<T>, _>>()
+        .with_context(|| format!("parsing {}", path))
+}
+
+fn parse_risk(value: &str) -> anyhow::Result<RiskLevel> {
+    match value {
+        "low" => Ok(RiskLevel::Low),
+        "moderate" => Ok(RiskLevel::Moderate),
+        "high" => Ok(RiskLevel::High),
+        _ => Err(anyhow!("Unknown risk level '{}'", value)),
+    }
+}
+
+fn parse_mode(value: &str) -> anyhow::Result<OptimizationMode> {
+    match value {
+        "heuristic" => Ok(OptimizationMode::Heuristic),
+        "mean_variance" => Ok(OptimizationMode::MeanVariance { risk_aversion: 1.0 }),
+        "max_sharpe" => Ok(OptimizationMode::MaxSharpe { risk_free_rate: 2.0 }),
+        _ => Err(anyhow!("Unknown optimization mode '{}'", value)),
+    }
+}
+
+/// Latest score at or before `timestamp`; `sentiment` must be sorted.
+fn sentiment_at(sentiment: &[SentimentPoint], timestamp: i64) -> f64 {
+    let idx = sentiment.partition_point(|p| p.timestamp <= timestamp);
+    if idx == 0 {
+        NEUTRAL_SCORE
+    } else {
+        sentiment[idx - 1].score
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse()?;
+
+    let mut sentiment: Vec<SentimentPoint> = load_series(args.required("sentiment")?)?;
+    sentiment.sort_by_key(|p| p.timestamp);
+
+    let pool_points: Vec<PoolPoint> = load_series(args.required("pools")?)?;
+    let mut steps: BTreeMap<i64, Vec<PoolPoint>> = BTreeMap::new();
+    for point in pool_points {
+        steps.entry(point.timestamp).or_default().push(point);
+    }
+    if steps.is_empty() {
+        return Err(anyhow!("No pool data to replay"));
+    }
+
+    let (mut thresholds, mut emergency_threshold) = match args.get("config") {
+        Some(path) => {
+            let config = load_config(path)?;
+            (config.scraping.sentiment_thresholds, config.safety.emergency_threshold)
+        }
+        None => (SentimentThresholds { bearish: 25, bullish: 75 }, 20),
+    };
+    if let Some(bearish) = args.parsed("bearish")? {
+        thresholds.bearish = bearish;
+    }
+    if let Some(bullish) = args.parsed("bullish")? {
+        thresholds.bullish = bullish;
+    }
+    if let Some(emergency) = args.parsed("emergency")? {
+        emergency_threshold = emergency;
+    }
+
+    let mut yield_config = YieldConfig::new(parse_risk(args.get("risk").unwrap_or("moderate"))?);
+    yield_config.optimization_mode = parse_mode(args.get("mode").unwrap_or("heuristic"))?;
+    let initial_value: f64 = args.parsed("initial-value")?.unwrap_or(10_000.0);
+
+    let (alert_tx, _alert_rx) = broadcast::channel(100);
+    let mut optimizers: HashMap<String, YieldOptimizer> = HashMap::new();
+    let mut tracker = PerformanceTracker::new();
+
+    let start = *steps.keys().next().unwrap();
+    let end = *steps.keys().next_back().unwrap();
+    tracker.register_strategy(STRATEGY_ID, initial_value, Utc.timestamp_opt(start, 0).unwrap());
+
+    if let Some(path) = args.get("market") {
+        let mut market: Vec<MarketPoint> = load_series(path)?;
+        market.sort_by_key(|p| p.timestamp);
+        for point in market {
+            tracker.record_market(MarketSnapshot {
+                timestamp: Utc.timestamp_opt(point.timestamp, 0).unwrap(),
+                near_price: point.near_price,
+                staking_apy: point.staking_apy,
+                stable_apy: point.stable_apy,
+            });
+        }
+    }
+    let period_days: i64 = args.parsed("period-days")?.unwrap_or(7);
+
+    let mut value = initial_value;
+    let mut weights: HashMap<String, f64> = HashMap::new();
+    let mut apys: HashMap<String, f64> = HashMap::new();
+    let mut regime_steps: HashMap<MarketRegime, usize> = HashMap::new();
+    let mut previous = start;
+
+    for (&timestamp, pools) in &steps {
+        // Accrue yield on the allocation held since the previous step
+        let dt = (timestamp - previous) as f64 / YEAR_SECS;
+        let growth: f64 = weights
+            .iter()
+            .map(|(pool_id, weight)| weight * apys.get(pool_id).copied().unwrap_or(0.0) / 100.0 * dt)
+            .sum();
+        value *= 1.0 + growth;
+        previous = timestamp;
+
+        let mut by_category: HashMap<&str, Vec<PoolMetrics>> = HashMap::new();
+        for pool in pools {
+            apys.insert(pool.pool_id.clone(), pool.apy);
+            by_category.entry(&pool.category).or_default().push(pool.to_metrics());
+        }
+        for (category, metrics) in by_category {
+            optimizers
+                .entry(category.to_string())
+                .or_insert_with(|| YieldOptimizer::new(yield_config.clone(), alert_tx.clone()))
+                .update_pool_data(metrics)
+                .await;
+        }
+
+        let score = sentiment_at(&sentiment, timestamp);
+        let regime = classify_sentiment(score, &thresholds, emergency_threshold);
+        *regime_steps.entry(regime).or_default() += 1;
+
+        let mut target: HashMap<String, f64> = HashMap::new();
+        for (bucket, percentage) in regime_allocation(regime) {
+            if let Some(optimizer) = optimizers.get(&bucket) {
+                for allocation in optimizer.optimize_portfolio().await {
+                    *target.entry(allocation.pool_id).or_default() +=
+                        percentage as f64 / 100.0 * allocation.allocation_percentage;
+                }
+            }
+        }
+
+        // Same drift rule as execute_rebalance: only move when a pool is off by more than the threshold
+        let threshold = yield_config.rebalance_threshold / 100.0;
+        let drifted = target
+            .keys()
+            .chain(weights.keys())
+            .any(|pool_id| {
+                let current = weights.get(pool_id).copied().unwrap_or(0.0);
+                let wanted = target.get(pool_id).copied().unwrap_or(0.0);
+                (current - wanted).abs() > threshold
+            });
+
+        let action = if drifted {
+            let initial = weights.is_empty();
+            weights = target;
+            // The opening allocation is a deployment, not a rebalance
+            (!initial).then(|| format!("rebalance_{:?}", regime).to_lowercase())
+        } else {
+            None
+        };
+
+        if timestamp != start {
+            tracker.update_strategy_at(
+                STRATEGY_ID,
+                Utc.timestamp_opt(timestamp, 0).unwrap(),
+                value,
+                score as f32,
+                action,
+            );
+        }
+    }
+
+    let stats = tracker
+        .get_strategy_stats(STRATEGY_ID)
+        .ok_or_else(|| anyhow!("Strategy was not registered"))?;
+    let strategy = tracker
+        .get_strategy(STRATEGY_ID)
+        .ok_or_else(|| anyhow!("Strategy was not registered"))?;
+
+    let report = BacktestReport {
+        start,
+        end,
+        steps: steps.len(),
+        initial_value,
+        final_value: value,
+        rebalances: strategy.rebalance_count,
+        regime_steps,
+        stats,
+        benchmarks: strategy.comparison.clone(),
+        periods: tracker.period_report(STRATEGY_ID, chrono::Duration::days(period_days)),
+    };
+
+    let output = serde_json::to_string_pretty(&report)?;
+    println!("{}", output);
+    if let Some(path) = args.get("output") {
+        std::fs::write(path, &output).with_context(|| format!("writing {}", path))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,280 @@
+use ndarray::{Array2, CowArray, Ix2};
+use ort::{Environment, GraphOptimizationLevel, Session, SessionBuilder, Value};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+use crate::sentiment::SourceReading;
+
+const DEFAULT_BATCH_SIZE: usize = 32;
+const MAX_TOKENS: usize = 128;
+const MODEL_NAME: &str = "twitter-roberta-sentiment";
+
+/// Softmax output of the three-class model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClassProbabilities {
+    pub negative: f32,
+    pub neutral: f32,
+    pub positive: f32,
+}
+
+impl ClassProbabilities {
+    pub const NEUTRAL: Self = Self { negative: 0.0, neutral: 1.0, positive: 0.0 };
+
+    /// Logits in the model's label order: negative, neutral, positive.
+    pub fn from_logits(logits: [f32; 3]) -> Self {
+        let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let exp = logits.map(|l| (l - max).exp());
+        let sum: f32 = exp.iter().sum();
+        Self {
+            negative: exp[0] / sum,
+            neutral: exp[1] / sum,
+            positive: exp[2] / sum,
+        }
+    }
+
+    /// 0-100, where 50 is neutral; neutral probability mass pulls toward 50.
+    pub fn score(&self) -> f32 {
+        50.0 + 50.0 * (self.positive - self.negative)
+    }
+
+    pub fn confidence(&self) -> f32 {
+        self.negative.max(self.neutral).max(self.positive)
+    }
+}
+
+/// Raw logits for a batch of texts, one row per text.
+pub trait LogitsModel: Send + Sync {
+    fn logits(&self, texts: &[&str]) -> Result<Vec<[f32; 3]>, Box<dyn Error>>;
+}
+
+/// A sequence-classification model exported to ONNX with its `tokenizer.json`,
+/// run on CPU.
+pub struct OnnxModel {
+    session: Session,
+    tokenizer: Tokenizer,
+}
+
+impl OnnxModel {
+    /// Loads `model.onnx` and `tokenizer.json` from `dir`.
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let environment = Environment::builder().with_name("sentiment").build()?.into_arc();
+        let session = SessionBuilder::new(&environment)?
+            .with_optimization_level(GraphOptimizationLevel::Level3)?
+            .with_model_from_file(dir.join("model.onnx"))?;
+
+        let mut tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))
+            .map_err(|e| format!("Failed to load tokenizer: {}", e))?;
+        tokenizer
+            .with_padding(Some(PaddingParams::default()))
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_TOKENS,
+                ..Default::default()
+            }));
+
+        Ok(Self { session, tokenizer })
+    }
+}
+
+impl LogitsModel for OnnxModel {
+    fn logits(&self, texts: &[&str]) -> Result<Vec<[f32; 3]>, Box<dyn Error>> {
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(|e| format!("Failed to tokenize: {}", e))?;
+        let len = encodings.first().map_or(0, |e| e.len());
+
+        let mut ids = Vec::with_capacity(texts.len() * len);
+        let mut mask = Vec::with_capacity(texts.len() * len);
+        for encoding in &encodings {
+            ids.extend(encoding.get_ids().iter().map(|&id| id as i64));
+            mask.extend(encoding.get_attention_mask().iter().map(|&m| m as i64));
+        }
+        let ids = CowArray::from(Array2::from_shape_vec((texts.len(), len), ids)?.into_dyn());
+        let mask = CowArray::from(Array2::from_shape_vec((texts.len(), len), mask)?.into_dyn());
+
+        let outputs = self.session.run(vec![
+            Value::from_array(self.session.allocator(), &ids)?,
+            Value::from_array(self.session.allocator(), &mask)?,
+        ])?;
+        let logits = outputs[0].try_extract::<f32>()?;
+        let logits = logits.view().into_dimensionality::<Ix2>()?;
+
+        Ok(logits
+            .rows()
+            .into_iter()
+            .map(|row| [row[0], row[1], row[2]])
+            .collect())
+    }
+}
+
+/// Where the model is cached: `SENTIMENT_MODEL_DIR`, or `~/.cache/yield-ai/twitter-roberta-sentiment`.
+pub fn default_model_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("SENTIMENT_MODEL_DIR") {
+        return PathBuf::from(dir);
+    }
+    let cache = std::env::var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|home| Path::new(&home).join(".cache")))
+        .unwrap_or_else(|_| PathBuf::from("."));
+    cache.join("yield-ai").join(MODEL_NAME)
+}
+
+/// Negative / neutral / positive classifier over short texts such as tweets.
+pub struct SentimentClassifier {
+    model: Box<dyn LogitsModel>,
+    batch_size: usize,
+}
+
+impl SentimentClassifier {
+    pub fn new(model: Box<dyn LogitsModel>, batch_size: usize) -> Self {
+        Self {
+            model,
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    /// Classifier over the ONNX model cached in `dir`.
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(Box::new(OnnxModel::load(dir)?), DEFAULT_BATCH_SIZE))
+    }
+
+    /// Probabilities for each text, in order. Blank texts are neutral and never reach
+    /// the model; the rest run in batches of `batch_size`.
+    pub fn classify(&self, texts: &[String]) -> Result<Vec<ClassProbabilities>, Box<dyn Error>> {
+        let mut results = vec![ClassProbabilities::NEUTRAL; texts.len()];
+        let pending: Vec<(usize, &str)> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| (i, text.trim()))
+            .filter(|(_, text)| !text.is_empty())
+            .collect();
+
+        for batch in pending.chunks(self.batch_size) {
+            let inputs: Vec<&str> = batch.iter().map(|(_, text)| *text).collect();
+            let logits = self.model.logits(&inputs)?;
+            if logits.len() != batch.len() {
+                return Err(format!("Model returned {} rows for {} texts", logits.len(), batch.len()).into());
+            }
+            for ((i, _), row) in batch.iter().zip(logits) {
+                results[*i] = ClassProbabilities::from_logits(row);
+            }
+        }
+        Ok(results)
+    }
+
+    /// Mean score and confidence over `texts`, or `None` when none has any content.
+    pub fn analyze(&self, texts: &[String]) -> Result<Option<SourceReading>, Box<dyn Error>> {
+        self.analyze_weighted(texts, &vec![1.0; texts.len()])
+    }
+
+    /// Like `analyze`, with each text counted by its weight.
+    pub fn analyze_weighted(&self, texts: &[String], weights: &[f32]) -> Result<Option<SourceReading>, Box<dyn Error>> {
+        if weights.len() != texts.len() {
+            return Err(format!("{} weights for {} texts", weights.len(), texts.len()).into());
+        }
+
+        let mut score = 0.0;
+        let mut confidence = 0.0;
+        let mut total_weight = 0.0;
+        for ((probabilities, text), weight) in self.classify(texts)?.iter().zip(texts).zip(weights) {
+            if text.trim().is_empty() {
+                continue;
+            }
+            score += probabilities.score() * weight;
+            confidence += probabilities.confidence() * weight;
+            total_weight += weight;
+        }
+        if total_weight <= 0.0 {
+            return Ok(None);
+        }
+
+        Ok(Some(SourceReading {
+            score: score / total_weight,
+            confidence: confidence / total_weight,
+            filter_stats: None,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Positive for texts containing "moon", negative for "rug", neutral otherwise.
+    #[derive(Default)]
+    struct KeywordModel {
+        batches: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl LogitsModel for KeywordModel {
+        fn logits(&self, texts: &[&str]) -> Result<Vec<[f32; 3]>, Box<dyn Error>> {
+            self.batches.lock().unwrap().push(texts.len());
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    if text.contains("moon") {
+                        [-2.0, 0.0, 3.0]
+                    } else if text.contains("rug") {
+                        [3.0, 0.0, -2.0]
+                    } else {
+                        [0.0, 2.0, 0.0]
+                    }
+                })
+                .collect())
+        }
+    }
+
+    fn texts(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn softmax_gives_a_continuous_score() {
+        let p = ClassProbabilities::from_logits([0.0, 0.0, 0.0]);
+        assert!((p.positive - 1.0 / 3.0).abs() < 1e-6);
+        assert!((p.score() - 50.0).abs() < 1e-4);
+
+        let p = ClassProbabilities::from_logits([-2.0, 0.0, 3.0]);
+        assert!((p.negative + p.neutral + p.positive - 1.0).abs() < 1e-6);
+        assert!(p.score() > 90.0 && p.score() < 100.0);
+        assert_eq!(p.confidence(), p.positive);
+    }
+
+    #[test]
+    fn batches_texts_and_skips_blank_ones() {
+        let model = KeywordModel::default();
+        let batches = model.batches.clone();
+        let classifier = SentimentClassifier::new(Box::new(model), 2);
+
+        let results = classifier
+            .classify(&texts(&["NEAR to the moon", "  ", "rug pull?", "gm", "moon soon"]))
+            .unwrap();
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[1], ClassProbabilities::NEUTRAL);
+        assert!(results[0].score() > 90.0);
+        assert!(results[2].score() < 10.0);
+        assert_eq!(*batches.lock().unwrap(), vec![2, 2]);
+    }
+
+    #[test]
+    fn empty_input_has_no_reading() {
+        let model = KeywordModel::default();
+        let batches = model.batches.clone();
+        let classifier = SentimentClassifier::new(Box::new(model), 8);
+
+        assert!(classifier.analyze(&[]).unwrap().is_none());
+        assert!(classifier.analyze(&texts(&["", " "])).unwrap().is_none());
+        assert!(batches.lock().unwrap().is_empty());
+
+        let reading = classifier.analyze(&texts(&["to the moon", "rug", "gm"])).unwrap().unwrap();
+        assert!((reading.score - 50.0).abs() < 1e-3);
+
+        let weighted = classifier
+            .analyze_weighted(&texts(&["to the moon", "rug"]), &[3.0, 1.0])
+            .unwrap()
+            .unwrap();
+        assert!(weighted.score > 70.0);
+        assert!(classifier.analyze_weighted(&texts(&["gm"]), &[]).is_err());
+    }
+}
@@ -1,4 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::anomaly::AnomalyConfig;
+use crate::onchain::OnChainConfig;
+use crate::sentiment::SentimentSourceConfig;
+use crate::smoothing::SmoothingMethod;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct YieldConfig {
@@ -8,6 +13,40 @@ pub struct YieldConfig {
     pub preferred_assets: Vec<String>,
     pub rebalance_threshold: f64,
     pub max_allocation_per_pool: f64,
+    /// Maximum slippage accepted on AMM rebalance legs, in percent.
+    #[serde(default = "default_slippage_tolerance")]
+    pub slippage_tolerance: f64,
+    /// Minimum fraction of the portfolio in staking pools, honoured by the
+    /// mean-variance modes.
+    #[serde(default = "default_min_staking_ratio")]
+    pub min_staking_ratio: f64,
+    #[serde(default)]
+    pub optimization_mode: OptimizationMode,
+}
+
+fn default_slippage_tolerance() -> f64 {
+    0.5
+}
+
+fn default_min_staking_ratio() -> f64 {
+    0.2
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum OptimizationMode {
+    /// Rank pools by `apy * risk_score * chain_factor` and hand out fixed slices.
+    Heuristic,
+    /// Maximize expected APY minus `risk_aversion / 2` times the portfolio APY variance.
+    MeanVariance { risk_aversion: f64 },
+    /// Pick the efficient-frontier portfolio with the best (APY - risk free) / volatility.
+    MaxSharpe { risk_free_rate: f64 },
+}
+
+impl Default for OptimizationMode {
+    fn default() -> Self {
+        OptimizationMode::Heuristic
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -17,16 +56,117 @@ pub enum RiskLevel {
     High,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PoolMetrics {
     pub protocol: String,
     pub pool_id: String,
-    pub apy: f64,
+    /// APY in percent, or `None` while the adapter can't measure it yet (e.g. right
+    /// after a restart, before enough price or volume history has been seen).
+    pub apy: Option<f64>,
     pub tvl: u64,
     pub risk_score: f64,
     pub audit_status: bool,
     pub token_pair: (String, String),
     pub chain_factor: f64,
+    /// Labels set by the adapter (pair label, pool category) used for risk bucket matching.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Risk bucket from `yield_sources`, set by the `ProtocolRegistry`.
+    #[serde(default)]
+    pub risk_bucket: Option<RiskBucket>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskBucket {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RiskLevels {
+    #[serde(default)]
+    pub low: Vec<String>,
+    #[serde(default)]
+    pub medium: Vec<String>,
+    #[serde(default)]
+    pub high: Vec<String>,
+}
+
+impl RiskLevels {
+    /// First bucket listing the pool's id or one of its tags, checked from low to high.
+    pub fn bucket_for(&self, pool: &PoolMetrics) -> Option<RiskBucket> {
+        let matches = |entries: &Vec<String>| {
+            entries
+                .iter()
+                .any(|entry| *entry == pool.pool_id || pool.tags.contains(entry))
+        };
+
+        if matches(&self.low) {
+            Some(RiskBucket::Low)
+        } else if matches(&self.medium) {
+            Some(RiskBucket::Medium)
+        } else if matches(&self.high) {
+            Some(RiskBucket::High)
+        } else {
+            None
+        }
+    }
+}
+
+/// A single protocol entry under `yield_sources`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SourceConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Sources without a contract id have no adapter and are skipped.
+    #[serde(default)]
+    pub contract_id: Option<String>,
+    #[serde(default)]
+    pub tracked_pools: Vec<u64>,
+    /// Where LiNEAR appends its price snapshots, so its staking APY survives restarts.
+    #[serde(default)]
+    pub history_path: Option<String>,
+    #[serde(default)]
+    pub risk_levels: RiskLevels,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StakingSourcesConfig {
+    pub min_validator_fee: f64,
+    pub max_validator_fee: f64,
+    #[serde(default)]
+    pub liquid: BTreeMap<String, SourceConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct YieldSourcesConfig {
+    pub staking: StakingSourcesConfig,
+    #[serde(default)]
+    pub defi: BTreeMap<String, SourceConfig>,
+    /// USD price feed in the Ref indexer's `list-token-price` format.
+    #[serde(default = "default_price_feed")]
+    pub price_feed: String,
+}
+
+fn default_price_feed() -> String {
+    "https://indexer.ref.finance/list-token-price".to_string()
+}
+
+impl YieldSourcesConfig {
+    /// Enabled staking and DeFi sources, keyed by protocol name.
+    pub fn enabled_sources(&self) -> impl Iterator<Item = (&String, &SourceConfig)> {
+        self.staking
+            .liquid
+            .iter()
+            .chain(self.defi.iter())
+            .filter(|(_, source)| source.enabled)
+    }
 }
 
 impl YieldConfig {
@@ -44,6 +184,9 @@ impl YieldConfig {
             preferred_assets: vec!["NEAR".to_string(), "ETH".to_string(), "USDC".to_string()],
             rebalance_threshold: 2.0, // 2% difference triggers rebalance
             max_allocation_per_pool: max_allocation,
+            slippage_tolerance: default_slippage_tolerance(),
+            min_staking_ratio: default_min_staking_ratio(),
+            optimization_mode: OptimizationMode::Heuristic,
         }
     }
 
@@ -52,7 +195,8 @@ impl YieldConfig {
             return false;
         }
 
-        if pool.apy < self.min_apy {
+        // Pools whose APY is unknown are never allocated to
+        if pool.apy.map_or(true, |apy| apy < self.min_apy) {
             return false;
         }
 
@@ -63,11 +207,21 @@ impl YieldConfig {
             RiskLevel::High => 0.4,
         };
 
+        // Configured risk buckets cap what each tolerance may hold
+        let max_bucket = match self.risk_tolerance {
+            RiskLevel::Low => RiskBucket::Low,
+            RiskLevel::Moderate => RiskBucket::Medium,
+            RiskLevel::High => RiskBucket::High,
+        };
+        if pool.risk_bucket.map_or(false, |bucket| bucket > max_bucket) {
+            return false;
+        }
+
         pool.risk_score >= risk_threshold && pool.chain_factor >= 0.9 && pool.audit_status
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PortfolioAllocation {
     pub pool_id: String,
     pub protocol: String,
@@ -75,3 +229,167 @@ pub struct PortfolioAllocation {
     pub expected_apy: f64,
     pub risk_score: f64,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkflowConfig {
+    pub scraping: ScrapingConfig,
+    pub fallback: FallbackConfig,
+    pub rebalancing: RebalancingConfig,
+    pub safety: SafetyConfig,
+    pub contracts: ContractsConfig,
+    pub yield_sources: YieldSourcesConfig,
+    #[serde(default)]
+    pub execution: ExecutionConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScrapingConfig {
+    pub default_interval: u64,
+    pub volatile_interval: u64,
+    pub price_volatility_threshold: f64,
+    pub minimum_tweets: usize,
+    pub sentiment_thresholds: SentimentThresholds,
+    pub volume_thresholds: VolumeThresholds,
+    pub smoothing: SmoothingConfig,
+    /// Weight and timeout per sentiment source, keyed by source name.
+    #[serde(default)]
+    pub sources: BTreeMap<String, SentimentSourceConfig>,
+    #[serde(default)]
+    pub onchain: OnChainConfig,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SentimentThresholds {
+    pub bearish: u8,
+    pub bullish: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeThresholds {
+    pub min_24h: u64,
+    pub min_hourly: u64,
+}
+
+impl Default for VolumeThresholds {
+    fn default() -> Self {
+        Self {
+            min_24h: 1000,
+            min_hourly: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmoothingConfig {
+    /// Hours of samples averaged into one signal.
+    pub window_size: u64,
+    pub min_samples: usize,
+    #[serde(default)]
+    pub method: SmoothingMethod,
+}
+
+impl Default for SmoothingConfig {
+    fn default() -> Self {
+        Self {
+            window_size: 12,
+            min_samples: 6,
+            method: SmoothingMethod::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FallbackConfig {
+    pub primary: String,
+    pub secondary: String,
+    pub historical_window: u64,
+    /// SQLite file holding every sentiment reading, used for the historical average.
+    #[serde(default = "default_history_path")]
+    pub history_path: String,
+}
+
+/// Sentiment history path used when `fallback.history_path` is not set.
+pub fn default_history_path() -> String {
+    "data/sentiment.db".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebalancingConfig {
+    pub min_rebalance_interval: u64,
+    pub gas_price_threshold: u64,
+    pub slippage_tolerance: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SafetyConfig {
+    pub max_single_allocation: u8,
+    pub min_staking_ratio: u8,
+    pub emergency_threshold: u8,
+    /// Manipulation checks that hold back rebalances while they fire.
+    #[serde(default)]
+    pub anomaly: AnomalyConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContractsConfig {
+    pub proxy_deployment: ProxyDeploymentConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProxyDeploymentConfig {
+    pub initial_stake: u64,
+    pub max_gas: u64,
+    /// Account the `SafetyProxy` contract is deployed to; emergencies only reallocate without it.
+    #[serde(default)]
+    pub contract_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecutionConfig {
+    /// Record on-chain actions to the ledger instead of sending them.
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub ledger_path: Option<String>,
+    #[serde(default = "default_rpc_url")]
+    pub rpc_url: String,
+    /// NEAR credentials file (`account_id`, `public_key`, `private_key`) of the
+    /// account that holds the positions and signs every call.
+    #[serde(default)]
+    pub credentials_path: Option<String>,
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            ledger_path: None,
+            rpc_url: default_rpc_url(),
+            credentials_path: None,
+        }
+    }
+}
+
+fn default_rpc_url() -> String {
+    "https://rpc.mainnet.near.org".to_string()
+}
+
+pub fn load_config(path: &str) -> anyhow::Result<WorkflowConfig> {
+    Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_config_tracks_explicit_ref_pools() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../config/workflow-config.yaml");
+        let config = load_config(path).unwrap();
+
+        let ref_finance = &config.yield_sources.defi["ref_finance"];
+        assert!(ref_finance.enabled);
+        assert!(!ref_finance.tracked_pools.is_empty());
+        assert!(config.yield_sources.price_feed.starts_with("https://"));
+    }
+}
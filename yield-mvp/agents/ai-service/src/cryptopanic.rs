@@ -0,0 +1,315 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::Arc;
+use crate::sentiment::{NewsItem, SentimentSource, SourceReading};
+
+const DEFAULT_BASE_URL: &str = "https://cryptopanic.com/api/v1";
+const DEFAULT_MAX_PAGES: usize = 5;
+/// Votes needed before they count as much as the title does.
+const VOTE_SATURATION: f32 = 5.0;
+const TITLE_WEIGHT: f32 = 0.4;
+
+const BULLISH_TERMS: &[&str] = &[
+    "surge", "rall", "soar", "gain", "bull", "partner", "launch", "record", "upgrade",
+    "adopt", "growth", "breakout", "integrat", "listing", "approv", "rebound",
+];
+const BEARISH_TERMS: &[&str] = &[
+    "crash", "plunge", "drop", "bear", "hack", "exploit", "lawsuit", "sell-off", "selloff",
+    "dump", "outage", "delist", "scam", "decline", "fall", "halt", "sue",
+];
+
+#[derive(Debug, Deserialize)]
+struct PostsPage {
+    next: Option<String>,
+    results: Vec<Post>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Post {
+    pub title: String,
+    pub url: String,
+    #[serde(default)]
+    pub source: Option<PostSource>,
+    #[serde(default)]
+    pub currencies: Vec<PostCurrency>,
+    #[serde(default)]
+    pub votes: Votes,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PostSource {
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PostCurrency {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct Votes {
+    pub positive: u32,
+    pub negative: u32,
+    pub important: u32,
+    pub liked: u32,
+    pub disliked: u32,
+    pub toxic: u32,
+}
+
+/// Client for the CryptoPanic posts API.
+pub struct CryptoPanicClient {
+    http: reqwest::Client,
+    base_url: String,
+    auth_token: String,
+    currencies: Vec<String>,
+    max_pages: usize,
+}
+
+impl CryptoPanicClient {
+    /// Client for NEAR and AURORA posts.
+    pub fn new(auth_token: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            auth_token: auth_token.to_string(),
+            currencies: vec!["NEAR".to_string(), "AURORA".to_string()],
+            max_pages: DEFAULT_MAX_PAGES,
+        }
+    }
+
+    /// Client using `CRYPTOPANIC_API_KEY`, or `None` when it isn't set.
+    pub fn from_env() -> Option<Self> {
+        std::env::var("CRYPTOPANIC_API_KEY").ok().map(|key| Self::new(&key))
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_currencies(mut self, currencies: Vec<String>) -> Self {
+        self.currencies = currencies;
+        self
+    }
+
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Recent posts about the tracked currencies, newest first, following up to
+    /// `max_pages` pages and skipping URLs already seen.
+    pub async fn fetch_news(&self) -> Result<Vec<NewsItem>, Box<dyn Error>> {
+        let mut collector = NewsCollector::new(&self.currencies);
+        let mut url = Some(format!(
+            "{}/posts/?auth_token={}&currencies={}&public=true",
+            self.base_url,
+            self.auth_token,
+            self.currencies.join(","),
+        ));
+
+        for _ in 0..self.max_pages {
+            let Some(page_url) = url.take() else { break };
+            let page: PostsPage = self
+                .http
+                .get(&page_url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            collector.add_page(page.results);
+            url = page.next;
+        }
+
+        Ok(collector.into_items())
+    }
+}
+
+/// Accumulates posts across pages, keeping the first post per URL that mentions
+/// one of the tracked currencies.
+pub struct NewsCollector {
+    currencies: HashSet<String>,
+    seen: HashSet<String>,
+    items: Vec<NewsItem>,
+}
+
+impl NewsCollector {
+    pub fn new(currencies: &[String]) -> Self {
+        Self {
+            currencies: currencies.iter().map(|c| c.to_uppercase()).collect(),
+            seen: HashSet::new(),
+            items: Vec::new(),
+        }
+    }
+
+    pub fn add_page(&mut self, posts: Vec<Post>) {
+        for post in posts {
+            let relevant = post
+                .currencies
+                .iter()
+                .any(|c| self.currencies.contains(&c.code.to_uppercase()));
+            if !relevant || !self.seen.insert(post.url.clone()) {
+                continue;
+            }
+
+            self.items.push(NewsItem {
+                sentiment: score_post(&post.title, &post.votes),
+                source: post
+                    .source
+                    .map(|s| s.title)
+                    .unwrap_or_else(|| "CryptoPanic".to_string()),
+                title: post.title,
+                url: post.url,
+            });
+        }
+    }
+
+    pub fn into_items(self) -> Vec<NewsItem> {
+        self.items
+    }
+}
+
+/// Title polarity in [-1, 1] from bullish and bearish keywords; 0 when neither appears.
+pub fn classify_title(title: &str) -> f32 {
+    let title = title.to_lowercase();
+    let words: Vec<&str> = title
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+        .filter(|w| !w.is_empty())
+        .collect();
+    let count = |terms: &[&str]| {
+        words
+            .iter()
+            .filter(|w| terms.iter().any(|t| w.starts_with(t)))
+            .count() as f32
+    };
+
+    let bullish = count(BULLISH_TERMS);
+    let bearish = count(BEARISH_TERMS);
+    if bullish + bearish == 0.0 {
+        0.0
+    } else {
+        (bullish - bearish) / (bullish + bearish)
+    }
+}
+
+/// Sentiment (0-100) of a post. Community votes dominate once there are enough of
+/// them; with few votes the title classification carries the score. "Important"
+/// votes push the result further from neutral.
+pub fn score_post(title: &str, votes: &Votes) -> f32 {
+    let up = (votes.positive + votes.liked) as f32;
+    let down = (votes.negative + votes.disliked + votes.toxic) as f32;
+    let cast = up + down;
+
+    let vote_weight = (1.0 - TITLE_WEIGHT) * cast / (cast + VOTE_SATURATION);
+    let vote_polarity = if cast > 0.0 { (up - down) / cast } else { 0.0 };
+    let polarity = (vote_weight * vote_polarity + TITLE_WEIGHT * classify_title(title))
+        / (vote_weight + TITLE_WEIGHT);
+
+    let important = votes.important as f32;
+    let emphasis = 1.0 + 0.5 * important / (important + VOTE_SATURATION);
+    (50.0 + 50.0 * polarity * emphasis).clamp(0.0, 100.0)
+}
+
+/// Mean sentiment of `news`, or `None` when there is none.
+pub fn news_score(news: &[NewsItem]) -> Option<f32> {
+    if news.is_empty() {
+        return None;
+    }
+    Some(news.iter().map(|item| item.sentiment).sum::<f32>() / news.len() as f32)
+}
+
+/// News sentiment from CryptoPanic.
+pub struct CryptoPanicSource {
+    client: Arc<CryptoPanicClient>,
+}
+
+impl CryptoPanicSource {
+    pub fn new(client: Arc<CryptoPanicClient>) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl SentimentSource for CryptoPanicSource {
+    fn name(&self) -> &str {
+        "cryptopanic"
+    }
+
+    async fn fetch(&self) -> Result<SourceReading, Box<dyn Error>> {
+        let news = self.client.fetch_news().await?;
+        let score = news_score(&news).ok_or("No news to analyze")?;
+
+        // A handful of headlines is a weaker signal than a full page
+        Ok(SourceReading {
+            score,
+            confidence: 0.7 * (news.len() as f32 / 10.0).min(1.0),
+            filter_stats: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(title: &str, url: &str, currencies: &[&str], votes: Votes) -> Post {
+        Post {
+            title: title.to_string(),
+            url: url.to_string(),
+            source: None,
+            currencies: currencies.iter().map(|c| PostCurrency { code: c.to_string() }).collect(),
+            votes,
+        }
+    }
+
+    #[test]
+    fn classifies_titles_by_keywords() {
+        assert_eq!(classify_title("NEAR rallies after Aurora upgrade"), 1.0);
+        assert_eq!(classify_title("Bridge exploit: NEAR plunges"), -1.0);
+        assert_eq!(classify_title("NEAR Protocol weekly update"), 0.0);
+    }
+
+    #[test]
+    fn votes_outweigh_the_title_once_cast() {
+        let neutral = score_post("NEAR Protocol weekly update", &Votes::default());
+        assert_eq!(neutral, 50.0);
+
+        let title_only = score_post("NEAR rallies", &Votes::default());
+        assert_eq!(title_only, 100.0);
+
+        // Heavy downvoting turns a bullish headline bearish
+        let downvoted = score_post("NEAR rallies", &Votes { negative: 40, toxic: 5, ..Default::default() });
+        assert!(downvoted < 50.0);
+
+        let upvoted = score_post("NEAR Protocol weekly update", &Votes { positive: 10, ..Default::default() });
+        let important = score_post(
+            "NEAR Protocol weekly update",
+            &Votes { positive: 10, important: 10, ..Default::default() },
+        );
+        assert!(upvoted > 60.0);
+        assert!(important > upvoted);
+    }
+
+    #[test]
+    fn collector_filters_currencies_and_dedups_urls() {
+        let mut collector = NewsCollector::new(&["NEAR".to_string(), "AURORA".to_string()]);
+        collector.add_page(vec![
+            post("NEAR rallies", "https://cryptopanic.com/news/1", &["NEAR"], Votes::default()),
+            post("BTC dumps", "https://cryptopanic.com/news/2", &["BTC"], Votes::default()),
+        ]);
+        collector.add_page(vec![
+            post("NEAR rallies", "https://cryptopanic.com/news/1", &["NEAR"], Votes::default()),
+            post("Aurora outage", "https://cryptopanic.com/news/3", &["aurora", "ETH"], Votes::default()),
+        ]);
+
+        let news = collector.into_items();
+        assert_eq!(news.len(), 2);
+        assert_eq!(news[0].source, "CryptoPanic");
+        assert_eq!(news_score(&news), Some(50.0));
+        assert!(news_score(&[]).is_none());
+    }
+}
@@ -0,0 +1,116 @@
+use async_trait::async_trait;
+use near_sdk::json_types::U128;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use crate::rpc::{NearRpc, TxOutcome};
+
+/// An on-chain action that was recorded instead of sent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub timestamp: i64,
+    /// Component that would have acted, e.g. `SafetyProxy` or `ref_finance`.
+    pub actor: String,
+    pub contract_id: String,
+    pub method: String,
+    pub args: Value,
+    pub deposit: U128,
+    pub gas: u64,
+}
+
+/// Records every on-chain action taken in dry-run mode, in memory and optionally
+/// appended as JSON lines to `path`.
+pub struct DryRunLedger {
+    path: Option<String>,
+    entries: Mutex<Vec<LedgerEntry>>,
+}
+
+impl DryRunLedger {
+    pub fn new(path: Option<String>) -> Self {
+        Self {
+            path,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn record(
+        &self,
+        actor: &str,
+        contract_id: &str,
+        method: &str,
+        args: Value,
+        deposit: u128,
+        gas: u64,
+    ) -> std::io::Result<()> {
+        let entry = LedgerEntry {
+            timestamp: chrono::Utc::now().timestamp(),
+            actor: actor.to_string(),
+            contract_id: contract_id.to_string(),
+            method: method.to_string(),
+            args,
+            deposit: U128(deposit),
+            gas,
+        };
+        log::info!(
+            "[dry-run] {} would call {}.{} with {}",
+            entry.actor, entry.contract_id, entry.method, entry.args
+        );
+
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        }
+
+        self.entries.lock().unwrap().push(entry);
+        Ok(())
+    }
+
+    pub fn entries(&self) -> Vec<LedgerEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+}
+
+/// Forwards views to the real RPC and records change calls to the ledger.
+pub struct DryRunRpc {
+    inner: Arc<dyn NearRpc>,
+    ledger: Arc<DryRunLedger>,
+}
+
+impl DryRunRpc {
+    pub fn new(inner: Arc<dyn NearRpc>, ledger: Arc<DryRunLedger>) -> Self {
+        Self { inner, ledger }
+    }
+}
+
+#[async_trait]
+impl NearRpc for DryRunRpc {
+    fn account_id(&self) -> &str {
+        self.inner.account_id()
+    }
+
+    async fn view(&self, contract_id: &str, method: &str, args: Value) -> Result<Value, Box<dyn Error>> {
+        self.inner.view(contract_id, method, args).await
+    }
+
+    async fn call(
+        &self,
+        contract_id: &str,
+        method: &str,
+        args: Value,
+        deposit: u128,
+        gas: u64,
+    ) -> Result<TxOutcome, Box<dyn Error>> {
+        self.ledger
+            .record(self.inner.account_id(), contract_id, method, args, deposit, gas)?;
+
+        Ok(TxOutcome {
+            transaction_hash: format!("dry-run-{}", self.ledger.entries().len()),
+            gas_burnt: 0,
+            tokens_burnt: 0,
+            result: Value::Null,
+        })
+    }
+}
@@ -0,0 +1,19 @@
+pub mod anomaly;
+pub mod benchmark;
+pub mod classifier;
+pub mod config;
+pub mod cryptopanic;
+pub mod ledger;
+pub mod onchain;
+pub mod optimizer;
+pub mod performance;
+pub mod performance_store;
+pub mod protocols;
+pub mod rebalance;
+pub mod registry;
+pub mod rpc;
+pub mod sentiment;
+pub mod sentiment_store;
+pub mod smoothing;
+pub mod tweet_filter;
+pub mod workflow_manager;
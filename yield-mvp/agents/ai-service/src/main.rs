@@ -1,4 +1,4 @@
-use actix_web::{web, App, HttpResponse, HttpServer};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
 use serde::{Deserialize, Serialize};
 use tokio_tungstenite::connect_async;
 use futures_util::StreamExt;
@@ -13,11 +13,21 @@ use serde::{Deserialize, Serialize};
 use futures::StreamExt;
 use dotenv::dotenv;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use config::{YieldConfig, RiskLevel, PortfolioAllocation};
-use optimizer::{YieldOptimizer, AlertMessage};
+use tokio::sync::{Mutex, RwLock};
+use ai_service::config::{
+    default_history_path, load_config, SmoothingConfig, VolumeThresholds, WorkflowConfig, YieldConfig, RiskLevel, PortfolioAllocation,
+};
+use ai_service::optimizer::{YieldOptimizer, AlertMessage};
 use tokio::sync::broadcast;
 use masa::{MasaIntegration, MasaProfile, UserPreferences};
+use near_sdk::json_types::U128;
+use ai_service::ledger::DryRunLedger;
+use ai_service::benchmark::MarketSnapshot;
+use ai_service::performance::PerformanceTracker;
+use ai_service::performance_store::SqlitePerformanceStore;
+use ai_service::sentiment_store::{SentimentStore, SentimentWindowStats, SqliteSentimentStore};
+use ai_service::smoothing::{SentimentBuffer, SentimentSample, SentimentSignal, SignalGate, SmoothedPoint};
+use ai_service::workflow_manager::WorkflowManager;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct MarketData {
@@ -57,6 +67,18 @@ struct AppState {
     strategies: RwLock<Vec<Strategy>>,
     optimizer: Arc<Mutex<YieldOptimizer>>,
     alert_tx: broadcast::Sender<AlertMessage>,
+    /// Set when `execution.dry_run` is enabled; every contract call, the workflow's
+    /// included, is recorded here instead.
+    ledger: Option<Arc<DryRunLedger>>,
+    /// Shared with the workflow, which records the live portfolio under `STRATEGY_ID`.
+    performance: Arc<RwLock<PerformanceTracker>>,
+    /// Shared with the workflow, which gates its rebalances on the same signal.
+    sentiment_buffer: Arc<RwLock<SentimentBuffer>>,
+    /// Every recorded reading; drives the `trend` field of `/api/sentiment`.
+    sentiment_history: Option<Arc<dyn SentimentStore>>,
+    /// Bearer token required to post sentiment samples and market snapshots; posting
+    /// is disabled without one.
+    sample_token: Option<String>,
 }
 
 fn calculate_confidence_score(market_data: &MarketData) -> f64 {
@@ -141,10 +163,116 @@ async fn execute_strategy(strategy: &Strategy, sentiment: &SentimentData) {
 }
 
 async fn get_sentiment(state: web::Data<Arc<AppState>>) -> HttpResponse {
+    if let Some(history) = &state.sentiment_history {
+        match history.load_since(Utc::now().timestamp() - 86_400) {
+            Ok(readings) => {
+                if let Some(stats) = SentimentWindowStats::from_readings(&readings) {
+                    state.sentiment.write().await.trend = stats.trend_label().to_string();
+                }
+            }
+            Err(e) => error!("Failed to load sentiment history: {}", e),
+        }
+    }
+
     let sentiment = state.sentiment.read().await;
     HttpResponse::Ok().json(&*sentiment)
 }
 
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    #[serde(default = "default_history_hours")]
+    hours: i64,
+}
+
+fn default_history_hours() -> i64 {
+    24
+}
+
+async fn get_sentiment_history(
+    state: web::Data<Arc<AppState>>,
+    query: web::Query<HistoryQuery>,
+) -> HttpResponse {
+    let Some(history) = &state.sentiment_history else {
+        return HttpResponse::ServiceUnavailable().json("Sentiment history is not available");
+    };
+    if query.hours <= 0 {
+        return HttpResponse::BadRequest().json("hours must be positive");
+    }
+
+    match history.load_since(Utc::now().timestamp() - query.hours * 3600) {
+        Ok(readings) => HttpResponse::Ok().json(SentimentWindowStats::from_readings(&readings)),
+        Err(e) => HttpResponse::InternalServerError().json(e.to_string()),
+    }
+}
+
+/// Whether the request carries `Authorization: Bearer <token>`, compared in
+/// constant time.
+fn has_bearer_token(req: &HttpRequest, token: &str) -> bool {
+    let presented = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or("");
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Feeds a sample to the smoothing buffer behind the published score. Samples are
+/// not added to the sentiment history, which the workflow falls back on.
+async fn record_sentiment_sample(
+    req: HttpRequest,
+    state: web::Data<Arc<AppState>>,
+    sample: web::Json<SentimentSample>,
+) -> HttpResponse {
+    match &state.sample_token {
+        Some(token) if has_bearer_token(&req, token) => {}
+        Some(_) => return HttpResponse::Unauthorized().json("Invalid or missing bearer token"),
+        None => return HttpResponse::Forbidden().json("Sentiment sample posting is disabled"),
+    }
+
+    let sample = sample.into_inner();
+    let signal = {
+        let mut buffer = state.sentiment_buffer.write().await;
+        buffer.push(sample);
+        buffer.signal(Utc::now().timestamp())
+    };
+
+    // Only a signal that passed the sample and volume gates moves the published score
+    if let Ok(signal) = &signal {
+        let mut sentiment = state.sentiment.write().await;
+        sentiment.score = signal.score;
+        sentiment.last_update = Utc::now().to_rfc3339();
+    }
+    HttpResponse::Ok().json(signal.ok())
+}
+
+#[derive(Debug, Serialize)]
+struct SentimentSeries {
+    raw: Vec<SentimentSample>,
+    smoothed: Vec<SmoothedPoint>,
+    signal: Option<SentimentSignal>,
+    gate: Option<SignalGate>,
+}
+
+async fn get_sentiment_series(state: web::Data<Arc<AppState>>) -> HttpResponse {
+    let buffer = state.sentiment_buffer.read().await;
+    let (signal, gate) = match buffer.signal(Utc::now().timestamp()) {
+        Ok(signal) => (Some(signal), None),
+        Err(gate) => (None, Some(gate)),
+    };
+    HttpResponse::Ok().json(SentimentSeries {
+        raw: buffer.raw_series(),
+        smoothed: buffer.smoothed_series(),
+        signal,
+        gate,
+    })
+}
+
 async fn create_strategy(
     state: web::Data<Arc<AppState>>,
     strategy: web::Json<Strategy>,
@@ -230,6 +358,164 @@ async fn optimize_portfolio(
     })
 }
 
+#[derive(Deserialize)]
+struct RebalancePreviewRequest {
+    current_allocations: Vec<PortfolioAllocation>,
+    /// Defaults to the optimizer's current recommendation.
+    target_allocations: Option<Vec<PortfolioAllocation>>,
+    portfolio_value: U128,
+}
+
+async fn preview_rebalance(
+    data: web::Json<RebalancePreviewRequest>,
+    state: web::Data<Arc<AppState>>,
+) -> impl Responder {
+    let data = data.into_inner();
+    let optimizer = state.optimizer.lock().await;
+    let target_allocations = match data.target_allocations {
+        Some(target) => target,
+        None => optimizer.optimize_portfolio().await,
+    };
+
+    match optimizer
+        .execute_rebalance(&data.current_allocations, &target_allocations, data.portfolio_value)
+        .await
+    {
+        Ok(plan) => HttpResponse::Ok().json(plan),
+        Err(e) => HttpResponse::InternalServerError().json(format!("Failed to plan rebalance: {}", e)),
+    }
+}
+
+async fn get_dry_run_ledger(state: web::Data<Arc<AppState>>) -> HttpResponse {
+    match &state.ledger {
+        Some(ledger) => HttpResponse::Ok().json(ledger.entries()),
+        None => HttpResponse::NotFound().json("Dry-run mode is not enabled"),
+    }
+}
+
+async fn get_strategy_performance(
+    state: web::Data<Arc<AppState>>,
+    strategy_id: web::Path<String>,
+) -> HttpResponse {
+    let performance = state.performance.read().await;
+    match performance.get_strategy_stats(&strategy_id) {
+        Some(stats) => HttpResponse::Ok().json(stats),
+        None => HttpResponse::NotFound().json(format!("Unknown strategy {}", strategy_id)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PeriodReportQuery {
+    #[serde(default = "default_period_days")]
+    days: i64,
+}
+
+fn default_period_days() -> i64 {
+    7
+}
+
+async fn get_strategy_periods(
+    state: web::Data<Arc<AppState>>,
+    strategy_id: web::Path<String>,
+    query: web::Query<PeriodReportQuery>,
+) -> HttpResponse {
+    if query.days <= 0 {
+        return HttpResponse::BadRequest().json("days must be positive");
+    }
+
+    let performance = state.performance.read().await;
+    if performance.get_strategy(&strategy_id).is_none() {
+        return HttpResponse::NotFound().json(format!("Unknown strategy {}", strategy_id));
+    }
+    HttpResponse::Ok().json(performance.period_report(&strategy_id, chrono::Duration::days(query.days)))
+}
+
+/// Records a market snapshot alongside the ones the workflow takes every tick.
+async fn record_market_snapshot(
+    req: HttpRequest,
+    state: web::Data<Arc<AppState>>,
+    snapshot: web::Json<MarketSnapshot>,
+) -> HttpResponse {
+    match &state.sample_token {
+        Some(token) if has_bearer_token(&req, token) => {}
+        Some(_) => return HttpResponse::Unauthorized().json("Invalid or missing bearer token"),
+        None => return HttpResponse::Forbidden().json("Market snapshot posting is disabled"),
+    }
+
+    state.performance.write().await.record_market(snapshot.into_inner());
+    HttpResponse::Ok().finish()
+}
+
+fn open_performance_tracker() -> PerformanceTracker {
+    let path = env::var("PERFORMANCE_DB_PATH").unwrap_or_else(|_| "data/performance.db".to_string());
+    let tracker = SqlitePerformanceStore::open(&path)
+        .and_then(|store| PerformanceTracker::with_store(Box::new(store)));
+
+    match tracker {
+        Ok(tracker) => {
+            info!("Loaded strategy performance history from {}", path);
+            tracker
+        }
+        Err(e) => {
+            error!("Failed to open performance store {}: {}; history will not persist", path, e);
+            PerformanceTracker::new()
+        }
+    }
+}
+
+/// Opens the sentiment history the workflow writes to, at `fallback.history_path`.
+fn open_sentiment_history(config: Option<&WorkflowConfig>) -> Option<Arc<dyn SentimentStore>> {
+    let path = config.map_or_else(default_history_path, |config| config.fallback.history_path.clone());
+    match SqliteSentimentStore::open(&path) {
+        Ok(store) => Some(Arc::new(store)),
+        Err(e) => {
+            error!("Failed to open sentiment history {}: {}; trend will stay neutral", path, e);
+            None
+        }
+    }
+}
+
+fn open_workflow_config() -> Option<WorkflowConfig> {
+    let path = env::var("WORKFLOW_CONFIG_PATH").unwrap_or_else(|_| "config/workflow-config.yaml".to_string());
+    match load_config(&path) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            error!("Failed to load {}: {}; using default sentiment smoothing, workflow disabled", path, e);
+            None
+        }
+    }
+}
+
+fn open_sentiment_buffer(config: Option<&WorkflowConfig>) -> SentimentBuffer {
+    match config {
+        Some(config) => SentimentBuffer::new(&config.scraping.smoothing, &config.scraping.volume_thresholds),
+        None => SentimentBuffer::new(&SmoothingConfig::default(), &VolumeThresholds::default()),
+    }
+}
+
+/// Builds the workflow on the shared optimizer and tracker, loads the tracked pools
+/// so `/api/optimize` has data from the start, then runs the workflow loop.
+async fn start_workflow(
+    config: WorkflowConfig,
+    optimizer: Arc<Mutex<YieldOptimizer>>,
+    performance: Arc<RwLock<PerformanceTracker>>,
+    sentiment_buffer: Arc<RwLock<SentimentBuffer>>,
+    ledger: Option<Arc<DryRunLedger>>,
+) {
+    let workflow = match WorkflowManager::new(config, optimizer, performance, sentiment_buffer, ledger).await {
+        Ok(workflow) => workflow,
+        Err(e) => {
+            error!("Failed to start workflow: {}", e);
+            return;
+        }
+    };
+
+    workflow.refresh_pools().await;
+    if let Err(e) = workflow.start().await {
+        error!("Workflow stopped: {}", e);
+    }
+}
+
 async fn subscribe_alerts(state: web::Data<Arc<AppState>>) -> impl Responder {
     let mut rx = state.alert_tx.subscribe();
     
@@ -249,7 +535,8 @@ struct SafetyStrategyRequest {
     near_wallet: String,
     twitter_handle: String,
     sentiment_threshold: f32,
-    rebalance_percentage: f32,
+    /// Minimum pool APY the agent accepts, in basis points.
+    min_apy: u64,
 }
 
 #[derive(Serialize)]
@@ -263,6 +550,10 @@ async fn deploy_safety_strategy(
     data: web::Json<SafetyStrategyRequest>,
     state: web::Data<Arc<AppState>>,
 ) -> impl Responder {
+    if data.min_apy > 10_000 {
+        return HttpResponse::BadRequest().json("min_apy is in basis points and cannot exceed 10000");
+    }
+
     // Initialize Masa integration
     let masa = MasaIntegration::new(
         std::env::var("MASA_API_KEY").expect("MASA_API_KEY must be set"),
@@ -278,8 +569,8 @@ async fn deploy_safety_strategy(
     // Deploy AutoRebalanceAgent contract
     let contract_address = match deploy_auto_rebalance_contract(
         &data.near_wallet,
-        data.sentiment_threshold,
-        data.rebalance_percentage,
+        data.min_apy,
+        state.ledger.as_deref(),
     ).await {
         Ok(address) => address,
         Err(e) => return HttpResponse::InternalServerError().json(format!("Failed to deploy contract: {}", e)),
@@ -305,13 +596,29 @@ async fn deploy_safety_strategy(
 
 async fn deploy_auto_rebalance_contract(
     owner_id: &str,
-    sentiment_threshold: f32,
-    rebalance_percentage: f32,
+    min_apy: u64,
+    ledger: Option<&DryRunLedger>,
 ) -> Result<String, Box<dyn std::error::Error>> {
+    let contract_address = format!("auto-rebalance-{}.near", uuid::Uuid::new_v4());
+
+    if let Some(ledger) = ledger {
+        // `AutoRebalanceAgent::new(args: InitArgs)`
+        ledger.record(
+            "AutoRebalanceAgent",
+            &contract_address,
+            "new",
+            serde_json::json!({
+                "args": { "owner_id": owner_id, "min_apy": min_apy },
+            }),
+            0,
+            0,
+        )?;
+        return Ok(contract_address);
+    }
+
     // Deploy contract using near_sdk_sim or actual NEAR connection
     // This is a placeholder for actual deployment logic
-    let contract_address = format!("auto-rebalance-{}.near", uuid::Uuid::new_v4());
-    
+
     Ok(contract_address)
 }
 
@@ -331,7 +638,7 @@ async fn monitor_sentiment(
             Ok(sentiment) => {
                 if sentiment < threshold {
                     // Call contract to execute rebalancing
-                    if let Err(e) = execute_rebalance(&contract_address, sentiment).await {
+                    if let Err(e) = execute_rebalance(&contract_address, sentiment, state.ledger.as_deref()).await {
                         eprintln!("Failed to execute rebalance: {}", e);
                     }
 
@@ -348,7 +655,23 @@ async fn monitor_sentiment(
     }
 }
 
-async fn execute_rebalance(contract_address: &str, sentiment: f32) -> Result<(), Box<dyn std::error::Error>> {
+async fn execute_rebalance(
+    contract_address: &str,
+    sentiment: f32,
+    ledger: Option<&DryRunLedger>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(ledger) = ledger {
+        ledger.record(
+            "AutoRebalanceAgent",
+            contract_address,
+            "check_and_rebalance",
+            serde_json::json!({ "sentiment": sentiment }),
+            0,
+            0,
+        )?;
+        return Ok(());
+    }
+
     // Execute contract call using near_sdk or RPC
     // This is a placeholder for actual contract interaction
     println!("Executing rebalance for contract {} with sentiment {}", contract_address, sentiment);
@@ -366,15 +689,27 @@ async fn main() -> std::io::Result<()> {
 
     info!("Starting AI service on {}", bind_address);
 
+    let workflow_config = open_workflow_config();
+    let ledger = match &workflow_config {
+        Some(config) if config.execution.dry_run => {
+            info!("Dry-run mode enabled: contract calls will be recorded, not sent");
+            Some(Arc::new(DryRunLedger::new(config.execution.ledger_path.clone())))
+        }
+        _ => None,
+    };
+
     let (alert_tx, _) = broadcast::channel(100);
     let alert_tx_clone = alert_tx.clone();
 
     // Initialize optimizer with moderate risk profile
-    let optimizer = YieldOptimizer::new(
-        YieldConfig::new(RiskLevel::Moderate),
-        alert_tx,
-    );
+    let mut yield_config = YieldConfig::new(RiskLevel::Moderate);
+    if let Some(config) = &workflow_config {
+        yield_config.slippage_tolerance = config.rebalancing.slippage_tolerance;
+    }
+    let optimizer = YieldOptimizer::new(yield_config, alert_tx);
     let optimizer = Arc::new(Mutex::new(optimizer));
+    let performance = Arc::new(RwLock::new(open_performance_tracker()));
+    let sentiment_buffer = Arc::new(RwLock::new(open_sentiment_buffer(workflow_config.as_ref())));
 
     let state = web::Data::new(Arc::new(AppState {
         sentiment: RwLock::new(SentimentData {
@@ -385,7 +720,36 @@ async fn main() -> std::io::Result<()> {
         strategies: RwLock::new(Vec::new()),
         optimizer: optimizer.clone(),
         alert_tx: alert_tx_clone,
+        ledger: ledger.clone(),
+        performance: performance.clone(),
+        sentiment_buffer: sentiment_buffer.clone(),
+        sentiment_history: open_sentiment_history(workflow_config.as_ref()),
+        sample_token: env::var("SENTIMENT_SAMPLE_TOKEN").ok().filter(|token| !token.is_empty()),
     }));
+
+    // Compact old performance history once an hour
+    let retention_days: i64 = env::var("PERFORMANCE_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(30);
+    let state_clone = state.clone();
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_secs(3600)).await;
+            let removed = state_clone.performance.write().await.compact(
+                Utc::now(),
+                chrono::Duration::days(retention_days),
+                chrono::Duration::hours(1),
+            );
+            if removed > 0 {
+                info!("Compacted {} performance points", removed);
+            }
+        }
+    });
+    
+    if let Some(config) = workflow_config {
+        tokio::spawn(start_workflow(config, optimizer, performance, sentiment_buffer, ledger));
+    }
     
     let state_clone = state.clone();
     tokio::spawn(async move {
@@ -404,8 +768,16 @@ async fn main() -> std::io::Result<()> {
             .app_data(state.clone())
             .service(web::resource("/analyze").route(web::post().to(process_market_data)))
             .service(web::resource("/api/sentiment").route(web::get().to(get_sentiment)))
+            .service(web::resource("/api/sentiment/samples").route(web::post().to(record_sentiment_sample)))
+            .service(web::resource("/api/sentiment/series").route(web::get().to(get_sentiment_series)))
+            .service(web::resource("/api/sentiment/history").route(web::get().to(get_sentiment_history)))
             .service(web::resource("/api/strategy").route(web::post().to(create_strategy)))
             .service(web::resource("/api/optimize").route(web::post().to(optimize_portfolio)))
+            .service(web::resource("/api/rebalance/preview").route(web::post().to(preview_rebalance)))
+            .service(web::resource("/api/dry-run/ledger").route(web::get().to(get_dry_run_ledger)))
+            .service(web::resource("/api/performance/market").route(web::post().to(record_market_snapshot)))
+            .service(web::resource("/api/performance/{strategy_id}").route(web::get().to(get_strategy_performance)))
+            .service(web::resource("/api/performance/{strategy_id}/periods").route(web::get().to(get_strategy_periods)))
             .service(web::resource("/api/alerts").route(web::get().to(subscribe_alerts)))
             .service(web::resource("/api/deploy-safety-strategy").route(web::post().to(deploy_safety_strategy)))
     })
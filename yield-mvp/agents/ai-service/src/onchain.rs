@@ -0,0 +1,322 @@
+use async_trait::async_trait;
+use near_sdk::json_types::U128;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use crate::registry::ProtocolRegistry;
+use crate::rpc::NearRpc;
+use crate::sentiment::{SentimentSource, SourceReading};
+
+const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
+/// Snapshots kept by the RPC provider; a week at the default 5 minute polling.
+const MAX_RPC_HISTORY: usize = 2016;
+
+// Share of the on-chain score per component, and the change (%) that moves a
+// component most of the way from neutral to its extreme.
+const TVL_WEIGHT: f64 = 0.35;
+const TVL_SCALE: f64 = 10.0;
+const STAKING_WEIGHT: f64 = 0.25;
+const STAKING_SCALE: f64 = 2.0;
+const ACCOUNTS_WEIGHT: f64 = 0.25;
+const ACCOUNTS_SCALE: f64 = 20.0;
+const TRANSFERS_WEIGHT: f64 = 0.15;
+const TRANSFERS_SCALE: f64 = 50.0;
+
+/// One observation of NEAR network activity. Metrics a provider can't see are `None`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainMetrics {
+    pub timestamp: i64,
+    /// Combined TVL of the tracked pools, in USD.
+    #[serde(default)]
+    pub tvl_usd: Option<f64>,
+    /// NEAR staked across the tracked staking pools.
+    #[serde(default)]
+    pub total_staked: Option<f64>,
+    /// Distinct accounts that signed a transaction in the preceding day.
+    #[serde(default)]
+    pub active_accounts: Option<u64>,
+    /// NEAR moved in large transfers during the preceding day.
+    #[serde(default)]
+    pub large_transfer_volume: Option<f64>,
+}
+
+#[async_trait]
+pub trait ChainMetricsProvider: Send + Sync {
+    /// Snapshots at or after `since` (unix seconds), oldest first.
+    async fn history(&self, since: i64) -> Result<Vec<ChainMetrics>, Box<dyn Error>>;
+}
+
+/// Reads snapshots exported by an indexer job as a JSON array of `ChainMetrics`.
+pub struct IndexerDump {
+    path: String,
+}
+
+impl IndexerDump {
+    pub fn new(path: &str) -> Self {
+        Self { path: path.to_string() }
+    }
+}
+
+#[async_trait]
+impl ChainMetricsProvider for IndexerDump {
+    async fn history(&self, since: i64) -> Result<Vec<ChainMetrics>, Box<dyn Error>> {
+        let contents = tokio::fs::read_to_string(&self.path).await?;
+        let mut snapshots: Vec<ChainMetrics> = serde_json::from_str(&contents)?;
+        snapshots.retain(|s| s.timestamp >= since);
+        snapshots.sort_by_key(|s| s.timestamp);
+        Ok(snapshots)
+    }
+}
+
+/// Samples TVL from the protocol adapters and staked NEAR from staking pool contracts
+/// on every call. JSON-RPC can't cheaply count accounts or transfers, so those stay
+/// empty and drop out of the score.
+pub struct RpcChainMetrics {
+    registry: Arc<ProtocolRegistry>,
+    rpc: Arc<dyn NearRpc>,
+    staking_pools: Vec<String>,
+    history: Mutex<Vec<ChainMetrics>>,
+}
+
+impl RpcChainMetrics {
+    pub fn new(registry: Arc<ProtocolRegistry>, rpc: Arc<dyn NearRpc>, staking_pools: Vec<String>) -> Self {
+        Self {
+            registry,
+            rpc,
+            staking_pools,
+            history: Mutex::new(Vec::new()),
+        }
+    }
+
+    async fn sample(&self) -> Result<ChainMetrics, Box<dyn Error>> {
+        let pools = self.registry.get_all_pools().await;
+        let tvl_usd = if pools.is_empty() {
+            None
+        } else {
+            Some(pools.iter().map(|p| p.tvl as f64).sum())
+        };
+
+        let mut total_staked = None;
+        for pool in &self.staking_pools {
+            let staked: U128 = serde_json::from_value(
+                self.rpc.view(pool, "get_total_staked_balance", json!({})).await?,
+            )?;
+            *total_staked.get_or_insert(0.0) += staked.0 as f64 / ONE_NEAR as f64;
+        }
+
+        Ok(ChainMetrics {
+            timestamp: chrono::Utc::now().timestamp(),
+            tvl_usd,
+            total_staked,
+            active_accounts: None,
+            large_transfer_volume: None,
+        })
+    }
+}
+
+#[async_trait]
+impl ChainMetricsProvider for RpcChainMetrics {
+    async fn history(&self, since: i64) -> Result<Vec<ChainMetrics>, Box<dyn Error>> {
+        let snapshot = self.sample().await?;
+
+        let mut history = self.history.lock().unwrap();
+        history.push(snapshot);
+        if history.len() > MAX_RPC_HISTORY {
+            let excess = history.len() - MAX_RPC_HISTORY;
+            history.drain(..excess);
+        }
+        Ok(history.iter().filter(|s| s.timestamp >= since).cloned().collect())
+    }
+}
+
+/// `scraping.onchain` in the workflow config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnChainConfig {
+    /// Indexer export to read metrics from.
+    #[serde(default)]
+    pub indexer_dump: Option<String>,
+    #[serde(default = "default_lookback_hours")]
+    pub lookback_hours: u64,
+}
+
+fn default_lookback_hours() -> u64 {
+    24
+}
+
+impl Default for OnChainConfig {
+    fn default() -> Self {
+        Self {
+            indexer_dump: None,
+            lookback_hours: default_lookback_hours(),
+        }
+    }
+}
+
+/// Sentiment from the change in network activity over the lookback window.
+pub struct OnChainSource {
+    provider: Box<dyn ChainMetricsProvider>,
+    lookback_secs: i64,
+}
+
+impl OnChainSource {
+    pub fn new(provider: Box<dyn ChainMetricsProvider>, lookback_hours: u64) -> Self {
+        Self {
+            provider,
+            lookback_secs: lookback_hours as i64 * 3600,
+        }
+    }
+}
+
+#[async_trait]
+impl SentimentSource for OnChainSource {
+    fn name(&self) -> &str {
+        "onchain"
+    }
+
+    async fn fetch(&self) -> Result<SourceReading, Box<dyn Error>> {
+        // Twice the lookback so there is a baseline snapshot before the window
+        let since = chrono::Utc::now().timestamp() - 2 * self.lookback_secs;
+        let history = self.provider.history(since).await?;
+        score_metrics(&history, self.lookback_secs).ok_or_else(|| "Not enough on-chain history to score".into())
+    }
+}
+
+/// Scores the latest snapshot against the last one at least `lookback_secs` older
+/// (or the oldest available). Rising TVL, staking and activity read as bullish; a
+/// jump in large transfers reads as whales moving funds and is bearish. Each
+/// component maps its % change onto 0-100 with `tanh`, so 50 means no change.
+pub fn score_metrics(history: &[ChainMetrics], lookback_secs: i64) -> Option<SourceReading> {
+    let latest = history.last()?;
+    let cutoff = latest.timestamp - lookback_secs;
+    let baseline = history
+        .iter()
+        .rev()
+        .find(|s| s.timestamp <= cutoff)
+        .or_else(|| history.first())
+        .filter(|s| s.timestamp < latest.timestamp)?;
+
+    let change = |then: Option<f64>, now: Option<f64>| match (then, now) {
+        (Some(then), Some(now)) if then > 0.0 => Some((now - then) / then * 100.0),
+        _ => None,
+    };
+    let components = [
+        (change(baseline.tvl_usd, latest.tvl_usd), TVL_WEIGHT, TVL_SCALE),
+        (change(baseline.total_staked, latest.total_staked), STAKING_WEIGHT, STAKING_SCALE),
+        (
+            change(
+                baseline.active_accounts.map(|a| a as f64),
+                latest.active_accounts.map(|a| a as f64),
+            ),
+            ACCOUNTS_WEIGHT,
+            ACCOUNTS_SCALE,
+        ),
+        (
+            change(baseline.large_transfer_volume, latest.large_transfer_volume).map(|c| -c),
+            TRANSFERS_WEIGHT,
+            TRANSFERS_SCALE,
+        ),
+    ];
+
+    let mut score = 0.0;
+    let mut weight = 0.0;
+    for (change, component_weight, scale) in components {
+        if let Some(change) = change {
+            score += (50.0 + 50.0 * (change / scale).tanh()) * component_weight;
+            weight += component_weight;
+        }
+    }
+    if weight == 0.0 {
+        return None;
+    }
+
+    // Confidence reflects how much of the signal could be measured
+    Some(SourceReading {
+        score: (score / weight) as f32,
+        confidence: (0.9 * weight) as f32,
+        filter_stats: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RiskLevels;
+    use crate::protocols::{Linear, TokenBook};
+    use crate::rpc::RecordedRpc;
+
+    const DAY: i64 = 86_400;
+
+    fn metrics(timestamp: i64, tvl: f64, staked: f64, accounts: u64, transfers: f64) -> ChainMetrics {
+        ChainMetrics {
+            timestamp,
+            tvl_usd: Some(tvl),
+            total_staked: Some(staked),
+            active_accounts: Some(accounts),
+            large_transfer_volume: Some(transfers),
+        }
+    }
+
+    #[test]
+    fn unchanged_activity_is_neutral() {
+        let history = vec![metrics(0, 1e8, 4e8, 50_000, 1e6), metrics(DAY, 1e8, 4e8, 50_000, 1e6)];
+        let reading = score_metrics(&history, DAY).unwrap();
+        assert!((reading.score - 50.0).abs() < 1e-4);
+        assert!((reading.confidence - 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn growth_is_bullish_and_transfer_spikes_are_bearish() {
+        let growth = vec![metrics(0, 1e8, 4e8, 50_000, 1e6), metrics(DAY, 1.1e8, 4.1e8, 60_000, 1e6)];
+        assert!(score_metrics(&growth, DAY).unwrap().score > 70.0);
+
+        let dumping = vec![metrics(0, 1e8, 4e8, 50_000, 1e6), metrics(DAY, 1e8, 4e8, 50_000, 3e6)];
+        assert!(score_metrics(&dumping, DAY).unwrap().score < 50.0);
+    }
+
+    #[test]
+    fn compares_against_the_snapshot_before_the_window() {
+        let history = vec![
+            metrics(0, 1e8, 4e8, 50_000, 1e6),
+            metrics(DAY / 2, 0.5e8, 4e8, 50_000, 1e6),
+            metrics(DAY + DAY / 2, 1e8, 4e8, 50_000, 1e6),
+        ];
+        // The baseline is the snapshot at day 0, not the dip at half a day
+        assert!((score_metrics(&history, DAY).unwrap().score - 50.0).abs() < 1e-4);
+        assert!(score_metrics(&history[..1], DAY).is_none());
+    }
+
+    #[tokio::test]
+    async fn rpc_provider_scores_tvl_and_staking_only() {
+        let rpc = Arc::new(
+            RecordedRpc::new("vault.near")
+                .with_view("aurora.pool.near", "get_total_staked_balance", None, json!("25000000000000000000000000000000"))
+                .with_view(
+                    "linear-protocol.near",
+                    "get_summary",
+                    None,
+                    json!({ "total_staked_near_amount": "0", "ft_price": "1000000000000000000000000" }),
+                ),
+        );
+        let mut registry = ProtocolRegistry::new();
+        registry.register(
+            "linear",
+            Box::new(Linear::new("linear-protocol.near", rpc.clone(), Arc::new(TokenBook::default()))),
+            RiskLevels::default(),
+        );
+        let provider = RpcChainMetrics::new(Arc::new(registry), rpc, vec!["aurora.pool.near".to_string()]);
+
+        let history = provider.history(0).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].total_staked, Some(25_000_000.0));
+        assert_eq!(history[0].tvl_usd, Some(0.0));
+        assert!(history[0].active_accounts.is_none());
+
+        // Partial metrics lower confidence to the measurable share
+        let mut second = history[0].clone();
+        second.timestamp += DAY;
+        second.tvl_usd = Some(1.0);
+        let reading = score_metrics(&[history[0].clone(), second], DAY).unwrap();
+        assert!((reading.confidence - 0.9 * STAKING_WEIGHT as f32).abs() < 1e-6);
+    }
+}
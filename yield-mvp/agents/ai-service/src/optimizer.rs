@@ -1,5 +1,7 @@
-use crate::config::{PoolMetrics, PortfolioAllocation, RiskLevel, YieldConfig};
-use std::collections::HashMap;
+use crate::config::{OptimizationMode, PoolMetrics, PortfolioAllocation, RiskLevel, YieldConfig};
+use crate::rebalance::{LegAction, RebalanceLeg, RebalancePlan};
+use crate::registry::ProtocolRegistry;
+use std::collections::{HashMap, VecDeque};
 use tokio::sync::broadcast;
 use near_sdk::json_types::U128;
 use std::error::Error;
@@ -7,9 +9,20 @@ use std::error::Error;
 pub struct YieldOptimizer {
     config: YieldConfig,
     pool_data: HashMap<String, PoolMetrics>,
+    apy_history: HashMap<String, VecDeque<f64>>,
     alert_tx: broadcast::Sender<AlertMessage>,
 }
 
+const MAX_APY_HISTORY: usize = 720;
+/// Samples needed before a pool's own APY history is trusted for variance.
+const MIN_APY_HISTORY: usize = 3;
+const FRANK_WOLFE_ITERATIONS: usize = 500;
+const MIN_WEIGHT: f64 = 1e-4;
+
+/// Protocol of the `PortfolioAllocation` for funds idle in the signer's wallet;
+/// its pool id is the token held.
+pub const WALLET: &str = "wallet";
+
 #[derive(Clone, Debug)]
 pub enum AlertMessage {
     ApyDrop { pool_id: String, old_apy: f64, new_apy: f64 },
@@ -23,6 +36,7 @@ impl YieldOptimizer {
         Self {
             config,
             pool_data: HashMap::new(),
+            apy_history: HashMap::new(),
             alert_tx,
         }
     }
@@ -31,12 +45,14 @@ impl YieldOptimizer {
         for pool in pools {
             if let Some(old_pool) = self.pool_data.get(&pool.pool_id) {
                 // Check for significant APY drops
-                if (old_pool.apy - pool.apy) > 2.0 {
-                    let _ = self.alert_tx.send(AlertMessage::ApyDrop {
-                        pool_id: pool.pool_id.clone(),
-                        old_apy: old_pool.apy,
-                        new_apy: pool.apy,
-                    });
+                if let (Some(old_apy), Some(new_apy)) = (old_pool.apy, pool.apy) {
+                    if (old_apy - new_apy) > 2.0 {
+                        let _ = self.alert_tx.send(AlertMessage::ApyDrop {
+                            pool_id: pool.pool_id.clone(),
+                            old_apy,
+                            new_apy,
+                        });
+                    }
                 }
 
                 // Monitor risk changes
@@ -48,21 +64,117 @@ impl YieldOptimizer {
                 }
             }
 
+            if let Some(apy) = pool.apy {
+                let history = self.apy_history.entry(pool.pool_id.clone()).or_default();
+                history.push_back(apy);
+                if history.len() > MAX_APY_HISTORY {
+                    history.pop_front();
+                }
+            }
+
             self.pool_data.insert(pool.pool_id.clone(), pool);
         }
     }
 
+    /// Every pool seen so far, including ones the config currently filters out.
+    pub fn pools(&self) -> Vec<PoolMetrics> {
+        self.pool_data.values().cloned().collect()
+    }
+
+    pub fn risk_tolerance(&self) -> &RiskLevel {
+        &self.config.risk_tolerance
+    }
+
+    /// Moves the risk tolerance one level up, if it isn't `High` already.
+    pub fn increase_risk_tolerance(&mut self) {
+        self.config.risk_tolerance = match self.config.risk_tolerance {
+            RiskLevel::Low => RiskLevel::Moderate,
+            RiskLevel::Moderate | RiskLevel::High => RiskLevel::High,
+        };
+    }
+
+    /// Moves the risk tolerance one level down, if it isn't `Low` already.
+    pub fn decrease_risk_tolerance(&mut self) {
+        self.config.risk_tolerance = match self.config.risk_tolerance {
+            RiskLevel::High => RiskLevel::Moderate,
+            RiskLevel::Moderate | RiskLevel::Low => RiskLevel::Low,
+        };
+    }
+
+    /// Pulls fresh pool data from every protocol in the registry.
+    pub async fn refresh_pools(&mut self, registry: &ProtocolRegistry) {
+        let pools = registry.get_all_pools().await;
+        self.update_pool_data(pools).await;
+    }
+
     pub async fn optimize_portfolio(&self) -> Vec<PortfolioAllocation> {
-        let mut valid_pools: Vec<&PoolMetrics> = self.pool_data
+        self.optimize_pools(|_| true).await
+    }
+
+    /// Optimizes over the valid pools `include` accepts only, e.g. one strategy bucket.
+    pub async fn optimize_pools(&self, include: impl Fn(&PoolMetrics) -> bool) -> Vec<PortfolioAllocation> {
+        let mut allocations = self.allocate(include, self.config.max_allocation_per_pool);
+        normalize_capped(&mut allocations, self.config.max_allocation_per_pool);
+        allocations
+    }
+
+    /// Splits each bucket's percentage of `buckets` across the pools `in_bucket`
+    /// places in it, then applies `max_allocation_per_pool` once to the combined
+    /// weights, so a bucket with few pools isn't capped relative to its own share.
+    pub async fn optimize_buckets(
+        &self,
+        buckets: &HashMap<String, u8>,
+        in_bucket: impl Fn(&str, &PoolMetrics) -> bool,
+    ) -> Vec<PortfolioAllocation> {
+        let mut allocations: Vec<PortfolioAllocation> = Vec::new();
+        for (bucket, percentage) in buckets {
+            let mut weights = self.allocate(|pool| in_bucket(bucket, pool), 1.0);
+            normalize_capped(&mut weights, 1.0);
+            for mut allocation in weights {
+                allocation.allocation_percentage *= *percentage as f64 / 100.0;
+                match allocations.iter_mut().find(|a| a.pool_id == allocation.pool_id) {
+                    Some(existing) => existing.allocation_percentage += allocation.allocation_percentage,
+                    None => allocations.push(allocation),
+                }
+            }
+        }
+
+        let cap = self.config.max_allocation_per_pool;
+        for allocation in allocations.iter_mut() {
+            allocation.allocation_percentage = allocation.allocation_percentage.min(cap);
+        }
+        normalize_capped(&mut allocations, cap);
+        allocations
+    }
+
+    /// Raw weights over the valid pools `include` accepts, none above `cap`.
+    fn allocate(&self, include: impl Fn(&PoolMetrics) -> bool, cap: f64) -> Vec<PortfolioAllocation> {
+        let valid_pools: Vec<&PoolMetrics> = self.pool_data
             .values()
-            .filter(|pool| self.config.validate_pool(pool))
+            .filter(|pool| include(pool) && self.config.validate_pool(pool))
             .collect();
 
+        match &self.config.optimization_mode {
+            OptimizationMode::Heuristic => self.heuristic_allocation(valid_pools, cap),
+            OptimizationMode::MeanVariance { risk_aversion } => {
+                let model = self.return_model(&valid_pools);
+                let weights = self.mean_variance_weights(&valid_pools, &model, *risk_aversion, cap);
+                weights_to_allocations(&valid_pools, &weights)
+            }
+            OptimizationMode::MaxSharpe { risk_free_rate } => {
+                let model = self.return_model(&valid_pools);
+                let weights = self.max_sharpe_weights(&valid_pools, &model, *risk_free_rate, cap);
+                weights_to_allocations(&valid_pools, &weights)
+            }
+        }
+    }
+
+    fn heuristic_allocation(&self, mut valid_pools: Vec<&PoolMetrics>, cap: f64) -> Vec<PortfolioAllocation> {
         // Sort by risk-adjusted return (APY * risk_score * chain_factor)
         valid_pools.sort_by(|a, b| {
-            let a_score = a.apy * a.risk_score * a.chain_factor;
-            let b_score = b.apy * b.risk_score * b.chain_factor;
-            b_score.partial_cmp(&a_score).unwrap()
+            let a_score = known_apy(a) * a.risk_score * a.chain_factor;
+            let b_score = known_apy(b) * b.risk_score * b.chain_factor;
+            b_score.total_cmp(&a_score)
         });
 
         let mut allocations = Vec::new();
@@ -81,14 +193,14 @@ impl YieldOptimizer {
 
             // Adjust allocation based on remaining capacity
             allocation = allocation.min(1.0 - total_allocation);
-            allocation = allocation.min(self.config.max_allocation_per_pool);
+            allocation = allocation.min(cap);
 
             if allocation > 0.0 {
                 allocations.push(PortfolioAllocation {
                     pool_id: pool.pool_id.clone(),
                     protocol: pool.protocol.clone(),
                     allocation_percentage: allocation,
-                    expected_apy: pool.apy,
+                    expected_apy: known_apy(pool),
                     risk_score: pool.risk_score,
                 });
 
@@ -96,25 +208,198 @@ impl YieldOptimizer {
             }
         }
 
-        // Normalize allocations to ensure they sum to 100%
-        if !allocations.is_empty() {
-            let sum: f64 = allocations.iter().map(|a| a.allocation_percentage).sum();
-            for allocation in &mut allocations {
-                allocation.allocation_percentage /= sum;
+        allocations
+    }
+
+    /// Expected APY and APY covariance per pool, estimated over the most recent
+    /// samples all pools have in common. Pools without enough history fall back to
+    /// their current APY and a variance implied by their risk score.
+    fn return_model(&self, pools: &[&PoolMetrics]) -> ReturnModel {
+        let histories: Vec<Vec<f64>> = pools
+            .iter()
+            .map(|p| {
+                self.apy_history
+                    .get(&p.pool_id)
+                    .map(|h| h.iter().copied().collect())
+                    .unwrap_or_default()
+            })
+            .collect();
+        let common = histories.iter().map(|h| h.len()).min().unwrap_or(0);
+        let n = pools.len();
+
+        if common < MIN_APY_HISTORY {
+            let mean = pools.iter().map(|p| known_apy(p)).collect();
+            let mut covariance = vec![vec![0.0; n]; n];
+            for (i, pool) in pools.iter().enumerate() {
+                covariance[i][i] = (known_apy(pool) * (1.0 - pool.risk_score)).powi(2);
             }
+            return ReturnModel { mean, covariance };
         }
 
-        allocations
+        let tails: Vec<&[f64]> = histories.iter().map(|h| &h[h.len() - common..]).collect();
+        let mean: Vec<f64> = tails
+            .iter()
+            .map(|t| t.iter().sum::<f64>() / common as f64)
+            .collect();
+
+        let mut covariance = vec![vec![0.0; n]; n];
+        for i in 0..n {
+            for j in i..n {
+                let cov = (0..common)
+                    .map(|k| (tails[i][k] - mean[i]) * (tails[j][k] - mean[j]))
+                    .sum::<f64>()
+                    / (common - 1) as f64;
+                covariance[i][j] = cov;
+                covariance[j][i] = cov;
+            }
+        }
+
+        ReturnModel { mean, covariance }
+    }
+
+    /// Solves `max w·mu - risk_aversion / 2 * w'Σw` over the feasible set with
+    /// Frank-Wolfe and exact line search.
+    fn mean_variance_weights(&self, pools: &[&PoolMetrics], model: &ReturnModel, risk_aversion: f64, cap: f64) -> Vec<f64> {
+        let staking: Vec<bool> = pools.iter().map(|p| is_staking_pool(p)).collect();
+        let mut weights = self.best_vertex(&model.mean, &staking, cap);
+
+        for _ in 0..FRANK_WOLFE_ITERATIONS {
+            let sigma_w = model.cov_times(&weights);
+            let gradient: Vec<f64> = model
+                .mean
+                .iter()
+                .zip(&sigma_w)
+                .map(|(mu, sw)| mu - risk_aversion * sw)
+                .collect();
+
+            let vertex = self.best_vertex(&gradient, &staking, cap);
+            let direction: Vec<f64> = vertex.iter().zip(&weights).map(|(v, w)| v - w).collect();
+            let ascent = dot(&gradient, &direction);
+            if ascent <= 1e-9 {
+                break;
+            }
+
+            let curvature = risk_aversion * dot(&direction, &model.cov_times(&direction));
+            let step = if curvature > 0.0 {
+                (ascent / curvature).min(1.0)
+            } else {
+                1.0
+            };
+            for (w, d) in weights.iter_mut().zip(&direction) {
+                *w += step * d;
+            }
+        }
+
+        weights
+    }
+
+    /// Scans the efficient frontier and keeps the portfolio with the best Sharpe ratio.
+    fn max_sharpe_weights(&self, pools: &[&PoolMetrics], model: &ReturnModel, risk_free_rate: f64, cap: f64) -> Vec<f64> {
+        let mut best_weights = Vec::new();
+        let mut best_sharpe = f64::NEG_INFINITY;
+
+        for step in -6..=6 {
+            let risk_aversion = 10f64.powf(step as f64 / 2.0);
+            let weights = self.mean_variance_weights(pools, model, risk_aversion, cap);
+            let volatility = dot(&weights, &model.cov_times(&weights)).sqrt();
+            let excess = dot(&weights, &model.mean) - risk_free_rate;
+            let sharpe = excess / volatility.max(1e-9);
+
+            if sharpe > best_sharpe {
+                best_sharpe = sharpe;
+                best_weights = weights;
+            }
+        }
+
+        best_weights
     }
 
+    /// Feasible allocation maximizing `score·w`: the staking floor is met with the best
+    /// staking pools first, then the remainder goes to the best pools overall, each
+    /// capped at `cap`.
+    fn best_vertex(&self, score: &[f64], staking: &[bool], cap: f64) -> Vec<f64> {
+        let mut order: Vec<usize> = (0..score.len()).collect();
+        order.sort_by(|&a, &b| score[b].total_cmp(&score[a]));
+
+        let mut weights = vec![0.0; score.len()];
+        let mut remaining = 1.0;
+
+        if staking.iter().any(|&s| s) {
+            let mut staking_needed = self.config.min_staking_ratio;
+            for &i in order.iter().filter(|&&i| staking[i]) {
+                let amount = cap.min(staking_needed);
+                weights[i] = amount;
+                staking_needed -= amount;
+                remaining -= amount;
+                if staking_needed <= 0.0 {
+                    break;
+                }
+            }
+        }
+
+        for &i in &order {
+            if remaining <= 0.0 {
+                break;
+            }
+            let amount = (cap - weights[i]).min(remaining);
+            weights[i] += amount;
+            remaining -= amount;
+        }
+
+        weights
+    }
+
+    /// Plans the withdraw/deposit legs moving `current_allocations` to
+    /// `target_allocations` for a portfolio worth `portfolio_value` yoctoNEAR.
+    /// Pools missing from the target are withdrawn in full, except pools whose APY
+    /// is unknown: those are held as they are and the targets are scaled into the
+    /// rest of the portfolio. Nothing swaps between assets, so each deposit is
+    /// capped at what withdrawals of the same asset hand back within the plan, plus
+    /// any of that asset idle in the wallet (`WALLET` allocations).
     pub async fn execute_rebalance(
         &self,
         current_allocations: &[PortfolioAllocation],
         target_allocations: &[PortfolioAllocation],
-    ) -> Result<(), Box<dyn Error>> {
-        // Calculate required trades
-        let mut trades = Vec::new();
-        for target in target_allocations {
+        portfolio_value: U128,
+    ) -> Result<RebalancePlan, Box<dyn Error>> {
+        let threshold = self.config.rebalance_threshold / 100.0;
+        let mut legs = Vec::new();
+
+        let (idle, positions): (Vec<&PortfolioAllocation>, Vec<&PortfolioAllocation>) =
+            current_allocations.iter().partition(|a| a.protocol == WALLET);
+        let (held, current_allocations): (Vec<&PortfolioAllocation>, Vec<&PortfolioAllocation>) =
+            positions.into_iter().partition(|a| !self.apy_known(&a.pool_id));
+        let investable = 1.0 - held.iter().map(|a| a.allocation_percentage).sum::<f64>();
+        let target_allocations: Vec<PortfolioAllocation> = target_allocations
+            .iter()
+            .filter(|t| !held.iter().any(|h| h.pool_id == t.pool_id))
+            .map(|t| PortfolioAllocation {
+                allocation_percentage: t.allocation_percentage * investable.max(0.0),
+                ..t.clone()
+            })
+            .collect();
+
+        for current in &current_allocations {
+            let target = target_allocations
+                .iter()
+                .find(|a| a.pool_id == current.pool_id)
+                .map(|a| a.allocation_percentage)
+                .unwrap_or(0.0);
+
+            let difference = current.allocation_percentage - target;
+            if difference > threshold {
+                legs.push(RebalanceLeg::new(
+                    LegAction::Withdraw,
+                    &current.protocol,
+                    &current.pool_id,
+                    value_share(portfolio_value, difference),
+                    Some(difference / current.allocation_percentage),
+                    self.leg_slippage(&current.protocol),
+                ));
+            }
+        }
+
+        for target in &target_allocations {
             let current = current_allocations
                 .iter()
                 .find(|a| a.pool_id == target.pool_id)
@@ -122,19 +407,89 @@ impl YieldOptimizer {
                 .unwrap_or(0.0);
 
             let difference = target.allocation_percentage - current;
-            if difference.abs() > self.config.rebalance_threshold / 100.0 {
-                trades.push((target.pool_id.clone(), difference));
+            if difference > threshold {
+                legs.push(RebalanceLeg::new(
+                    LegAction::Deposit,
+                    &target.protocol,
+                    &target.pool_id,
+                    value_share(portfolio_value, difference),
+                    None,
+                    self.leg_slippage(&target.protocol),
+                ));
             }
         }
 
+        let mut idle_funds: HashMap<String, u128> = HashMap::new();
+        for allocation in idle {
+            *idle_funds.entry(allocation.pool_id.clone()).or_default() +=
+                value_share(portfolio_value, allocation.allocation_percentage).0;
+        }
+        self.fund_deposits_by_asset(&mut legs, idle_funds);
+        let plan = RebalancePlan::new(legs);
+
         // If rebalancing is needed, notify
-        if !trades.is_empty() {
+        if !plan.is_empty() {
             let _ = self.alert_tx.send(AlertMessage::RebalanceNeeded {
-                reason: format!("Portfolio drift detected: {} trades required", trades.len()),
+                reason: format!("Portfolio drift detected: {} trades required", plan.legs.len()),
             });
         }
 
-        Ok(())
+        Ok(plan)
+    }
+
+    /// Funds each asset's deposit legs, in order, from `funded` (idle value per
+    /// asset) plus the value its withdraw legs free up, and drops deposits nothing
+    /// funds. LiNEAR withdrawals fund nothing: unstaked NEAR is only claimable
+    /// epochs later.
+    fn fund_deposits_by_asset(&self, legs: &mut Vec<RebalanceLeg>, mut funded: HashMap<String, u128>) {
+        for leg in legs.iter() {
+            if leg.action == LegAction::Withdraw && leg.protocol != "linear" {
+                if let Some(asset) = self.pool_asset(&leg.pool_id) {
+                    *funded.entry(asset).or_default() += leg.amount.0;
+                }
+            }
+        }
+
+        for leg in legs.iter_mut().filter(|leg| leg.action == LegAction::Deposit) {
+            let available = match self.pool_asset(&leg.pool_id).and_then(|asset| funded.get_mut(&asset)) {
+                Some(available) => available,
+                None => {
+                    leg.amount = U128(0);
+                    continue;
+                }
+            };
+            leg.amount = U128(leg.amount.0.min(*available));
+            *available -= leg.amount.0;
+        }
+        legs.retain(|leg| leg.action == LegAction::Withdraw || leg.amount.0 > 0);
+    }
+
+    /// Asset a pool's deposit takes and its withdrawal returns: its tokens, or native
+    /// NEAR for LiNEAR. `None` for pools the optimizer hasn't seen.
+    fn pool_asset(&self, pool_id: &str) -> Option<String> {
+        let pool = self.pool_data.get(pool_id)?;
+        if pool.protocol == "linear" {
+            return Some("near".to_string());
+        }
+        let (a, b) = &pool.token_pair;
+        Some(match a.cmp(b) {
+            std::cmp::Ordering::Equal => a.clone(),
+            std::cmp::Ordering::Less => format!("{}+{}", a, b),
+            std::cmp::Ordering::Greater => format!("{}+{}", b, a),
+        })
+    }
+
+    /// Whether the last data seen for `pool_id` carried an APY.
+    fn apy_known(&self, pool_id: &str) -> bool {
+        self.pool_data.get(pool_id).map_or(false, |pool| pool.apy.is_some())
+    }
+
+    /// Only AMM swaps move the price; lending and staking legs execute at par.
+    fn leg_slippage(&self, protocol: &str) -> f64 {
+        match protocol {
+            "ref_finance" => self.config.slippage_tolerance,
+            _ => 0.0,
+        }
     }
 
     pub fn get_portfolio_stats(&self, allocations: &[PortfolioAllocation]) -> PortfolioStats {
@@ -162,3 +517,334 @@ pub struct PortfolioStats {
     pub risk_score: f64,
     pub num_pools: usize,
 }
+
+struct ReturnModel {
+    mean: Vec<f64>,
+    covariance: Vec<Vec<f64>>,
+}
+
+impl ReturnModel {
+    fn cov_times(&self, weights: &[f64]) -> Vec<f64> {
+        self.covariance.iter().map(|row| dot(row, weights)).collect()
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// `fraction` of `portfolio_value`, kept in integer math to the nearest part per billion.
+fn value_share(portfolio_value: U128, fraction: f64) -> U128 {
+    const SCALE: u128 = 1_000_000_000;
+    let parts = (fraction * SCALE as f64).round() as u128;
+    U128(portfolio_value.0 / SCALE * parts + portfolio_value.0 % SCALE * parts / SCALE)
+}
+
+/// APY of a pool that passed `validate_pool`, which rejects unknown APYs.
+fn known_apy(pool: &PoolMetrics) -> f64 {
+    pool.apy.unwrap_or(0.0)
+}
+
+fn is_staking_pool(pool: &PoolMetrics) -> bool {
+    pool.tags.iter().any(|t| t == "staking")
+}
+
+/// Scales allocations toward a 100% total without pushing any pool past `cap`: pools
+/// that reach the cap are held there and the others absorb the rest. When every pool
+/// is capped the remainder stays unallocated.
+fn normalize_capped(allocations: &mut [PortfolioAllocation], cap: f64) {
+    const EPSILON: f64 = 1e-12;
+
+    let total: f64 = allocations.iter().map(|a| a.allocation_percentage).sum();
+    if total > 1.0 {
+        for allocation in allocations.iter_mut() {
+            allocation.allocation_percentage /= total;
+        }
+        return;
+    }
+
+    // Each pass either fills the gap or pins at least one more pool at the cap
+    loop {
+        let total: f64 = allocations.iter().map(|a| a.allocation_percentage).sum();
+        let uncapped: f64 = allocations
+            .iter()
+            .map(|a| a.allocation_percentage)
+            .filter(|&w| w < cap - EPSILON)
+            .sum();
+        let missing = 1.0 - total;
+        if missing <= EPSILON || uncapped <= 0.0 {
+            break;
+        }
+
+        let scale = 1.0 + missing / uncapped;
+        for allocation in allocations.iter_mut() {
+            if allocation.allocation_percentage < cap - EPSILON {
+                allocation.allocation_percentage = (allocation.allocation_percentage * scale).min(cap);
+            }
+        }
+    }
+}
+
+fn weights_to_allocations(pools: &[&PoolMetrics], weights: &[f64]) -> Vec<PortfolioAllocation> {
+    pools
+        .iter()
+        .zip(weights)
+        .filter(|(_, &w)| w > MIN_WEIGHT)
+        .map(|(pool, &w)| PortfolioAllocation {
+            pool_id: pool.pool_id.clone(),
+            protocol: pool.protocol.clone(),
+            allocation_percentage: w,
+            expected_apy: known_apy(pool),
+            risk_score: pool.risk_score,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(pool_id: &str, apy: f64, tags: &[&str]) -> PoolMetrics {
+        PoolMetrics {
+            protocol: "test".to_string(),
+            pool_id: pool_id.to_string(),
+            apy: Some(apy),
+            tvl: 10_000_000,
+            risk_score: 0.9,
+            audit_status: true,
+            token_pair: ("a".to_string(), "b".to_string()),
+            chain_factor: 1.0,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            risk_bucket: None,
+        }
+    }
+
+    async fn optimizer_with_history(mode: OptimizationMode) -> YieldOptimizer {
+        let mut config = YieldConfig::new(RiskLevel::Moderate);
+        config.optimization_mode = mode;
+        let (alert_tx, _) = broadcast::channel(16);
+        let mut optimizer = YieldOptimizer::new(config, alert_tx);
+
+        // Two volatile, positively correlated pools, one volatile hedge and steady staking
+        let series = [
+            (12.0, 14.0, 11.0, 9.0),
+            (20.0, 22.0, 15.0, 9.1),
+            (10.0, 11.0, 17.0, 9.0),
+            (18.0, 19.0, 12.0, 9.2),
+            (15.0, 16.0, 14.0, 9.1),
+        ];
+        for (a, b, c, staking) in series {
+            optimizer
+                .update_pool_data(vec![
+                    pool("a", a, &[]),
+                    pool("b", b, &[]),
+                    pool("c", c, &[]),
+                    pool("staking", staking, &["staking"]),
+                ])
+                .await;
+        }
+        optimizer
+    }
+
+    fn weight(allocations: &[PortfolioAllocation], pool_id: &str) -> f64 {
+        allocations
+            .iter()
+            .find(|a| a.pool_id == pool_id)
+            .map_or(0.0, |a| a.allocation_percentage)
+    }
+
+    #[tokio::test]
+    async fn mean_variance_respects_caps_and_staking_floor() {
+        let optimizer =
+            optimizer_with_history(OptimizationMode::MeanVariance { risk_aversion: 0.5 }).await;
+        let allocations = optimizer.optimize_portfolio().await;
+
+        let total: f64 = allocations.iter().map(|a| a.allocation_percentage).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        for allocation in &allocations {
+            assert!(allocation.allocation_percentage <= 0.4 + 1e-9);
+        }
+        assert!(weight(&allocations, "staking") >= 0.2 - 1e-9);
+    }
+
+    #[tokio::test]
+    async fn too_few_pools_leave_the_remainder_unallocated() {
+        for mode in [
+            OptimizationMode::Heuristic,
+            OptimizationMode::MeanVariance { risk_aversion: 0.5 },
+        ] {
+            let mut config = YieldConfig::new(RiskLevel::Moderate);
+            config.optimization_mode = mode;
+            assert_eq!(config.max_allocation_per_pool, 0.4);
+            let (alert_tx, _) = broadcast::channel(16);
+            let mut optimizer = YieldOptimizer::new(config, alert_tx);
+            optimizer
+                .update_pool_data(vec![pool("a", 12.0, &[]), pool("b", 10.0, &[])])
+                .await;
+
+            let allocations = optimizer.optimize_portfolio().await;
+            assert_eq!(allocations.len(), 2);
+            for allocation in &allocations {
+                assert!((allocation.allocation_percentage - 0.4).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn caps_pools_once_across_buckets() {
+        let (alert_tx, _) = broadcast::channel(16);
+        let mut optimizer = YieldOptimizer::new(YieldConfig::new(RiskLevel::Moderate), alert_tx);
+        optimizer
+            .update_pool_data(vec![
+                pool("staking", 9.0, &["staking"]),
+                pool("a", 12.0, &[]),
+                pool("b", 11.0, &[]),
+                pool("c", 10.0, &[]),
+            ])
+            .await;
+
+        let buckets = HashMap::from([("staking".to_string(), 50), ("other".to_string(), 50)]);
+        let allocations = optimizer
+            .optimize_buckets(&buckets, |bucket, pool| (bucket == "staking") == is_staking_pool(pool))
+            .await;
+
+        // The lone staking pool is capped at 40% of the portfolio, not 40% of its bucket
+        let total: f64 = allocations.iter().map(|a| a.allocation_percentage).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert!((weight(&allocations, "staking") - 0.4).abs() < 1e-9);
+        for pool_id in ["a", "b", "c"] {
+            assert!((weight(&allocations, pool_id) - 0.2).abs() < 1e-9);
+        }
+    }
+
+    #[tokio::test]
+    async fn high_risk_aversion_shifts_weight_to_staking() {
+        let cautious =
+            optimizer_with_history(OptimizationMode::MeanVariance { risk_aversion: 10.0 }).await;
+        let greedy =
+            optimizer_with_history(OptimizationMode::MeanVariance { risk_aversion: 0.0 }).await;
+
+        let cautious = cautious.optimize_portfolio().await;
+        let greedy = greedy.optimize_portfolio().await;
+        assert!(weight(&cautious, "staking") > weight(&greedy, "staking"));
+    }
+
+    fn ref_pool(pool_id: &str, tokens: (&str, &str)) -> PoolMetrics {
+        PoolMetrics {
+            protocol: "ref_finance".to_string(),
+            token_pair: (tokens.0.to_string(), tokens.1.to_string()),
+            ..pool(pool_id, 8.0, &[])
+        }
+    }
+
+    #[tokio::test]
+    async fn drops_deposits_no_withdrawal_of_the_same_asset_funds() {
+        let (alert_tx, _) = broadcast::channel(16);
+        let mut optimizer = YieldOptimizer::new(YieldConfig::new(RiskLevel::Moderate), alert_tx);
+        let mut usdc = pool("usdc.near", 6.0, &["lending"]);
+        usdc.protocol = "burrow".to_string();
+        usdc.token_pair = ("usdc.near".to_string(), "usdc.near".to_string());
+        optimizer
+            .update_pool_data(vec![ref_pool("79", ("wrap.near", "usdt.tether-token.near")), usdc])
+            .await;
+
+        let plan = optimizer
+            .execute_rebalance(
+                &[allocation("79", "ref_finance", 1.0)],
+                &[allocation("usdc.near", "burrow", 0.4)],
+                U128(1_000_000),
+            )
+            .await
+            .unwrap();
+
+        // The exit still happens, but the wallet holds no USDC to supply
+        assert_eq!(plan.legs.len(), 1);
+        assert_eq!(plan.legs[0].action, LegAction::Withdraw);
+    }
+
+    fn allocation(pool_id: &str, protocol: &str, share: f64) -> PortfolioAllocation {
+        PortfolioAllocation {
+            pool_id: pool_id.to_string(),
+            protocol: protocol.to_string(),
+            allocation_percentage: share,
+            expected_apy: 10.0,
+            risk_score: 0.9,
+        }
+    }
+
+    #[tokio::test]
+    async fn plans_withdrawals_before_deposits() {
+        let (alert_tx, mut alerts) = broadcast::channel(16);
+        let mut optimizer = YieldOptimizer::new(YieldConfig::new(RiskLevel::Moderate), alert_tx);
+        optimizer
+            .update_pool_data(vec![
+                ref_pool("79", ("wrap.near", "usdt.tether-token.near")),
+                ref_pool("80", ("usdt.tether-token.near", "wrap.near")),
+                PoolMetrics { protocol: "linear".to_string(), ..pool("linear", 9.0, &["staking"]) },
+            ])
+            .await;
+        let near = 1_000_000_000_000_000_000_000_000u128;
+
+        let plan = optimizer
+            .execute_rebalance(
+                &[allocation("79", "ref_finance", 0.5), allocation("linear", "linear", 0.5)],
+                &[allocation("80", "ref_finance", 0.7), allocation("linear", "linear", 0.3)],
+                U128(100 * near),
+            )
+            .await
+            .unwrap();
+
+        let summary: Vec<(LegAction, &str, u128)> = plan
+            .legs
+            .iter()
+            .map(|leg| (leg.action, leg.pool_id.as_str(), leg.amount.0 / near))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (LegAction::Withdraw, "79", 50),
+                (LegAction::Withdraw, "linear", 20),
+                // Only the same-pair withdrawal from 79 funds it; unstaked NEAR isn't liquid yet
+                (LegAction::Deposit, "80", 50),
+            ]
+        );
+        assert_eq!(plan.legs[0].position_fraction, Some(1.0));
+        assert!((plan.legs[1].position_fraction.unwrap() - 0.4).abs() < 1e-9);
+        assert_eq!(plan.legs[0].max_slippage, 0.5);
+        assert_eq!(plan.total_gas, plan.legs.iter().map(|l| l.estimated_gas).sum::<u64>());
+        assert!(matches!(alerts.try_recv(), Ok(AlertMessage::RebalanceNeeded { .. })));
+    }
+
+    #[tokio::test]
+    async fn holds_pools_with_unknown_apy() {
+        let (alert_tx, _) = broadcast::channel(16);
+        let mut optimizer = YieldOptimizer::new(YieldConfig::new(RiskLevel::Moderate), alert_tx);
+        // LiNEAR right after a restart, before a day of price history
+        let mut linear = pool("linear", 0.0, &["staking"]);
+        linear.apy = None;
+        optimizer
+            .update_pool_data(vec![linear, pool("a", 12.0, &[]), pool("b", 11.0, &[])])
+            .await;
+        let near = 1_000_000_000_000_000_000_000_000u128;
+
+        let plan = optimizer
+            .execute_rebalance(
+                &[allocation("linear", "linear", 0.5), allocation("a", "test", 0.5)],
+                &[allocation("a", "test", 0.4), allocation("b", "test", 0.4)],
+                U128(100 * near),
+            )
+            .await
+            .unwrap();
+
+        // Targets are scaled into the half of the portfolio outside LiNEAR
+        let summary: Vec<(LegAction, &str, u128)> = plan
+            .legs
+            .iter()
+            .map(|leg| (leg.action, leg.pool_id.as_str(), leg.amount.0 / near))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(LegAction::Withdraw, "a", 30), (LegAction::Deposit, "b", 20)]
+        );
+    }
+}
@@ -1,14 +1,23 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use std::error::Error;
+use crate::benchmark::{benchmark_curve, value_at, BenchmarkValues, MarketSnapshot};
+use crate::performance_store::{compact_points, PerformanceStore};
+use crate::rpc::GasUsage;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StrategyPerformance {
     pub strategy_id: String,
     pub initial_value: f64,
     pub current_value: f64,
+    /// Annualized return net of gas spent.
     pub apy: f64,
-    pub gas_saved: f64,
+    #[serde(default)]
+    pub gross_apy: f64,
+    /// Total gas cost of recorded actions, in the same unit as the values.
+    #[serde(default)]
+    pub gas_spent: f64,
     pub rebalance_count: u32,
     pub last_update: DateTime<Utc>,
     pub historical_values: Vec<TimeSeriesData>,
@@ -21,36 +30,236 @@ pub struct TimeSeriesData {
     pub value: f64,
     pub sentiment_score: f32,
     pub action: Option<String>,
+    /// Gas burnt by the transactions behind `action`.
+    #[serde(default)]
+    pub gas: Option<GasUsage>,
+    /// `gas` valued at the NEAR price of the time, in the same unit as `value`.
+    #[serde(default)]
+    pub gas_cost: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Benchmark APYs over the strategy's lifetime; `None` until market data covers it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ComparisonMetrics {
-    pub manual_strategy_apy: f64,
-    pub market_average_apy: f64,
-    pub alpha: f64, // Excess returns compared to market
+    #[serde(default)]
+    pub hold_near_apy: Option<f64>,
+    #[serde(default)]
+    pub staking_apy: Option<f64>,
+    #[serde(default)]
+    pub balanced_apy: Option<f64>,
+    pub alpha: f64, // Excess returns compared to holding NEAR
     pub sharpe_ratio: f64,
 }
 
+/// Strategy and benchmark returns (%) over one reporting period.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeriodComparison {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub strategy_return: f64,
+    pub hold_near_return: Option<f64>,
+    pub staking_return: Option<f64>,
+    pub balanced_return: Option<f64>,
+    /// Strategy return minus the hold-NEAR return.
+    pub alpha: Option<f64>,
+}
+
 pub struct PerformanceTracker {
     strategies: HashMap<String, StrategyPerformance>,
+    /// Sorted by timestamp; shared by every strategy's benchmarks.
+    market: Vec<MarketSnapshot>,
+    store: Option<Box<dyn PerformanceStore>>,
 }
 
 impl PerformanceTracker {
     pub fn new() -> Self {
         Self {
             strategies: HashMap::new(),
+            market: Vec::new(),
+            store: None,
+        }
+    }
+
+    /// Loads every strategy already in `store` and persists all further updates to it.
+    pub fn with_store(store: Box<dyn PerformanceStore>) -> Result<Self, Box<dyn Error>> {
+        let strategies = store
+            .load_strategies()?
+            .into_iter()
+            .map(|strategy| (strategy.strategy_id.clone(), strategy))
+            .collect();
+
+        Ok(Self {
+            strategies,
+            market: store.load_market()?,
+            store: Some(store),
+        })
+    }
+
+    /// Starts tracking a strategy from `initial_value` at `started_at`. Does nothing
+    /// if the strategy is already tracked, e.g. after being loaded from the store.
+    pub fn register_strategy(&mut self, strategy_id: &str, initial_value: f64, started_at: DateTime<Utc>) {
+        if self.strategies.contains_key(strategy_id) {
+            return;
+        }
+
+        let strategy = StrategyPerformance {
+            strategy_id: strategy_id.to_string(),
+            initial_value,
+            current_value: initial_value,
+            apy: 0.0,
+            gross_apy: 0.0,
+            gas_spent: 0.0,
+            rebalance_count: 0,
+            last_update: started_at,
+            historical_values: vec![TimeSeriesData {
+                timestamp: started_at,
+                value: initial_value,
+                sentiment_score: 50.0,
+                action: None,
+                gas: None,
+                gas_cost: 0.0,
+            }],
+            comparison: ComparisonMetrics::default(),
+        };
+
+        if let Some(store) = &self.store {
+            let persisted = store
+                .save_strategy(&strategy)
+                .and_then(|_| store.append_point(strategy_id, &strategy.historical_values[0]));
+            if let Err(e) = persisted {
+                log::error!("Failed to persist strategy {}: {}", strategy_id, e);
+            }
         }
+        self.strategies.insert(strategy_id.to_string(), strategy);
+    }
+
+    /// Annualized return (%) from the first to the latest recorded value, net of
+    /// the gas spent along the way.
+    pub fn calculate_apy(strategy: &StrategyPerformance) -> f64 {
+        Self::annualized(strategy, strategy.current_value - strategy.gas_spent)
+    }
+
+    fn calculate_gross_apy(strategy: &StrategyPerformance) -> f64 {
+        Self::annualized(strategy, strategy.current_value)
     }
 
-    pub fn calculate_apy(&self, strategy: &StrategyPerformance) -> f64 {
-        let days_elapsed = (Utc::now() - strategy.historical_values[0].timestamp).num_days() as f64;
-        if days_elapsed == 0.0 {
-            return 0.0;
+    fn annualized(strategy: &StrategyPerformance, end_value: f64) -> f64 {
+        match (strategy.historical_values.first(), strategy.historical_values.last()) {
+            (Some(first), Some(last)) => annualized_return(
+                strategy.initial_value,
+                end_value,
+                last.timestamp - first.timestamp,
+            ),
+            _ => 0.0,
+        }
+    }
+
+    /// The strategy's history with each value reduced by the gas spent up to that point.
+    fn net_history(strategy: &StrategyPerformance) -> Vec<TimeSeriesData> {
+        let mut spent = 0.0;
+        strategy
+            .historical_values
+            .iter()
+            .map(|point| {
+                spent += point.gas_cost;
+                TimeSeriesData {
+                    value: point.value - spent,
+                    ..point.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// NEAR price in effect at `timestamp`, falling back to the earliest known price.
+    fn near_price_at(&self, timestamp: DateTime<Utc>) -> Option<f64> {
+        let idx = self.market.partition_point(|s| s.timestamp <= timestamp);
+        self.market.get(idx.saturating_sub(1)).map(|s| s.near_price)
+    }
+
+    /// NEAR price change, in percent, over the `period` before `now`. Measured from
+    /// the earliest snapshot when the market history is shorter than `period`.
+    pub fn near_price_change(&self, now: DateTime<Utc>, period: Duration) -> Option<f64> {
+        let latest = self.near_price_at(now)?;
+        let earlier = self.near_price_at(now - period)?;
+        if earlier <= 0.0 {
+            return None;
+        }
+        Some((latest / earlier - 1.0) * 100.0)
+    }
+
+    /// The most recent market observation.
+    pub fn latest_market(&self) -> Option<&MarketSnapshot> {
+        self.market.last()
+    }
+
+    /// Adds a market observation and re-measures every strategy against the benchmarks.
+    /// Snapshots older than the latest one are ignored.
+    pub fn record_market(&mut self, snapshot: MarketSnapshot) {
+        if self.market.last().map_or(false, |last| snapshot.timestamp <= last.timestamp) {
+            log::warn!("Ignoring out-of-order market snapshot at {}", snapshot.timestamp);
+            return;
+        }
+
+        if let Some(store) = &self.store {
+            if let Err(e) = store.append_market(&snapshot) {
+                log::error!("Failed to persist market snapshot: {}", e);
+            }
+        }
+        self.market.push(snapshot);
+
+        for strategy in self.strategies.values_mut() {
+            Self::update_comparison_metrics(strategy, &self.market);
+            if let Some(store) = &self.store {
+                if let Err(e) = store.save_strategy(strategy) {
+                    log::error!("Failed to persist strategy {}: {}", strategy.strategy_id, e);
+                }
+            }
         }
+    }
+
+    /// Strategy vs benchmark returns over consecutive `period`s from the strategy's
+    /// first point. The last period ends at the latest point.
+    pub fn period_report(&self, strategy_id: &str, period: Duration) -> Vec<PeriodComparison> {
+        let strategy = match self.strategies.get(strategy_id) {
+            Some(strategy) => strategy,
+            None => return Vec::new(),
+        };
+        let (first, last) = match (strategy.historical_values.first(), strategy.historical_values.last()) {
+            (Some(first), Some(last)) if period > Duration::zero() => (first, last),
+            _ => return Vec::new(),
+        };
+        let curve = benchmark_curve(&self.market, first.timestamp, strategy.initial_value);
 
-        let return_rate = (strategy.current_value - strategy.initial_value) / strategy.initial_value;
-        let annualized = (1.0 + return_rate).powf(365.0 / days_elapsed) - 1.0;
-        annualized * 100.0
+        let history = Self::net_history(strategy);
+        let value_on = |timestamp: DateTime<Utc>| {
+            let idx = history.partition_point(|p| p.timestamp <= timestamp);
+            history[idx.saturating_sub(1)].value
+        };
+        let change = |from: f64, to: f64| (to / from - 1.0) * 100.0;
+
+        let mut report = Vec::new();
+        let mut start = first.timestamp;
+        while start < last.timestamp {
+            let end = (start + period).min(last.timestamp);
+            let strategy_return = change(value_on(start), value_on(end));
+            let benchmarks = value_at(&curve, start).zip(value_at(&curve, end));
+            let benchmark_return = |pick: fn(&BenchmarkValues) -> f64| {
+                benchmarks.map(|(from, to)| change(pick(&from), pick(&to)))
+            };
+
+            let hold_near_return = benchmark_return(|b| b.hold_near);
+            report.push(PeriodComparison {
+                start,
+                end,
+                strategy_return,
+                hold_near_return,
+                staking_return: benchmark_return(|b| b.staking),
+                balanced_return: benchmark_return(|b| b.balanced),
+                alpha: hold_near_return.map(|r| strategy_return - r),
+            });
+            start = end;
+        }
+        report
     }
 
     pub fn update_strategy(&mut self, 
@@ -59,85 +268,173 @@ impl PerformanceTracker {
         sentiment_score: f32,
         action: Option<String>
     ) {
+        self.update_strategy_at(strategy_id, Utc::now(), current_value, sentiment_score, action);
+    }
+
+    /// Records a value observed at `timestamp`, e.g. when replaying history.
+    pub fn update_strategy_at(&mut self,
+        strategy_id: &str,
+        timestamp: DateTime<Utc>,
+        current_value: f64,
+        sentiment_score: f32,
+        action: Option<String>
+    ) {
+        self.push_point(strategy_id, timestamp, current_value, sentiment_score, action, None);
+    }
+
+    /// Records an executed action together with the gas its transactions burnt,
+    /// which is valued at the NEAR price of the time and deducted from net returns.
+    pub fn record_action(&mut self,
+        strategy_id: &str,
+        timestamp: DateTime<Utc>,
+        current_value: f64,
+        sentiment_score: f32,
+        action: String,
+        gas: GasUsage
+    ) {
+        self.push_point(strategy_id, timestamp, current_value, sentiment_score, Some(action), Some(gas));
+    }
+
+    fn push_point(&mut self,
+        strategy_id: &str,
+        timestamp: DateTime<Utc>,
+        current_value: f64,
+        sentiment_score: f32,
+        action: Option<String>,
+        gas: Option<GasUsage>
+    ) {
+        let gas_cost = match gas {
+            Some(gas) if gas.tokens_burnt.0 > 0 => match self.near_price_at(timestamp) {
+                Some(price) => gas.near_spent() * price,
+                None => {
+                    log::warn!("No NEAR price to value gas for {}; recording it at zero cost", strategy_id);
+                    0.0
+                }
+            },
+            _ => 0.0,
+        };
+
         if let Some(strategy) = self.strategies.get_mut(strategy_id) {
             strategy.current_value = current_value;
-            strategy.apy = self.calculate_apy(strategy);
-            strategy.last_update = Utc::now();
+            strategy.last_update = timestamp;
+            strategy.gas_spent += gas_cost;
+            if action.is_some() {
+                strategy.rebalance_count += 1;
+            }
 
             strategy.historical_values.push(TimeSeriesData {
-                timestamp: Utc::now(),
+                timestamp,
                 value: current_value,
                 sentiment_score,
                 action,
+                gas,
+                gas_cost,
             });
+            strategy.apy = Self::calculate_apy(strategy);
+            strategy.gross_apy = Self::calculate_gross_apy(strategy);
 
             // Update comparison metrics
-            self.update_comparison_metrics(strategy);
+            Self::update_comparison_metrics(strategy, &self.market);
+
+            if let Some(store) = &self.store {
+                let point = strategy.historical_values.last().unwrap();
+                let persisted = store
+                    .append_point(strategy_id, point)
+                    .and_then(|_| store.save_strategy(strategy));
+                if let Err(e) = persisted {
+                    log::error!("Failed to persist update for strategy {}: {}", strategy_id, e);
+                }
+            }
         }
     }
 
-    fn update_comparison_metrics(&self, strategy: &mut StrategyPerformance) {
-        // Calculate market average APY (simplified example)
-        let market_apy = 5.0; // This would come from external data
+    /// Thins out history older than `retention`, keeping one point per `resolution`
+    /// plus the first point and every rebalance. Returns the number of points removed.
+    pub fn compact(&mut self, now: DateTime<Utc>, retention: Duration, resolution: Duration) -> usize {
+        let cutoff = now - retention;
+        let mut removed = 0;
 
-        // Calculate Sharpe ratio
-        let returns: Vec<f64> = strategy.historical_values.windows(2)
-            .map(|w| (w[1].value - w[0].value) / w[0].value)
-            .collect();
+        for (strategy_id, strategy) in self.strategies.iter_mut() {
+            removed += compact_points(&mut strategy.historical_values, cutoff, resolution);
 
-        let avg_return = returns.iter().sum::<f64>() / returns.len() as f64;
-        let std_dev = (returns.iter()
-            .map(|r| (r - avg_return).powi(2))
-            .sum::<f64>() / returns.len() as f64)
-            .sqrt();
+            if let Some(store) = &self.store {
+                if let Err(e) = store.compact(strategy_id, cutoff, resolution) {
+                    log::error!("Failed to compact stored history for {}: {}", strategy_id, e);
+                }
+            }
+        }
+        removed
+    }
 
-        let risk_free_rate = 0.02; // 2% annual
-        let sharpe = if std_dev > 0.0 {
-            (avg_return - risk_free_rate) / std_dev
-        } else {
-            0.0
+    fn update_comparison_metrics(strategy: &mut StrategyPerformance, market: &[MarketSnapshot]) {
+        let sharpe = RiskMetrics::from_history(&Self::net_history(strategy), RISK_FREE_RATE).sharpe_ratio;
+
+        // Benchmarks are valued over the same window as the strategy
+        let (first, last) = match (strategy.historical_values.first(), strategy.historical_values.last()) {
+            (Some(first), Some(last)) => (first.timestamp, last.timestamp),
+            _ => return,
+        };
+        let curve = benchmark_curve(market, first, strategy.initial_value);
+        let benchmark_apy = |pick: fn(&BenchmarkValues) -> f64| {
+            let from = value_at(&curve, first)?;
+            let to = value_at(&curve, last)?;
+            Some(annualized_return(pick(&from), pick(&to), last - first))
         };
 
+        let hold_near_apy = benchmark_apy(|b| b.hold_near);
         strategy.comparison = ComparisonMetrics {
-            manual_strategy_apy: market_apy,
-            market_average_apy: market_apy,
-            alpha: strategy.apy - market_apy,
+            hold_near_apy,
+            staking_apy: benchmark_apy(|b| b.staking),
+            balanced_apy: benchmark_apy(|b| b.balanced),
+            alpha: hold_near_apy.map_or(0.0, |apy| strategy.apy - apy),
             sharpe_ratio: sharpe,
         };
     }
 
-    pub fn get_gas_savings(&self, strategy_id: &str) -> f64 {
-        if let Some(strategy) = self.strategies.get(strategy_id) {
-            // Estimate gas savings based on number of automated rebalances
-            let avg_gas_per_tx = 0.001; // NEAR tokens
-            strategy.rebalance_count as f64 * avg_gas_per_tx
-        } else {
-            0.0
-        }
+    /// Total gas cost recorded for the strategy, in the same unit as its values.
+    pub fn get_gas_spent(&self, strategy_id: &str) -> f64 {
+        self.strategies.get(strategy_id).map_or(0.0, |strategy| strategy.gas_spent)
+    }
+
+    pub fn get_strategy(&self, strategy_id: &str) -> Option<&StrategyPerformance> {
+        self.strategies.get(strategy_id)
     }
 
     pub fn get_strategy_stats(&self, strategy_id: &str) -> Option<StrategyStats> {
         self.strategies.get(strategy_id).map(|strategy| {
-            let win_rate = self.calculate_win_rate(strategy);
-            let avg_rebalance_gain = self.calculate_avg_rebalance_gain(strategy);
+            // Every stat is net of gas, so churning strategies are penalized
+            let history = Self::net_history(strategy);
+            let win_rate = self.calculate_win_rate(&history);
+            let avg_rebalance_gain = self.calculate_avg_rebalance_gain(&history);
+            let risk = RiskMetrics::from_history(&history, RISK_FREE_RATE);
 
             StrategyStats {
                 apy: strategy.apy,
+                gross_apy: strategy.gross_apy,
                 win_rate,
                 avg_rebalance_gain,
-                gas_saved: self.get_gas_savings(strategy_id),
+                gas_spent: strategy.gas_spent,
                 alpha: strategy.comparison.alpha,
-                sharpe_ratio: strategy.comparison.sharpe_ratio,
+                sharpe_ratio: risk.sharpe_ratio,
+                sortino_ratio: risk.sortino_ratio,
+                volatility: risk.volatility,
+                max_drawdown: risk.max_drawdown,
+                max_drawdown_recovery_days: risk.max_drawdown_recovery_days,
+                calmar_ratio: if risk.max_drawdown > 0.0 {
+                    strategy.apy / risk.max_drawdown
+                } else {
+                    0.0
+                },
             }
         })
     }
 
-    fn calculate_win_rate(&self, strategy: &StrategyPerformance) -> f64 {
+    fn calculate_win_rate(&self, history: &[TimeSeriesData]) -> f64 {
         let mut winning_trades = 0;
         let mut total_trades = 0;
 
-        for window in strategy.historical_values.windows(2) {
-            if let Some(action) = &window[1].action {
+        for window in history.windows(2) {
+            if window[1].action.is_some() {
                 total_trades += 1;
                 if window[1].value > window[0].value {
                     winning_trades += 1;
@@ -152,11 +449,11 @@ impl PerformanceTracker {
         }
     }
 
-    fn calculate_avg_rebalance_gain(&self, strategy: &StrategyPerformance) -> f64 {
+    fn calculate_avg_rebalance_gain(&self, history: &[TimeSeriesData]) -> f64 {
         let mut total_gain = 0.0;
         let mut total_rebalances = 0;
 
-        for window in strategy.historical_values.windows(2) {
+        for window in history.windows(2) {
             if window[1].action.is_some() {
                 total_rebalances += 1;
                 total_gain += (window[1].value - window[0].value) / window[0].value;
@@ -173,10 +470,285 @@ impl PerformanceTracker {
 
 #[derive(Debug, Serialize)]
 pub struct StrategyStats {
+    /// Net of gas.
     pub apy: f64,
+    pub gross_apy: f64,
     pub win_rate: f64,
     pub avg_rebalance_gain: f64,
-    pub gas_saved: f64,
+    pub gas_spent: f64,
     pub alpha: f64,
+    /// Annualized, net of `RISK_FREE_RATE`.
     pub sharpe_ratio: f64,
+    /// Annualized, penalizing only returns below `RISK_FREE_RATE`.
+    pub sortino_ratio: f64,
+    /// Annualized standard deviation of returns, in percent.
+    pub volatility: f64,
+    /// Largest peak-to-trough loss, in percent.
+    pub max_drawdown: f64,
+    /// Days from the max drawdown's peak back to a new high; `None` while underwater.
+    pub max_drawdown_recovery_days: Option<f64>,
+    /// APY divided by max drawdown.
+    pub calmar_ratio: f64,
+}
+
+/// Compound annual growth (%) from `start_value` to `end_value` over `elapsed`.
+fn annualized_return(start_value: f64, end_value: f64, elapsed: Duration) -> f64 {
+    let days_elapsed = elapsed.num_seconds() as f64 / 86_400.0;
+    if days_elapsed <= 0.0 || start_value <= 0.0 {
+        return 0.0;
+    }
+
+    let return_rate = (end_value - start_value) / start_value;
+    let annualized = (1.0 + return_rate).powf(365.0 / days_elapsed) - 1.0;
+    annualized * 100.0
+}
+
+/// Annual risk-free rate used for Sharpe and Sortino.
+pub const RISK_FREE_RATE: f64 = 0.02;
+const SECONDS_PER_YEAR: f64 = 365.0 * 86_400.0;
+/// Deviations below this are rounding noise; the ratios are reported as 0.
+const MIN_DEVIATION: f64 = 1e-12;
+
+/// Return and drawdown statistics of a value series. Per-sample returns are
+/// annualized using the series' average sampling interval.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RiskMetrics {
+    pub sharpe_ratio: f64,
+    pub sortino_ratio: f64,
+    pub volatility: f64,
+    pub max_drawdown: f64,
+    pub max_drawdown_recovery_days: Option<f64>,
+}
+
+impl RiskMetrics {
+    pub fn from_history(history: &[TimeSeriesData], risk_free_rate: f64) -> Self {
+        let (max_drawdown, max_drawdown_recovery_days) = max_drawdown(history);
+        let mut metrics = RiskMetrics {
+            max_drawdown,
+            max_drawdown_recovery_days,
+            ..Default::default()
+        };
+
+        let returns: Vec<f64> = history
+            .windows(2)
+            .filter(|w| w[0].value > 0.0)
+            .map(|w| w[1].value / w[0].value - 1.0)
+            .collect();
+        let span = match (history.first(), history.last()) {
+            (Some(first), Some(last)) => (last.timestamp - first.timestamp).num_seconds() as f64,
+            _ => 0.0,
+        };
+        if returns.len() < 2 || span <= 0.0 {
+            return metrics;
+        }
+
+        let periods_per_year = SECONDS_PER_YEAR / (span / returns.len() as f64);
+        let period_risk_free = (1.0 + risk_free_rate).powf(1.0 / periods_per_year) - 1.0;
+        let n = returns.len() as f64;
+
+        let mean = returns.iter().sum::<f64>() / n;
+        let std_dev = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+        let downside_dev = (returns
+            .iter()
+            .map(|r| (r - period_risk_free).min(0.0).powi(2))
+            .sum::<f64>()
+            / n)
+            .sqrt();
+
+        let excess = mean - period_risk_free;
+        let annualize = periods_per_year.sqrt();
+        metrics.volatility = std_dev * annualize * 100.0;
+        if std_dev > MIN_DEVIATION {
+            metrics.sharpe_ratio = excess / std_dev * annualize;
+        }
+        if downside_dev > MIN_DEVIATION {
+            metrics.sortino_ratio = excess / downside_dev * annualize;
+        }
+        metrics
+    }
+}
+
+/// Largest peak-to-trough loss (%) and the days from that peak until the value
+/// first regains it.
+fn max_drawdown(history: &[TimeSeriesData]) -> (f64, Option<f64>) {
+    let mut peak: Option<&TimeSeriesData> = None;
+    let mut worst = 0.0;
+    let mut worst_peak: Option<&TimeSeriesData> = None;
+    let mut recovery = None;
+
+    for point in history {
+        match peak {
+            Some(p) if point.value < p.value => {
+                let drawdown = (p.value - point.value) / p.value;
+                if drawdown > worst {
+                    worst = drawdown;
+                    worst_peak = Some(p);
+                    recovery = None;
+                }
+            }
+            _ => {
+                if let Some(wp) = worst_peak {
+                    if recovery.is_none() && point.value >= wp.value {
+                        recovery = Some((point.timestamp - wp.timestamp).num_seconds() as f64 / 86_400.0);
+                    }
+                }
+                peak = Some(point);
+            }
+        }
+    }
+
+    (worst * 100.0, recovery)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use near_sdk::json_types::U128;
+
+    fn daily(values: &[f64]) -> Vec<TimeSeriesData> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(day, value)| TimeSeriesData {
+                timestamp: Utc.timestamp_opt(day as i64 * 86_400, 0).unwrap(),
+                value: *value,
+                sentiment_score: 50.0,
+                action: None,
+                gas: None,
+                gas_cost: 0.0,
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-3, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn drawdown_and_recovery_time() {
+        let metrics = RiskMetrics::from_history(&daily(&[100.0, 120.0, 90.0, 110.0, 130.0]), 0.0);
+        assert_close(metrics.max_drawdown, 25.0);
+        // Peak on day 1, regained on day 4
+        assert_eq!(metrics.max_drawdown_recovery_days, Some(3.0));
+
+        let underwater = RiskMetrics::from_history(&daily(&[100.0, 80.0, 90.0]), 0.0);
+        assert_close(underwater.max_drawdown, 20.0);
+        assert_eq!(underwater.max_drawdown_recovery_days, None);
+    }
+
+    #[test]
+    fn annualized_ratios_on_alternating_returns() {
+        // Daily returns of +1%, -0.5%, +1%, -0.5%
+        let mut values = vec![100.0];
+        for r in [0.01, -0.005, 0.01, -0.005] {
+            values.push(values.last().unwrap() * (1.0 + r));
+        }
+        let metrics = RiskMetrics::from_history(&daily(&values), 0.0);
+
+        // mean 0.25%, sample std 0.866%, downside deviation 0.354%, 365 periods a year
+        let annualize = 365f64.sqrt();
+        let std_dev = 0.0075 * (4.0f64 / 3.0).sqrt();
+        assert_close(metrics.sharpe_ratio, 0.0025 / std_dev * annualize);
+        assert_close(metrics.sortino_ratio, 0.0025 / 0.0000125f64.sqrt() * annualize);
+        assert_close(metrics.volatility, std_dev * annualize * 100.0);
+    }
+
+    #[test]
+    fn steady_growth_has_no_volatility_or_drawdown() {
+        let values: Vec<f64> = (0..10).map(|day| 100.0 * 1.001f64.powi(day)).collect();
+        let metrics = RiskMetrics::from_history(&daily(&values), RISK_FREE_RATE);
+
+        assert_close(metrics.volatility, 0.0);
+        assert_eq!(metrics.sharpe_ratio, 0.0);
+        assert_eq!(metrics.sortino_ratio, 0.0);
+        assert_eq!(metrics.max_drawdown, 0.0);
+    }
+
+    #[test]
+    fn strategy_stats_include_calmar() {
+        let mut tracker = PerformanceTracker::new();
+        let start = Utc.timestamp_opt(0, 0).unwrap();
+        tracker.register_strategy("sentiment", 100.0, start);
+        for (day, value) in [(1, 120.0), (2, 90.0), (3, 110.0)] {
+            tracker.update_strategy_at("sentiment", start + Duration::days(day), value, 50.0, None);
+        }
+
+        let stats = tracker.get_strategy_stats("sentiment").unwrap();
+        assert_close(stats.max_drawdown, 25.0);
+        assert_close(stats.calmar_ratio, stats.apy / 25.0);
+    }
+
+    #[test]
+    fn alpha_is_measured_against_holding_near() {
+        let mut tracker = PerformanceTracker::new();
+        let start = Utc.timestamp_opt(0, 0).unwrap();
+        tracker.register_strategy("sentiment", 100.0, start);
+        // NEAR drops 20% over 60 days while the strategy gains 2%
+        for (day, price) in [(0, 5.0), (30, 4.5), (60, 4.0)] {
+            tracker.record_market(MarketSnapshot {
+                timestamp: start + Duration::days(day),
+                near_price: price,
+                staking_apy: 10.0,
+                stable_apy: 5.0,
+            });
+        }
+        tracker.update_strategy_at("sentiment", start + Duration::days(30), 101.0, 50.0, None);
+        tracker.update_strategy_at("sentiment", start + Duration::days(60), 102.0, 50.0, None);
+
+        let comparison = &tracker.get_strategy("sentiment").unwrap().comparison;
+        let hold_near_apy = comparison.hold_near_apy.unwrap();
+        assert_close(hold_near_apy, annualized_return(100.0, 80.0, Duration::days(60)));
+        assert!(comparison.staking_apy.unwrap() > hold_near_apy);
+        assert!(comparison.alpha > 0.0);
+
+        let periods = tracker.period_report("sentiment", Duration::days(30));
+        assert_eq!(periods.len(), 2);
+        assert_close(periods[0].strategy_return, 1.0);
+        assert_close(periods[0].hold_near_return.unwrap(), -10.0);
+        assert_close(periods[0].alpha.unwrap(), 11.0);
+        assert_close(periods[1].hold_near_return.unwrap(), (4.0 / 4.5 - 1.0) * 100.0);
+    }
+
+    #[test]
+    fn benchmarks_stay_empty_without_market_data() {
+        let mut tracker = PerformanceTracker::new();
+        let start = Utc.timestamp_opt(0, 0).unwrap();
+        tracker.register_strategy("sentiment", 100.0, start);
+        tracker.update_strategy_at("sentiment", start + Duration::days(30), 101.0, 50.0, None);
+
+        let comparison = &tracker.get_strategy("sentiment").unwrap().comparison;
+        assert!(comparison.hold_near_apy.is_none());
+        assert_eq!(comparison.alpha, 0.0);
+        assert!(tracker.period_report("sentiment", Duration::days(7))[0].alpha.is_none());
+    }
+
+    #[test]
+    fn gas_is_deducted_from_net_returns() {
+        let mut tracker = PerformanceTracker::new();
+        let start = Utc.timestamp_opt(0, 0).unwrap();
+        tracker.register_strategy("sentiment", 100.0, start);
+        tracker.record_market(MarketSnapshot {
+            timestamp: start,
+            near_price: 5.0,
+            staking_apy: 10.0,
+            stable_apy: 5.0,
+        });
+
+        // 0.1 NEAR of gas at $5
+        let gas = GasUsage {
+            gas_burnt: 30_000_000_000_000,
+            tokens_burnt: U128(100_000_000_000_000_000_000_000),
+        };
+        tracker.record_action("sentiment", start + Duration::days(30), 101.0, 50.0, "rebalance".to_string(), gas);
+
+        let strategy = tracker.get_strategy("sentiment").unwrap();
+        assert_close(strategy.gas_spent, 0.5);
+        assert_eq!(strategy.historical_values[1].gas, Some(gas));
+        assert_close(strategy.apy, annualized_return(100.0, 100.5, Duration::days(30)));
+        assert_close(strategy.gross_apy, annualized_return(100.0, 101.0, Duration::days(30)));
+
+        let stats = tracker.get_strategy_stats("sentiment").unwrap();
+        assert_close(stats.avg_rebalance_gain, 0.5);
+    }
 }
@@ -0,0 +1,443 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::error::Error;
+use std::sync::Mutex;
+use crate::benchmark::MarketSnapshot;
+use crate::performance::{ComparisonMetrics, StrategyPerformance, TimeSeriesData};
+use crate::rpc::GasUsage;
+use near_sdk::json_types::U128;
+
+/// Persistence backend for `PerformanceTracker`.
+pub trait PerformanceStore: Send + Sync {
+    /// Every stored strategy with its full history, oldest point first.
+    fn load_strategies(&self) -> Result<Vec<StrategyPerformance>, Box<dyn Error>>;
+
+    /// Inserts or updates the strategy summary (everything but the history).
+    fn save_strategy(&self, strategy: &StrategyPerformance) -> Result<(), Box<dyn Error>>;
+
+    fn append_point(&self, strategy_id: &str, point: &TimeSeriesData) -> Result<(), Box<dyn Error>>;
+
+    /// Market snapshots used for benchmarks, oldest first.
+    fn load_market(&self) -> Result<Vec<MarketSnapshot>, Box<dyn Error>>;
+
+    fn append_market(&self, snapshot: &MarketSnapshot) -> Result<(), Box<dyn Error>>;
+
+    /// Applies `compact_points` to the stored history of `strategy_id`.
+    fn compact(
+        &self,
+        strategy_id: &str,
+        cutoff: DateTime<Utc>,
+        resolution: Duration,
+    ) -> Result<usize, Box<dyn Error>>;
+}
+
+/// Thins out points older than `cutoff` to at most one per `resolution` bucket (the
+/// last one). The first point and points that record an action are always kept, so
+/// APY, win rate and rebalance history are unaffected. Returns the number removed.
+pub fn compact_points(points: &mut Vec<TimeSeriesData>, cutoff: DateTime<Utc>, resolution: Duration) -> usize {
+    let bucket_secs = resolution.num_seconds().max(1);
+    let before = points.len();
+
+    let mut keep = vec![true; points.len()];
+    for i in 1..points.len() {
+        let point = &points[i];
+        if point.timestamp >= cutoff || point.action.is_some() {
+            continue;
+        }
+        let bucket = point.timestamp.timestamp().div_euclid(bucket_secs);
+        let superseded = points
+            .get(i + 1)
+            .map_or(false, |next| next.timestamp < cutoff && next.timestamp.timestamp().div_euclid(bucket_secs) == bucket);
+        if superseded {
+            keep[i] = false;
+        }
+    }
+
+    let mut index = 0;
+    points.retain(|_| {
+        index += 1;
+        keep[index - 1]
+    });
+    before - points.len()
+}
+
+/// Default store: a single SQLite file.
+pub struct SqlitePerformanceStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqlitePerformanceStore {
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, Box<dyn Error>> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, Box<dyn Error>> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS strategies (
+                strategy_id     TEXT PRIMARY KEY,
+                initial_value   REAL NOT NULL,
+                current_value   REAL NOT NULL,
+                apy             REAL NOT NULL,
+                gross_apy       REAL NOT NULL,
+                gas_spent       REAL NOT NULL,
+                rebalance_count INTEGER NOT NULL,
+                last_update     INTEGER NOT NULL,
+                comparison      TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS strategy_points (
+                id              INTEGER PRIMARY KEY AUTOINCREMENT,
+                strategy_id     TEXT NOT NULL,
+                timestamp       INTEGER NOT NULL,
+                value           REAL NOT NULL,
+                sentiment_score REAL NOT NULL,
+                action          TEXT,
+                gas_burnt       INTEGER,
+                tokens_burnt    TEXT,
+                gas_cost        REAL NOT NULL DEFAULT 0
+            );
+            CREATE INDEX IF NOT EXISTS strategy_points_by_time
+                ON strategy_points (strategy_id, timestamp);
+            CREATE TABLE IF NOT EXISTS market_snapshots (
+                timestamp   INTEGER PRIMARY KEY,
+                near_price  REAL NOT NULL,
+                staking_apy REAL NOT NULL,
+                stable_apy  REAL NOT NULL
+            );",
+        )?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn load_points(conn: &Connection, strategy_id: &str) -> Result<Vec<TimeSeriesData>, Box<dyn Error>> {
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, value, sentiment_score, action, gas_burnt, tokens_burnt, gas_cost
+             FROM strategy_points WHERE strategy_id = ?1 ORDER BY timestamp, id",
+        )?;
+        let rows = stmt
+            .query_map(params![strategy_id], |row| {
+                Ok((
+                    TimeSeriesData {
+                        timestamp: from_unix(row.get(0)?),
+                        value: row.get(1)?,
+                        sentiment_score: row.get::<_, f64>(2)? as f32,
+                        action: row.get(3)?,
+                        gas: None,
+                        gas_cost: row.get(6)?,
+                    },
+                    row.get::<_, Option<i64>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut points = Vec::with_capacity(rows.len());
+        for (mut point, gas_burnt, tokens_burnt) in rows {
+            // u128 amounts don't fit SQLite integers, so tokens are stored as text
+            if let (Some(gas_burnt), Some(tokens_burnt)) = (gas_burnt, tokens_burnt) {
+                point.gas = Some(GasUsage {
+                    gas_burnt: gas_burnt as u64,
+                    tokens_burnt: U128(tokens_burnt.parse()?),
+                });
+            }
+            points.push(point);
+        }
+        Ok(points)
+    }
+}
+
+fn from_unix(secs: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(secs, 0).single().unwrap_or_default()
+}
+
+impl PerformanceStore for SqlitePerformanceStore {
+    fn load_strategies(&self) -> Result<Vec<StrategyPerformance>, Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT strategy_id, initial_value, current_value, apy, gross_apy, gas_spent,
+                    rebalance_count, last_update, comparison
+             FROM strategies",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                    row.get::<_, f64>(4)?,
+                    row.get::<_, f64>(5)?,
+                    row.get::<_, u32>(6)?,
+                    row.get::<_, i64>(7)?,
+                    row.get::<_, String>(8)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut strategies = Vec::with_capacity(rows.len());
+        for (strategy_id, initial_value, current_value, apy, gross_apy, gas_spent, rebalance_count, last_update, comparison) in rows {
+            let comparison: ComparisonMetrics = serde_json::from_str(&comparison)?;
+            strategies.push(StrategyPerformance {
+                historical_values: Self::load_points(&conn, &strategy_id)?,
+                strategy_id,
+                initial_value,
+                current_value,
+                apy,
+                gross_apy,
+                gas_spent,
+                rebalance_count,
+                last_update: from_unix(last_update),
+                comparison,
+            });
+        }
+        Ok(strategies)
+    }
+
+    fn save_strategy(&self, strategy: &StrategyPerformance) -> Result<(), Box<dyn Error>> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO strategies
+                (strategy_id, initial_value, current_value, apy, gross_apy, gas_spent, rebalance_count, last_update, comparison)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(strategy_id) DO UPDATE SET
+                initial_value = excluded.initial_value,
+                current_value = excluded.current_value,
+                apy = excluded.apy,
+                gross_apy = excluded.gross_apy,
+                gas_spent = excluded.gas_spent,
+                rebalance_count = excluded.rebalance_count,
+                last_update = excluded.last_update,
+                comparison = excluded.comparison",
+            params![
+                strategy.strategy_id,
+                strategy.initial_value,
+                strategy.current_value,
+                strategy.apy,
+                strategy.gross_apy,
+                strategy.gas_spent,
+                strategy.rebalance_count,
+                strategy.last_update.timestamp(),
+                serde_json::to_string(&strategy.comparison)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn append_point(&self, strategy_id: &str, point: &TimeSeriesData) -> Result<(), Box<dyn Error>> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO strategy_points
+                (strategy_id, timestamp, value, sentiment_score, action, gas_burnt, tokens_burnt, gas_cost)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                strategy_id,
+                point.timestamp.timestamp(),
+                point.value,
+                point.sentiment_score as f64,
+                point.action,
+                point.gas.map(|gas| gas.gas_burnt as i64),
+                point.gas.map(|gas| gas.tokens_burnt.0.to_string()),
+                point.gas_cost,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn load_market(&self) -> Result<Vec<MarketSnapshot>, Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, near_price, staking_apy, stable_apy FROM market_snapshots ORDER BY timestamp",
+        )?;
+        let snapshots = stmt
+            .query_map([], |row| {
+                Ok(MarketSnapshot {
+                    timestamp: from_unix(row.get(0)?),
+                    near_price: row.get(1)?,
+                    staking_apy: row.get(2)?,
+                    stable_apy: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(snapshots)
+    }
+
+    fn append_market(&self, snapshot: &MarketSnapshot) -> Result<(), Box<dyn Error>> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO market_snapshots (timestamp, near_price, staking_apy, stable_apy)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                snapshot.timestamp.timestamp(),
+                snapshot.near_price,
+                snapshot.staking_apy,
+                snapshot.stable_apy,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn compact(
+        &self,
+        strategy_id: &str,
+        cutoff: DateTime<Utc>,
+        resolution: Duration,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let first_id: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM strategy_points WHERE strategy_id = ?1 ORDER BY timestamp, id LIMIT 1",
+                params![strategy_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        // Keep the last point of each bucket before the cutoff, plus the first point
+        // and every action point
+        let removed = tx.execute(
+            "DELETE FROM strategy_points
+             WHERE strategy_id = ?1
+               AND timestamp < ?2
+               AND action IS NULL
+               AND id != ?4
+               AND id NOT IN (
+                   SELECT id FROM (
+                       SELECT id, ROW_NUMBER() OVER (
+                           PARTITION BY timestamp / ?3 ORDER BY timestamp DESC, id DESC
+                       ) AS rank
+                       FROM strategy_points
+                       WHERE strategy_id = ?1 AND timestamp < ?2
+                   ) WHERE rank = 1
+               )",
+            params![
+                strategy_id,
+                cutoff.timestamp(),
+                resolution.num_seconds().max(1),
+                first_id.unwrap_or(-1),
+            ],
+        )?;
+
+        tx.commit()?;
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::PerformanceTracker;
+
+    fn point(secs: i64, value: f64, action: Option<&str>) -> TimeSeriesData {
+        TimeSeriesData {
+            timestamp: from_unix(secs),
+            value,
+            sentiment_score: 50.0,
+            action: action.map(str::to_string),
+            gas: None,
+            gas_cost: 0.0,
+        }
+    }
+
+    fn strategy(points: Vec<TimeSeriesData>) -> StrategyPerformance {
+        StrategyPerformance {
+            strategy_id: "sentiment".to_string(),
+            initial_value: 100.0,
+            current_value: points.last().map_or(100.0, |p| p.value),
+            apy: 7.5,
+            gross_apy: 7.5,
+            gas_spent: 0.0,
+            rebalance_count: 1,
+            last_update: points.last().map_or_else(Utc::now, |p| p.timestamp),
+            historical_values: points,
+            comparison: ComparisonMetrics::default(),
+        }
+    }
+
+    fn hourly_points() -> Vec<TimeSeriesData> {
+        // Two days of hourly points with a rebalance at hour 5
+        (0..48)
+            .map(|h| point(h * 3600, 100.0 + h as f64, (h == 5).then_some("rebalance")))
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_strategies_and_points() {
+        let store = SqlitePerformanceStore::in_memory().unwrap();
+        let strategy = strategy(hourly_points());
+        store.save_strategy(&strategy).unwrap();
+        for p in &strategy.historical_values {
+            store.append_point(&strategy.strategy_id, p).unwrap();
+        }
+
+        let loaded = store.load_strategies().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].strategy_id, "sentiment");
+        assert_eq!(loaded[0].apy, 7.5);
+        assert_eq!(loaded[0].historical_values.len(), 48);
+        assert_eq!(loaded[0].historical_values[5].action.as_deref(), Some("rebalance"));
+    }
+
+    #[test]
+    fn compaction_matches_in_memory_rule() {
+        let store = SqlitePerformanceStore::in_memory().unwrap();
+        let mut points = hourly_points();
+        for p in &points {
+            store.append_point("sentiment", p).unwrap();
+        }
+
+        // Compact the first day to 6-hour buckets
+        let cutoff = from_unix(24 * 3600);
+        let removed_db = store.compact("sentiment", cutoff, Duration::hours(6)).unwrap();
+        let removed_mem = compact_points(&mut points, cutoff, Duration::hours(6));
+        assert_eq!(removed_db, removed_mem);
+
+        let stored = SqlitePerformanceStore::load_points(&store.conn.lock().unwrap(), "sentiment").unwrap();
+        let stored: Vec<i64> = stored.iter().map(|p| p.timestamp.timestamp() / 3600).collect();
+        let kept: Vec<i64> = points.iter().map(|p| p.timestamp.timestamp() / 3600).collect();
+        assert_eq!(stored, kept);
+
+        // First point, the rebalance and the last point of each bucket survive
+        assert_eq!(&kept[..6], &[0, 5, 11, 17, 23, 24]);
+    }
+
+    #[test]
+    fn tracker_reloads_history_on_startup() {
+        let path = std::env::temp_dir().join(format!("performance-store-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        {
+            let store = SqlitePerformanceStore::open(path).unwrap();
+            let mut tracker = PerformanceTracker::with_store(Box::new(store)).unwrap();
+            tracker.register_strategy("sentiment", 100.0, from_unix(0));
+            tracker.record_market(MarketSnapshot {
+                timestamp: from_unix(0),
+                near_price: 4.0,
+                staking_apy: 9.5,
+                stable_apy: 6.0,
+            });
+            tracker.record_action("sentiment", from_unix(86_400), 101.0, 60.0, "rebalance".to_string(), GasUsage {
+                gas_burnt: 25_000_000_000_000,
+                tokens_burnt: U128(2_500_000_000_000_000_000_000),
+            });
+        }
+
+        let store = SqlitePerformanceStore::open(path).unwrap();
+        let mut tracker = PerformanceTracker::with_store(Box::new(store)).unwrap();
+        // Registering again must not reset the loaded history
+        tracker.register_strategy("sentiment", 100.0, from_unix(172_800));
+
+        let strategy = tracker.get_strategy("sentiment").unwrap();
+        assert_eq!(strategy.historical_values.len(), 2);
+        assert_eq!(strategy.current_value, 101.0);
+        assert_eq!(strategy.rebalance_count, 1);
+        assert_eq!(strategy.historical_values[1].gas.unwrap().tokens_burnt.0, 2_500_000_000_000_000_000_000);
+        assert!((strategy.gas_spent - 0.01).abs() < 1e-12);
+        assert!(strategy.comparison.hold_near_apy.is_some());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,96 +1,1676 @@
 use async_trait::async_trait;
 use near_sdk::json_types::U128;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
 use crate::config::PoolMetrics;
+use crate::rebalance::LegAction;
+use crate::rpc::{NearRpc, TxFailure, TxOutcome};
 
 #[async_trait]
-pub trait DeFiProtocol {
+pub trait DeFiProtocol: Send + Sync {
     async fn get_pools(&self) -> Result<Vec<PoolMetrics>, Box<dyn Error>>;
     async fn get_pool_balance(&self, pool_id: &str) -> Result<U128, Box<dyn Error>>;
-    async fn deposit(&self, pool_id: &str, amount: U128) -> Result<(), Box<dyn Error>>;
-    async fn withdraw(&self, pool_id: &str, amount: U128) -> Result<(), Box<dyn Error>>;
+    /// yoctoNEAR value of the signer's position in the pool.
+    async fn position_value(&self, pool_id: &str) -> Result<U128, Box<dyn Error>>;
+    /// Converts a yoctoNEAR value into the amount `deposit` takes for the pool.
+    async fn deposit_amount(&self, pool_id: &str, value: U128) -> Result<U128, Box<dyn Error>>;
+    /// Returns the outcome of every transaction sent, in order. `max_slippage` is in
+    /// percent; adapters whose deposits don't move a price ignore it.
+    async fn deposit(&self, pool_id: &str, amount: U128, max_slippage: f64) -> Result<Vec<TxOutcome>, Box<dyn Error>>;
+    /// Returns the outcome of every transaction sent, in order. `max_slippage` is in
+    /// percent; adapters whose withdrawals don't move a price ignore it.
+    async fn withdraw(&self, pool_id: &str, amount: U128, max_slippage: f64) -> Result<Vec<TxOutcome>, Box<dyn Error>>;
+    /// Completes withdrawals that only settle after a delay, e.g. LiNEAR's unstaked
+    /// NEAR, and returns the transactions sent. Most protocols settle immediately.
+    async fn settle_withdrawals(&self) -> Result<Vec<TxOutcome>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+}
+
+/// A deposit or withdrawal that failed after some of its transactions had landed.
+/// Adapters return it boxed so callers can still account for what was sent.
+#[derive(Debug)]
+pub struct PartialExecution {
+    /// Every transaction that landed, including any sent to recover funds.
+    pub outcomes: Vec<TxOutcome>,
+    pub error: String,
+}
+
+impl fmt::Display for PartialExecution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (after {} transactions)", self.error, self.outcomes.len())
+    }
+}
+
+impl Error for PartialExecution {}
+
+impl PartialExecution {
+    /// The transactions that landed before `error`, whether it is a partial
+    /// execution or a single transaction that failed after burning gas.
+    pub fn outcomes_of(error: &(dyn Error + 'static)) -> Vec<TxOutcome> {
+        if let Some(partial) = error.downcast_ref::<PartialExecution>() {
+            partial.outcomes.clone()
+        } else if let Some(failure) = error.downcast_ref::<TxFailure>() {
+            vec![failure.outcome.clone()]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+const YEAR_SECS: f64 = 365.0 * 24.0 * 3600.0;
+const ONE_YOCTO: u128 = 1;
+const FT_TRANSFER_CALL_GAS: u64 = 100_000_000_000_000;
+const ADD_LIQUIDITY_GAS: u64 = 50_000_000_000_000;
+const REMOVE_LIQUIDITY_GAS: u64 = 50_000_000_000_000;
+const REF_WITHDRAW_GAS: u64 = 50_000_000_000_000;
+const BURROW_EXECUTE_GAS: u64 = 100_000_000_000_000;
+const LINEAR_GAS: u64 = 50_000_000_000_000;
+const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
+/// wNEAR, the NEP-141 wrapped NEAR.
+pub const WRAP_NEAR: &str = "wrap.near";
+// Covers LP share storage on first deposit; Ref refunds the unused part.
+const LP_STORAGE_DEPOSIT: u128 = 10_000_000_000_000_000_000_000;
+
+/// Prepaid gas for one deposit or withdrawal through the named protocol's adapter.
+pub fn estimated_gas(protocol: &str, action: LegAction) -> u64 {
+    match (protocol, action) {
+        // One token transfer per side of the pair, then the liquidity call
+        ("ref_finance", LegAction::Deposit) => 2 * FT_TRANSFER_CALL_GAS + ADD_LIQUIDITY_GAS,
+        // The liquidity call, then one deposit withdrawal per side of the pair
+        ("ref_finance", LegAction::Withdraw) => REMOVE_LIQUIDITY_GAS + 2 * REF_WITHDRAW_GAS,
+        ("burrow", LegAction::Deposit) => FT_TRANSFER_CALL_GAS,
+        ("burrow", LegAction::Withdraw) => BURROW_EXECUTE_GAS,
+        ("linear", _) => LINEAR_GAS,
+        _ => FT_TRANSFER_CALL_GAS,
+    }
+}
+
+/// Appends `value` to `path` as one JSON line, creating the file if needed.
+fn append_json_line(path: &str, value: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    use std::io::Write;
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(value)?)?;
+    Ok(())
+}
+
+/// `a * b / c`, falling back to floating point when the product overflows.
+fn mul_div(a: u128, b: u128, c: u128) -> u128 {
+    match a.checked_mul(b) {
+        Some(product) => product / c,
+        None => (a as f64 * b as f64 / c as f64) as u128,
+    }
+}
+
+pub(crate) const STABLECOINS: &[&str] = &["usdc", "usdt", "dai", "usn", "frax", "cusd"];
+
+/// Normalized symbol used in pool tags, e.g. `wNEAR` -> `near`, `USDC.e` -> `usdc`.
+fn symbol_tag(symbol: &str) -> String {
+    let symbol = symbol.to_lowercase();
+    let symbol = symbol.trim_end_matches(".e");
+    if symbol == "wnear" {
+        "near".to_string()
+    } else {
+        symbol.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenMetadata {
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// One token in a `list-token-price` feed; the price is a decimal string.
+#[derive(Debug, Deserialize)]
+struct FeedPrice {
+    price: String,
+}
+
+/// Token metadata cache and USD price table shared by the adapters to value reserves.
+#[derive(Default)]
+pub struct TokenBook {
+    prices: RwLock<HashMap<String, f64>>,
+    metadata: Mutex<HashMap<String, TokenMetadata>>,
+}
+
+impl TokenBook {
+    pub fn set_prices(&self, prices: HashMap<String, f64>) {
+        *self.prices.write().unwrap() = prices;
+    }
+
+    /// Replaces the price table from a Ref indexer style feed at `feed_url` and
+    /// returns the number of tokens priced.
+    pub async fn refresh_prices(&self, feed_url: &str) -> Result<usize, Box<dyn Error>> {
+        let feed: serde_json::Value = reqwest::get(feed_url).await?.error_for_status()?.json().await?;
+        self.set_prices_from_feed(feed)
+    }
+
+    /// Loads `{ "<token_id>": { "price": "<usd>", .. } }`, skipping unparsable prices.
+    fn set_prices_from_feed(&self, feed: serde_json::Value) -> Result<usize, Box<dyn Error>> {
+        let feed: HashMap<String, FeedPrice> = serde_json::from_value(feed)?;
+        let prices: HashMap<String, f64> = feed
+            .into_iter()
+            .filter_map(|(token_id, entry)| entry.price.parse().ok().map(|price| (token_id, price)))
+            .collect();
+        let count = prices.len();
+        self.set_prices(prices);
+        Ok(count)
+    }
+
+    pub fn price(&self, token_id: &str) -> Option<f64> {
+        self.prices.read().unwrap().get(token_id).copied()
+    }
+
+    pub async fn metadata(&self, rpc: &dyn NearRpc, token_id: &str) -> Result<TokenMetadata, Box<dyn Error>> {
+        if let Some(metadata) = self.metadata.lock().unwrap().get(token_id) {
+            return Ok(metadata.clone());
+        }
+
+        let metadata: TokenMetadata =
+            serde_json::from_value(rpc.view(token_id, "ft_metadata", json!({})).await?)?;
+        self.metadata
+            .lock()
+            .unwrap()
+            .insert(token_id.to_string(), metadata.clone());
+        Ok(metadata)
+    }
+
+    /// USD value of a raw token amount, or `None` when the token has no price.
+    pub async fn usd_value(
+        &self,
+        rpc: &dyn NearRpc,
+        token_id: &str,
+        raw_amount: u128,
+    ) -> Result<Option<f64>, Box<dyn Error>> {
+        let price = match self.price(token_id) {
+            Some(price) => price,
+            None => return Ok(None),
+        };
+        let decimals = self.metadata(rpc, token_id).await?.decimals;
+        Ok(Some(raw_amount as f64 / 10f64.powi(decimals as i32) * price))
+    }
+
+    /// USD price of NEAR, taken from wNEAR.
+    pub fn near_price(&self) -> Option<f64> {
+        self.price(WRAP_NEAR)
+    }
+
+    /// yoctoNEAR worth `usd` at the wNEAR price.
+    pub fn usd_to_near(&self, usd: f64) -> Result<U128, Box<dyn Error>> {
+        let near_price = self.near_price().ok_or("wrap.near has no price")?;
+        Ok(U128((usd / near_price * ONE_NEAR as f64) as u128))
+    }
+
+    /// Raw amount of `token_id` worth `value` yoctoNEAR. wNEAR converts one to one.
+    pub async fn near_to_token(
+        &self,
+        rpc: &dyn NearRpc,
+        token_id: &str,
+        value: U128,
+    ) -> Result<U128, Box<dyn Error>> {
+        if token_id == WRAP_NEAR {
+            return Ok(value);
+        }
+        let near_price = self.near_price().ok_or("wrap.near has no price")?;
+        let token_price = self
+            .price(token_id)
+            .ok_or_else(|| format!("{} has no price", token_id))?;
+        let decimals = self.metadata(rpc, token_id).await?.decimals;
+        let usd = value.0 as f64 / ONE_NEAR as f64 * near_price;
+        Ok(U128((usd / token_price * 10f64.powi(decimals as i32)) as u128))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RefPool {
+    pool_kind: String,
+    token_account_ids: Vec<String>,
+    amounts: Vec<U128>,
+    total_fee: u32,
+    shares_total_supply: U128,
 }
 
+impl RefPool {
+    fn is_stable(&self) -> bool {
+        self.pool_kind == "STABLE_SWAP" || self.pool_kind == "RATED_SWAP"
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RefPoolVolume {
+    input: U128,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct VolumeSnapshot {
+    timestamp: i64,
+    volume_usd: f64,
+    apy: Option<f64>,
+}
+
+/// Ref Finance AMM adapter. Pool ids are Ref's numeric pool indices.
 pub struct RefFinance {
     contract_id: String,
-    rpc_client: near_jsonrpc_client::JsonRpcClient,
+    rpc: Arc<dyn NearRpc>,
+    tokens: Arc<TokenBook>,
+    /// Pools to report; all pools are paged through when empty.
+    tracked_pools: Vec<u64>,
+    volume_snapshots: Mutex<HashMap<u64, VolumeSnapshot>>,
+}
+
+impl RefFinance {
+    /// Fee share left to LPs after Ref's admin/referral cut.
+    const LP_FEE_SHARE: f64 = 0.8;
+    /// Minimum time between volume snapshots used to annualize fee income.
+    const MIN_APY_WINDOW_SECS: i64 = 3600;
+    const PAGE_SIZE: u64 = 100;
+
+    pub fn new(contract_id: &str, rpc: Arc<dyn NearRpc>, tokens: Arc<TokenBook>) -> Self {
+        Self {
+            contract_id: contract_id.to_string(),
+            rpc,
+            tokens,
+            tracked_pools: Vec::new(),
+            volume_snapshots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_tracked_pools(mut self, pool_ids: Vec<u64>) -> Self {
+        self.tracked_pools = pool_ids;
+        self
+    }
+
+    async fn fetch_pools(&self) -> Result<Vec<(u64, RefPool)>, Box<dyn Error>> {
+        if !self.tracked_pools.is_empty() {
+            let mut pools = Vec::new();
+            for &pool_id in &self.tracked_pools {
+                match self.fetch_pool(pool_id).await {
+                    Ok(pool) => pools.push((pool_id, pool)),
+                    Err(e) => log::warn!("Skipping Ref Finance pool {}: {}", pool_id, e),
+                }
+            }
+            return Ok(pools);
+        }
+
+        let count: u64 = serde_json::from_value(
+            self.rpc
+                .view(&self.contract_id, "get_number_of_pools", json!({}))
+                .await?,
+        )?;
+
+        let mut pools = Vec::new();
+        let mut from_index = 0;
+        while from_index < count {
+            let page: Vec<RefPool> = serde_json::from_value(
+                self.rpc
+                    .view(
+                        &self.contract_id,
+                        "get_pools",
+                        json!({ "from_index": from_index, "limit": Self::PAGE_SIZE }),
+                    )
+                    .await?,
+            )?;
+            if page.is_empty() {
+                break;
+            }
+            for (offset, pool) in page.into_iter().enumerate() {
+                pools.push((from_index + offset as u64, pool));
+            }
+            from_index += Self::PAGE_SIZE;
+        }
+        Ok(pools)
+    }
+
+    async fn fetch_pool(&self, pool_id: u64) -> Result<RefPool, Box<dyn Error>> {
+        Ok(serde_json::from_value(
+            self.rpc
+                .view(&self.contract_id, "get_pool", json!({ "pool_id": pool_id }))
+                .await?,
+        )?)
+    }
+
+    /// Pool TVL in USD, or `None` if any of its tokens is unpriced.
+    async fn pool_tvl(&self, pool: &RefPool) -> Result<Option<f64>, Box<dyn Error>> {
+        let mut tvl = 0.0;
+        for (token_id, amount) in pool.token_account_ids.iter().zip(&pool.amounts) {
+            match self.tokens.usd_value(self.rpc.as_ref(), token_id, amount.0).await? {
+                Some(value) => tvl += value,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(tvl))
+    }
+
+    /// Annualized LP fee APY from the change in cumulative swap volume since the last
+    /// snapshot, or `None` until a snapshot at least `MIN_APY_WINDOW_SECS` old exists.
+    async fn fee_apy(&self, pool_id: u64, pool: &RefPool, tvl: f64) -> Result<Option<f64>, Box<dyn Error>> {
+        let volumes: Vec<RefPoolVolume> = serde_json::from_value(
+            self.rpc
+                .view(&self.contract_id, "get_pool_volumes", json!({ "pool_id": pool_id }))
+                .await?,
+        )?;
+
+        let mut volume_usd = 0.0;
+        for (token_id, volume) in pool.token_account_ids.iter().zip(&volumes) {
+            volume_usd += self
+                .tokens
+                .usd_value(self.rpc.as_ref(), token_id, volume.input.0)
+                .await?
+                .unwrap_or(0.0);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let mut snapshots = self.volume_snapshots.lock().unwrap();
+        let snapshot = match snapshots.get(&pool_id) {
+            Some(previous) if now - previous.timestamp < Self::MIN_APY_WINDOW_SECS => return Ok(previous.apy),
+            Some(previous) if tvl > 0.0 => {
+                let elapsed = (now - previous.timestamp) as f64;
+                let fee_rate = pool.total_fee as f64 / 10_000.0 * Self::LP_FEE_SHARE;
+                let fees = (volume_usd - previous.volume_usd).max(0.0) * fee_rate;
+                VolumeSnapshot {
+                    timestamp: now,
+                    volume_usd,
+                    apy: Some(fees / tvl * (YEAR_SECS / elapsed) * 100.0),
+                }
+            }
+            _ => VolumeSnapshot { timestamp: now, volume_usd, apy: None },
+        };
+        snapshots.insert(pool_id, snapshot);
+        Ok(snapshot.apy)
+    }
+
+    /// Metrics for a two-token pool, or `None` if any of its tokens is unpriced.
+    async fn pool_metrics(&self, pool_id: u64, pool: &RefPool) -> Result<Option<PoolMetrics>, Box<dyn Error>> {
+        let tvl = match self.pool_tvl(pool).await? {
+            Some(tvl) => tvl,
+            None => return Ok(None),
+        };
+        let apy = self.fee_apy(pool_id, pool, tvl).await?;
+        let tags = self.pool_tags(pool).await?;
+
+        Ok(Some(PoolMetrics {
+            protocol: "ref_finance".to_string(),
+            pool_id: pool_id.to_string(),
+            apy,
+            tvl: tvl.round() as u64,
+            risk_score: Self::risk_score(pool, tvl),
+            audit_status: true,
+            token_pair: (
+                pool.token_account_ids[0].clone(),
+                pool.token_account_ids[1].clone(),
+            ),
+            chain_factor: 1.0,
+            tags,
+            risk_bucket: None,
+        }))
+    }
+
+    /// Pair labels in both orders plus one of `stable_pool`, `near_stable` or `near_meta`.
+    async fn pool_tags(&self, pool: &RefPool) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut symbols = Vec::new();
+        for token_id in &pool.token_account_ids {
+            symbols.push(symbol_tag(&self.tokens.metadata(self.rpc.as_ref(), token_id).await?.symbol));
+        }
+        let (a, b) = (&symbols[0], &symbols[1]);
+
+        let category = if pool.is_stable() {
+            "stable_pool"
+        } else if a == "near" || b == "near" {
+            let other = if a == "near" { b } else { a };
+            if STABLECOINS.contains(&other.as_str()) {
+                "near_stable"
+            } else {
+                "near_meta"
+            }
+        } else {
+            "pair"
+        };
+
+        Ok(vec![
+            format!("{}_{}", a, b),
+            format!("{}_{}", b, a),
+            category.to_string(),
+        ])
+    }
+
+    fn risk_score(pool: &RefPool, tvl: f64) -> f64 {
+        let base = if pool.is_stable() { 0.9 } else { 0.7 };
+        let depth = if tvl >= 10_000_000.0 {
+            0.05
+        } else if tvl >= 1_000_000.0 {
+            0.0
+        } else {
+            -0.1
+        };
+        (base + depth).clamp(0.0, 1.0)
+    }
+
+    fn parse_pool_id(pool_id: &str) -> Result<u64, Box<dyn Error>> {
+        pool_id
+            .parse()
+            .map_err(|_| format!("Invalid Ref Finance pool id: {}", pool_id).into())
+    }
+
+    /// `amount` less `max_slippage` percent.
+    fn with_slippage_floor(amount: u128, max_slippage: f64) -> U128 {
+        let slippage_bps = (max_slippage * 100.0).round().clamp(0.0, 10_000.0) as u128;
+        U128(amount - mul_div(amount, slippage_bps, 10_000))
+    }
+
+    /// Transfers `amounts` into the signer's Ref deposit, then adds them to the pool.
+    /// Each landed transaction is pushed to `outcomes`.
+    async fn add_liquidity(
+        &self,
+        id: u64,
+        pool: &RefPool,
+        amounts: &[U128],
+        max_slippage: f64,
+        outcomes: &mut Vec<TxOutcome>,
+    ) -> Result<(), Box<dyn Error>> {
+        for (token_id, token_amount) in pool.token_account_ids.iter().zip(amounts) {
+            let outcome = self.rpc
+                .call(
+                    token_id,
+                    "ft_transfer_call",
+                    json!({ "receiver_id": self.contract_id, "amount": token_amount, "msg": "" }),
+                    ONE_YOCTO,
+                    FT_TRANSFER_CALL_GAS,
+                )
+                .await?;
+            outcomes.push(outcome);
+        }
+
+        if pool.is_stable() {
+            let predicted: U128 = serde_json::from_value(
+                self.rpc
+                    .view(
+                        &self.contract_id,
+                        "predict_add_stable_liquidity",
+                        json!({ "pool_id": id, "amounts": amounts }),
+                    )
+                    .await?,
+            )?;
+            let outcome = self.rpc
+                .call(
+                    &self.contract_id,
+                    "add_stable_liquidity",
+                    json!({
+                        "pool_id": id,
+                        "amounts": amounts,
+                        "min_shares": Self::with_slippage_floor(predicted.0, max_slippage),
+                    }),
+                    LP_STORAGE_DEPOSIT,
+                    ADD_LIQUIDITY_GAS,
+                )
+                .await?;
+            outcomes.push(outcome);
+        } else {
+            // Simple pools take the amounts at the reserve ratio, which may have moved
+            let min_amounts: Vec<U128> = amounts
+                .iter()
+                .map(|amount| Self::with_slippage_floor(amount.0, max_slippage))
+                .collect();
+            let outcome = self.rpc
+                .call(
+                    &self.contract_id,
+                    "add_liquidity",
+                    json!({ "pool_id": id, "amounts": amounts, "min_amounts": min_amounts }),
+                    LP_STORAGE_DEPOSIT,
+                    ADD_LIQUIDITY_GAS,
+                )
+                .await?;
+            outcomes.push(outcome);
+        }
+
+        Ok(())
+    }
+
+    /// The signer's balance of `token_id` in its Ref deposit.
+    async fn deposit_balance(&self, token_id: &str) -> Result<U128, Box<dyn Error>> {
+        Ok(serde_json::from_value(
+            self.rpc
+                .view(
+                    &self.contract_id,
+                    "get_deposit",
+                    json!({ "account_id": self.rpc.account_id(), "token_id": token_id }),
+                )
+                .await?,
+        )?)
+    }
+
+    /// Moves `amount` of `token_id` from the signer's Ref deposit back to its wallet.
+    async fn withdraw_deposit(&self, token_id: &str, amount: U128) -> Result<TxOutcome, Box<dyn Error>> {
+        self.rpc
+            .call(
+                &self.contract_id,
+                "withdraw",
+                json!({ "token_id": token_id, "amount": amount, "unregister": false }),
+                ONE_YOCTO,
+                REF_WITHDRAW_GAS,
+            )
+            .await
+    }
 }
 
 #[async_trait]
 impl DeFiProtocol for RefFinance {
+    /// A pool that fails to load or value is logged and left out, so one bad pool
+    /// doesn't hide the rest.
     async fn get_pools(&self) -> Result<Vec<PoolMetrics>, Box<dyn Error>> {
-        // Implementation for Ref Finance pool fetching
-        todo!()
+        let mut metrics = Vec::new();
+
+        for (pool_id, pool) in self.fetch_pools().await? {
+            // Only two-token pools map onto `PoolMetrics::token_pair`
+            if pool.token_account_ids.len() != 2 {
+                continue;
+            }
+            match self.pool_metrics(pool_id, &pool).await {
+                Ok(Some(pool_metrics)) => metrics.push(pool_metrics),
+                Ok(None) => {}
+                Err(e) => log::warn!("Skipping Ref Finance pool {}: {}", pool_id, e),
+            }
+        }
+
+        Ok(metrics)
     }
 
+    /// Returns the LP shares held by the signer account.
     async fn get_pool_balance(&self, pool_id: &str) -> Result<U128, Box<dyn Error>> {
-        // Implementation for balance checking
-        todo!()
+        let shares = self
+            .rpc
+            .view(
+                &self.contract_id,
+                "get_pool_shares",
+                json!({
+                    "pool_id": Self::parse_pool_id(pool_id)?,
+                    "account_id": self.rpc.account_id(),
+                }),
+            )
+            .await?;
+        Ok(serde_json::from_value(shares)?)
+    }
+
+    /// Values the signer's share of each reserve at the book's prices.
+    async fn position_value(&self, pool_id: &str) -> Result<U128, Box<dyn Error>> {
+        let shares = self.get_pool_balance(pool_id).await?.0;
+        if shares == 0 {
+            return Ok(U128(0));
+        }
+        let pool = self.fetch_pool(Self::parse_pool_id(pool_id)?).await?;
+        let total_shares = pool.shares_total_supply.0;
+        if total_shares == 0 {
+            return Ok(U128(0));
+        }
+
+        let mut usd = 0.0;
+        for (token_id, reserve) in pool.token_account_ids.iter().zip(&pool.amounts) {
+            let amount = mul_div(reserve.0, shares, total_shares);
+            usd += self
+                .tokens
+                .usd_value(self.rpc.as_ref(), token_id, amount)
+                .await?
+                .ok_or_else(|| format!("{} has no price", token_id))?;
+        }
+        self.tokens.usd_to_near(usd)
     }
 
-    async fn deposit(&self, pool_id: &str, amount: U128) -> Result<(), Box<dyn Error>> {
-        // Implementation for deposits
-        todo!()
+    /// `deposit` takes the pool's first token and adds the others at the reserve
+    /// ratio, so only the first token's share of the pool value is converted.
+    async fn deposit_amount(&self, pool_id: &str, value: U128) -> Result<U128, Box<dyn Error>> {
+        let pool = self.fetch_pool(Self::parse_pool_id(pool_id)?).await?;
+        let token_id = &pool.token_account_ids[0];
+        let tvl = self
+            .pool_tvl(&pool)
+            .await?
+            .ok_or_else(|| format!("Ref Finance pool {} has unpriced tokens", pool_id))?;
+        let reserve0 = pool.amounts.first().map(|a| a.0).unwrap_or(0);
+        let reserve0_usd = self
+            .tokens
+            .usd_value(self.rpc.as_ref(), token_id, reserve0)
+            .await?
+            .unwrap_or(0.0);
+        if tvl <= 0.0 {
+            return Err(format!("Ref Finance pool {} has no liquidity", pool_id).into());
+        }
+
+        let amount = self.tokens.near_to_token(self.rpc.as_ref(), token_id, value).await?;
+        Ok(U128((amount.0 as f64 * reserve0_usd / tvl) as u128))
     }
 
-    async fn withdraw(&self, pool_id: &str, amount: U128) -> Result<(), Box<dyn Error>> {
-        // Implementation for withdrawals
-        todo!()
+    /// Adds liquidity with `amount` of the pool's first token and the matching
+    /// amounts of the others at the current reserve ratio. Tokens are moved into
+    /// the signer's Ref deposit first, so wNEAR must already be wrapped. If a later
+    /// step fails, the tokens already transferred are withdrawn back to the signer
+    /// and a `PartialExecution` is returned.
+    async fn deposit(&self, pool_id: &str, amount: U128, max_slippage: f64) -> Result<Vec<TxOutcome>, Box<dyn Error>> {
+        let id = Self::parse_pool_id(pool_id)?;
+        let pool = self.fetch_pool(id).await?;
+        let reserve0 = pool.amounts.first().map(|a| a.0).unwrap_or(0);
+        if reserve0 == 0 {
+            return Err(format!("Ref Finance pool {} has no liquidity", pool_id).into());
+        }
+
+        let amounts: Vec<U128> = pool
+            .amounts
+            .iter()
+            .map(|reserve| U128(mul_div(amount.0, reserve.0, reserve0)))
+            .collect();
+
+        let mut outcomes = Vec::new();
+        let error = match self.add_liquidity(id, &pool, &amounts, max_slippage, &mut outcomes).await {
+            Ok(()) => return Ok(outcomes),
+            Err(e) if outcomes.is_empty() => return Err(e),
+            Err(e) => e,
+        };
+
+        // Every outcome so far is a token transfer, in pool token order
+        let transferred = outcomes.len();
+        outcomes.extend(PartialExecution::outcomes_of(error.as_ref()));
+        let mut error = error.to_string();
+        for (token_id, token_amount) in pool.token_account_ids.iter().zip(&amounts).take(transferred) {
+            match self.withdraw_deposit(token_id, *token_amount).await {
+                Ok(outcome) => outcomes.push(outcome),
+                Err(e) => {
+                    outcomes.extend(PartialExecution::outcomes_of(e.as_ref()));
+                    error = format!("{}; {} left in the Ref deposit: {}", error, token_id, e);
+                }
+            }
+        }
+        Err(Box::new(PartialExecution { outcomes, error }))
+    }
+
+    /// Burns `amount` LP shares, then withdraws each pool token's Ref deposit back
+    /// to the signer's wallet. Tokens a failed withdrawal leaves in the deposit are
+    /// reported in a `PartialExecution` and swept by the next withdrawal.
+    async fn withdraw(&self, pool_id: &str, amount: U128, max_slippage: f64) -> Result<Vec<TxOutcome>, Box<dyn Error>> {
+        let id = Self::parse_pool_id(pool_id)?;
+        let pool = self.fetch_pool(id).await?;
+        let total_shares = pool.shares_total_supply.0;
+        if total_shares == 0 {
+            return Err(format!("Ref Finance pool {} has no shares", pool_id).into());
+        }
+
+        let min_amounts: Vec<U128> = pool
+            .amounts
+            .iter()
+            .map(|reserve| Self::with_slippage_floor(mul_div(reserve.0, amount.0, total_shares), max_slippage))
+            .collect();
+
+        let outcome = self.rpc
+            .call(
+                &self.contract_id,
+                "remove_liquidity",
+                json!({ "pool_id": id, "shares": amount, "min_amounts": min_amounts }),
+                ONE_YOCTO,
+                REMOVE_LIQUIDITY_GAS,
+            )
+            .await?;
+
+        let mut outcomes = vec![outcome];
+        let mut errors = Vec::new();
+        for token_id in &pool.token_account_ids {
+            let balance = match self.deposit_balance(token_id).await {
+                Ok(balance) => balance,
+                Err(e) => {
+                    errors.push(format!("{} left in the Ref deposit: {}", token_id, e));
+                    continue;
+                }
+            };
+            if balance.0 == 0 {
+                continue;
+            }
+            match self.withdraw_deposit(token_id, balance).await {
+                Ok(outcome) => outcomes.push(outcome),
+                Err(e) => {
+                    outcomes.extend(PartialExecution::outcomes_of(e.as_ref()));
+                    errors.push(format!("{} left in the Ref deposit: {}", token_id, e));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(outcomes)
+        } else {
+            Err(Box::new(PartialExecution { outcomes, error: errors.join("; ") }))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BurrowPool {
+    balance: U128,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BurrowAssetConfig {
+    reserve_ratio: u32,
+    target_utilization: u32,
+    target_utilization_rate: String,
+    max_utilization_rate: String,
+    extra_decimals: u8,
+    can_deposit: bool,
+    can_withdraw: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BurrowAsset {
+    supplied: BurrowPool,
+    borrowed: BurrowPool,
+    reserved: U128,
+    config: BurrowAssetConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BurrowAssetView {
+    token_id: String,
+    balance: U128,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct BurrowAccount {
+    supplied: Vec<BurrowAssetView>,
+}
+
+/// Snapshot of a single Burrow lending market.
+#[derive(Debug, Clone, Serialize)]
+pub struct BurrowMarket {
+    pub token_id: String,
+    pub symbol: String,
+    pub supplied_usd: f64,
+    pub borrowed_usd: f64,
+    /// Borrowed share of the supplied liquidity, 0.0 - 1.0.
+    pub utilization: f64,
+    pub borrow_apr: f64,
+    pub supply_apy: f64,
+    /// Share of borrow interest retained by the protocol reserve, 0.0 - 1.0.
+    pub reserve_ratio: f64,
+    /// Protocol reserves relative to total supplied liquidity, 0.0 - 1.0.
+    pub reserves_to_supply: f64,
+    pub can_withdraw: bool,
+}
+
+impl BurrowMarket {
+    /// Lenders can only exit from idle liquidity, so safety falls off as utilization
+    /// approaches 100%; markets that block withdrawals are never safe.
+    pub fn risk_score(&self) -> f64 {
+        if !self.can_withdraw {
+            return 0.0;
+        }
+        let utilization_penalty = ((self.utilization - 0.6).max(0.0) * 1.25).min(0.5);
+        let reserve_bonus = (self.reserves_to_supply * 0.5).min(0.05);
+        (0.9 - utilization_penalty + reserve_bonus).clamp(0.0, 1.0)
     }
 }
 
+/// Burrow lending adapter. Pool ids are the market's token account id and amounts
+/// are in the token's own decimals.
 pub struct Burrow {
     contract_id: String,
-    rpc_client: near_jsonrpc_client::JsonRpcClient,
+    rpc: Arc<dyn NearRpc>,
+    tokens: Arc<TokenBook>,
+}
+
+impl Burrow {
+    const PAGE_SIZE: u64 = 50;
+    const MS_PER_YEAR: f64 = 31_536_000_000.0;
+    const MAX_RATIO: f64 = 10_000.0;
+
+    pub fn new(contract_id: &str, rpc: Arc<dyn NearRpc>, tokens: Arc<TokenBook>) -> Self {
+        Self {
+            contract_id: contract_id.to_string(),
+            rpc,
+            tokens,
+        }
+    }
+
+    async fn fetch_assets(&self) -> Result<Vec<(String, BurrowAsset)>, Box<dyn Error>> {
+        let mut assets = Vec::new();
+        let mut from_index = 0;
+        loop {
+            let page: Vec<(String, BurrowAsset)> = serde_json::from_value(
+                self.rpc
+                    .view(
+                        &self.contract_id,
+                        "get_assets_paged",
+                        json!({ "from_index": from_index, "limit": Self::PAGE_SIZE }),
+                    )
+                    .await?,
+            )?;
+            let last_page = (page.len() as u64) < Self::PAGE_SIZE;
+            assets.extend(page);
+            if last_page {
+                return Ok(assets);
+            }
+            from_index += Self::PAGE_SIZE;
+        }
+    }
+
+    async fn fetch_asset(&self, token_id: &str) -> Result<BurrowAsset, Box<dyn Error>> {
+        let asset: Option<BurrowAsset> = serde_json::from_value(
+            self.rpc
+                .view(&self.contract_id, "get_asset", json!({ "token_id": token_id }))
+                .await?,
+        )?;
+        asset.ok_or_else(|| format!("Burrow has no market for {}", token_id).into())
+    }
+
+    /// Per-millisecond borrow rate above 1.0, following Burrow's piecewise-linear model.
+    fn borrow_rate_per_ms(config: &BurrowAssetConfig, utilization: f64) -> Result<f64, Box<dyn Error>> {
+        let target_rate = rate_excess(&config.target_utilization_rate)?;
+        let max_rate = rate_excess(&config.max_utilization_rate)?;
+        let target = config.target_utilization as f64 / Self::MAX_RATIO;
+
+        Ok(if utilization < target {
+            utilization * target_rate / target
+        } else {
+            target_rate + (utilization - target) * (max_rate - target_rate) / (1.0 - target)
+        })
+    }
+
+    async fn market(&self, token_id: &str, asset: &BurrowAsset) -> Result<BurrowMarket, Box<dyn Error>> {
+        let extra_decimals = asset.config.extra_decimals;
+        let total_supplied = asset.supplied.balance.0 + asset.reserved.0;
+        let borrowed = asset.borrowed.balance.0;
+
+        let utilization = if total_supplied > 0 {
+            borrowed as f64 / total_supplied as f64
+        } else {
+            0.0
+        };
+        let reserve_ratio = asset.config.reserve_ratio as f64 / Self::MAX_RATIO;
+        let borrow_apr = Self::borrow_rate_per_ms(&asset.config, utilization)? * Self::MS_PER_YEAR;
+        let supply_apr = borrow_apr * utilization * (1.0 - reserve_ratio);
+
+        let rpc = self.rpc.as_ref();
+        let symbol = self.tokens.metadata(rpc, token_id).await?.symbol;
+        let supplied_usd = self
+            .tokens
+            .usd_value(rpc, token_id, from_inner(total_supplied, extra_decimals))
+            .await?
+            .unwrap_or(0.0);
+        let borrowed_usd = self
+            .tokens
+            .usd_value(rpc, token_id, from_inner(borrowed, extra_decimals))
+            .await?
+            .unwrap_or(0.0);
+
+        Ok(BurrowMarket {
+            token_id: token_id.to_string(),
+            symbol,
+            supplied_usd,
+            borrowed_usd,
+            utilization,
+            borrow_apr,
+            supply_apy: supply_apr.exp() - 1.0,
+            reserve_ratio,
+            reserves_to_supply: if total_supplied > 0 {
+                asset.reserved.0 as f64 / total_supplied as f64
+            } else {
+                0.0
+            },
+            can_withdraw: asset.config.can_withdraw,
+        })
+    }
+
+    /// All markets that currently accept deposits.
+    pub async fn get_markets(&self) -> Result<Vec<BurrowMarket>, Box<dyn Error>> {
+        let mut markets = Vec::new();
+        for (token_id, asset) in self.fetch_assets().await? {
+            if asset.config.can_deposit {
+                markets.push(self.market(&token_id, &asset).await?);
+            }
+        }
+        Ok(markets)
+    }
+}
+
+/// Fractional part of a Burrow rate, which is stored as `1.0 + r` with 27 decimals.
+fn rate_excess(rate: &str) -> Result<f64, Box<dyn Error>> {
+    let rate: u128 = rate.parse()?;
+    Ok(rate.saturating_sub(10u128.pow(27)) as f64 / 1e27)
+}
+
+/// Converts an amount in Burrow's inner decimals to the token's own decimals.
+fn from_inner(amount: u128, extra_decimals: u8) -> u128 {
+    amount / 10u128.pow(extra_decimals as u32)
 }
 
 #[async_trait]
 impl DeFiProtocol for Burrow {
     async fn get_pools(&self) -> Result<Vec<PoolMetrics>, Box<dyn Error>> {
-        // Implementation for Burrow market fetching
-        todo!()
+        Ok(self
+            .get_markets()
+            .await?
+            .into_iter()
+            .map(|market| PoolMetrics {
+                protocol: "burrow".to_string(),
+                pool_id: market.token_id.clone(),
+                apy: Some(market.supply_apy * 100.0),
+                tvl: market.supplied_usd.round() as u64,
+                risk_score: market.risk_score(),
+                audit_status: true,
+                token_pair: (market.token_id.clone(), market.token_id.clone()),
+                chain_factor: 1.0,
+                tags: vec!["lending".to_string(), symbol_tag(&market.symbol)],
+                risk_bucket: None,
+            })
+            .collect())
     }
 
+    /// Returns the signer's supplied balance, in token decimals.
     async fn get_pool_balance(&self, pool_id: &str) -> Result<U128, Box<dyn Error>> {
-        // Implementation for balance checking
-        todo!()
+        let account: Option<BurrowAccount> = serde_json::from_value(
+            self.rpc
+                .view(
+                    &self.contract_id,
+                    "get_account",
+                    json!({ "account_id": self.rpc.account_id() }),
+                )
+                .await?,
+        )?;
+
+        let supplied = match account.and_then(|a| a.supplied.into_iter().find(|s| s.token_id == pool_id)) {
+            Some(supplied) => supplied.balance.0,
+            None => return Ok(U128(0)),
+        };
+        let extra_decimals = self.fetch_asset(pool_id).await?.config.extra_decimals;
+        Ok(U128(from_inner(supplied, extra_decimals)))
+    }
+
+    async fn position_value(&self, pool_id: &str) -> Result<U128, Box<dyn Error>> {
+        let balance = self.get_pool_balance(pool_id).await?;
+        if balance.0 == 0 {
+            return Ok(U128(0));
+        }
+        let usd = self
+            .tokens
+            .usd_value(self.rpc.as_ref(), pool_id, balance.0)
+            .await?
+            .ok_or_else(|| format!("{} has no price", pool_id))?;
+        self.tokens.usd_to_near(usd)
+    }
+
+    async fn deposit_amount(&self, pool_id: &str, value: U128) -> Result<U128, Box<dyn Error>> {
+        self.tokens.near_to_token(self.rpc.as_ref(), pool_id, value).await
     }
 
-    async fn deposit(&self, pool_id: &str, amount: U128) -> Result<(), Box<dyn Error>> {
-        // Implementation for deposits
-        todo!()
+    /// Supplies `amount` of the token by transferring it to Burrow with an empty message.
+    async fn deposit(&self, pool_id: &str, amount: U128, _max_slippage: f64) -> Result<Vec<TxOutcome>, Box<dyn Error>> {
+        let outcome = self.rpc
+            .call(
+                pool_id,
+                "ft_transfer_call",
+                json!({ "receiver_id": self.contract_id, "amount": amount, "msg": "" }),
+                ONE_YOCTO,
+                FT_TRANSFER_CALL_GAS,
+            )
+            .await?;
+        Ok(vec![outcome])
     }
 
-    async fn withdraw(&self, pool_id: &str, amount: U128) -> Result<(), Box<dyn Error>> {
-        // Implementation for withdrawals
-        todo!()
+    async fn withdraw(&self, pool_id: &str, amount: U128, _max_slippage: f64) -> Result<Vec<TxOutcome>, Box<dyn Error>> {
+        let extra_decimals = self.fetch_asset(pool_id).await?.config.extra_decimals;
+        let inner_amount = amount.0 * 10u128.pow(extra_decimals as u32);
+
+        let outcome = self.rpc
+            .call(
+                &self.contract_id,
+                "execute",
+                json!({
+                    "actions": [{
+                        "Withdraw": { "token_id": pool_id, "max_amount": U128(inner_amount) }
+                    }]
+                }),
+                ONE_YOCTO,
+                BURROW_EXECUTE_GAS,
+            )
+            .await?;
+        Ok(vec![outcome])
     }
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct LinearSummary {
+    total_staked_near_amount: U128,
+    ft_price: U128,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct LinearAccountDetails {
+    unstaked_balance: U128,
+    staked_balance: U128,
+    unstaked_available_epoch_height: u64,
+    can_withdraw: bool,
+}
+
+/// LiNEAR price in NEAR (24 decimals) observed at `timestamp` (unix seconds).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PriceSnapshot {
+    pub timestamp: i64,
+    pub price: U128,
+}
+
+/// Unstaked NEAR waiting out LiNEAR's withdrawal delay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingWithdrawal {
+    pub amount: U128,
+    pub requested_at: i64,
+    /// `None` if the account couldn't be read back after unstaking.
+    pub available_epoch: Option<u64>,
+}
+
+/// LiNEAR liquid staking adapter. There is a single pool, `linear`, and amounts are NEAR.
 pub struct Linear {
     contract_id: String,
-    rpc_client: near_jsonrpc_client::JsonRpcClient,
+    rpc: Arc<dyn NearRpc>,
+    tokens: Arc<TokenBook>,
+    price_history: Mutex<VecDeque<PriceSnapshot>>,
+    /// JSON-lines file new price snapshots are appended to, if any.
+    history_path: Option<String>,
+    pending_withdrawals: Mutex<Vec<PendingWithdrawal>>,
+}
+
+impl Linear {
+    pub const POOL_ID: &'static str = "linear";
+    const MAX_PRICE_SNAPSHOTS: usize = 1024;
+    /// `ft_price` only moves once per epoch, so shorter windows can't yield an APY.
+    const MIN_APY_WINDOW_SECS: i64 = 24 * 3600;
+    const APY_WINDOW_SECS: i64 = 7 * 24 * 3600;
+
+    pub fn new(contract_id: &str, rpc: Arc<dyn NearRpc>, tokens: Arc<TokenBook>) -> Self {
+        Self {
+            contract_id: contract_id.to_string(),
+            rpc,
+            tokens,
+            price_history: Mutex::new(VecDeque::new()),
+            history_path: None,
+            pending_withdrawals: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Seeds the price history, e.g. with snapshots persisted before a restart.
+    pub fn with_price_history(self, snapshots: Vec<PriceSnapshot>) -> Self {
+        let skip = snapshots.len().saturating_sub(Self::MAX_PRICE_SNAPSHOTS);
+        *self.price_history.lock().unwrap() = snapshots.into_iter().skip(skip).collect();
+        self
+    }
+
+    /// Appends every new price snapshot to `path`, for `load_price_history`.
+    pub fn with_history_path(mut self, path: &str) -> Self {
+        self.history_path = Some(path.to_string());
+        self
+    }
+
+    /// Reads the snapshots appended to `path`; a missing file is an empty history.
+    pub fn load_price_history(path: &str) -> Result<Vec<PriceSnapshot>, Box<dyn Error>> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut snapshots = Vec::new();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            snapshots.push(serde_json::from_str(line)?);
+        }
+        Ok(snapshots)
+    }
+
+    pub fn price_history(&self) -> Vec<PriceSnapshot> {
+        self.price_history.lock().unwrap().iter().copied().collect()
+    }
+
+    pub fn pending_withdrawals(&self) -> Vec<PendingWithdrawal> {
+        self.pending_withdrawals.lock().unwrap().clone()
+    }
+
+    /// Records a price snapshot; unchanged prices keep the earlier observation.
+    fn record_price(&self, timestamp: i64, price: U128) {
+        let mut history = self.price_history.lock().unwrap();
+        if history.back().map_or(false, |last| last.price == price) {
+            return;
+        }
+        let snapshot = PriceSnapshot { timestamp, price };
+        history.push_back(snapshot);
+        if history.len() > Self::MAX_PRICE_SNAPSHOTS {
+            history.pop_front();
+        }
+
+        if let Some(path) = &self.history_path {
+            if let Err(e) = append_json_line(path, &snapshot) {
+                log::warn!("Failed to persist LiNEAR price to {}: {}", path, e);
+            }
+        }
+    }
+
+    /// Staking APY (%) annualized from the `ft_price` growth over the last week,
+    /// or `None` until at least a day of history is available.
+    pub fn staking_apy(&self) -> Option<f64> {
+        let history = self.price_history.lock().unwrap();
+        let latest = *history.back()?;
+        let earliest = history
+            .iter()
+            .find(|s| latest.timestamp - s.timestamp <= Self::APY_WINDOW_SECS)?;
+
+        let elapsed = latest.timestamp - earliest.timestamp;
+        if elapsed < Self::MIN_APY_WINDOW_SECS || earliest.price.0 == 0 {
+            return None;
+        }
+
+        let growth = latest.price.0 as f64 / earliest.price.0 as f64;
+        Some((growth.powf(YEAR_SECS / elapsed as f64) - 1.0) * 100.0)
+    }
+
+    async fn account_details(&self) -> Result<LinearAccountDetails, Box<dyn Error>> {
+        Ok(serde_json::from_value(
+            self.rpc
+                .view(
+                    &self.contract_id,
+                    "get_account_details",
+                    json!({ "account_id": self.rpc.account_id() }),
+                )
+                .await?,
+        )?)
+    }
+
+    /// Withdraws all unstaked NEAR whose delay has passed and returns the amount claimed.
+    pub async fn claim_withdrawals(&self) -> Result<U128, Box<dyn Error>> {
+        Ok(self.claim().await?.map_or(U128(0), |(amount, _)| amount))
+    }
+
+    /// The amount claimed and the `withdraw_all` outcome, or `None` if nothing was
+    /// claimable yet.
+    async fn claim(&self) -> Result<Option<(U128, TxOutcome)>, Box<dyn Error>> {
+        let details = self.account_details().await?;
+        if !details.can_withdraw || details.unstaked_balance.0 == 0 {
+            return Ok(None);
+        }
+
+        let outcome = self.rpc
+            .call(&self.contract_id, "withdraw_all", json!({}), 0, LINEAR_GAS)
+            .await?;
+        self.pending_withdrawals.lock().unwrap().clear();
+        Ok(Some((details.unstaked_balance, outcome)))
+    }
+
+    fn check_pool_id(pool_id: &str) -> Result<(), Box<dyn Error>> {
+        if pool_id != Self::POOL_ID {
+            return Err(format!("Unknown LiNEAR pool: {}", pool_id).into());
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl DeFiProtocol for Linear {
     async fn get_pools(&self) -> Result<Vec<PoolMetrics>, Box<dyn Error>> {
-        // Implementation for LiNEAR staking info
-        todo!()
+        let summary: LinearSummary = serde_json::from_value(
+            self.rpc
+                .view(&self.contract_id, "get_summary", json!({}))
+                .await?,
+        )?;
+        self.record_price(chrono::Utc::now().timestamp(), summary.ft_price);
+
+        let near_price = self.tokens.price(WRAP_NEAR).unwrap_or(0.0);
+        let tvl = summary.total_staked_near_amount.0 as f64 / ONE_NEAR as f64 * near_price;
+
+        Ok(vec![PoolMetrics {
+            protocol: "linear".to_string(),
+            pool_id: Self::POOL_ID.to_string(),
+            apy: self.staking_apy(),
+            tvl: tvl.round() as u64,
+            risk_score: 0.95,
+            audit_status: true,
+            token_pair: (WRAP_NEAR.to_string(), self.contract_id.clone()),
+            chain_factor: 1.0,
+            tags: vec!["staking".to_string(), "liquid_staking".to_string()],
+            risk_bucket: None,
+        }])
     }
 
+    /// Returns the NEAR value of the signer's LiNEAR.
     async fn get_pool_balance(&self, pool_id: &str) -> Result<U128, Box<dyn Error>> {
-        // Implementation for balance checking
-        todo!()
+        Self::check_pool_id(pool_id)?;
+        Ok(self.account_details().await?.staked_balance)
+    }
+
+    async fn position_value(&self, pool_id: &str) -> Result<U128, Box<dyn Error>> {
+        self.get_pool_balance(pool_id).await
+    }
+
+    /// Deposits are attached NEAR, so the value is the amount.
+    async fn deposit_amount(&self, pool_id: &str, value: U128) -> Result<U128, Box<dyn Error>> {
+        Self::check_pool_id(pool_id)?;
+        Ok(value)
+    }
+
+    async fn deposit(&self, pool_id: &str, amount: U128, _max_slippage: f64) -> Result<Vec<TxOutcome>, Box<dyn Error>> {
+        Self::check_pool_id(pool_id)?;
+        let outcome = self.rpc
+            .call(&self.contract_id, "deposit_and_stake", json!({}), amount.0, LINEAR_GAS)
+            .await?;
+        Ok(vec![outcome])
+    }
+
+    /// Claims unstaked NEAR once LiNEAR's epoch delay has passed.
+    async fn settle_withdrawals(&self) -> Result<Vec<TxOutcome>, Box<dyn Error>> {
+        Ok(match self.claim().await? {
+            Some((amount, outcome)) => {
+                log::info!("Claimed {} yoctoNEAR unstaked from LiNEAR", amount.0);
+                vec![outcome]
+            }
+            None => Vec::new(),
+        })
+    }
+
+    /// Unstakes `amount` NEAR. Funds become claimable via `claim_withdrawals`
+    /// once the unstake epoch delay has passed. The unstake has landed once this
+    /// returns, so failing to read back its epoch is only logged.
+    async fn withdraw(&self, pool_id: &str, amount: U128, _max_slippage: f64) -> Result<Vec<TxOutcome>, Box<dyn Error>> {
+        Self::check_pool_id(pool_id)?;
+        let outcome = self.rpc
+            .call(&self.contract_id, "unstake", json!({ "amount": amount }), 0, LINEAR_GAS)
+            .await?;
+
+        let available_epoch = match self.account_details().await {
+            Ok(details) => Some(details.unstaked_available_epoch_height),
+            Err(e) => {
+                log::warn!("Unstaked {} from LiNEAR but failed to read its unlock epoch: {}", amount.0, e);
+                None
+            }
+        };
+        self.pending_withdrawals.lock().unwrap().push(PendingWithdrawal {
+            amount,
+            requested_at: chrono::Utc::now().timestamp(),
+            available_epoch,
+        });
+        Ok(vec![outcome])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::RecordedRpc;
+
+    const REF: &str = "v2.ref-finance.near";
+
+    fn recorded_ref() -> RecordedRpc {
+        RecordedRpc::new("vault.near")
+            .with_view(
+                REF,
+                "get_pool",
+                Some(json!({ "pool_id": 79 })),
+                json!({
+                    "pool_kind": "SIMPLE_POOL",
+                    "token_account_ids": ["wrap.near", "usdt.tether-token.near"],
+                    "amounts": ["1000000000000000000000000000000", "2000000000000"],
+                    "total_fee": 30,
+                    "shares_total_supply": "1000000000000000000000000",
+                    "amp": 0
+                }),
+            )
+            .with_view(
+                REF,
+                "get_pool_volumes",
+                Some(json!({ "pool_id": 79 })),
+                json!([{ "input": "0", "output": "0" }, { "input": "0", "output": "0" }]),
+            )
+            .with_view(
+                REF,
+                "get_pool_shares",
+                Some(json!({ "pool_id": 79, "account_id": "vault.near" })),
+                json!("5000"),
+            )
+            .with_view(
+                REF,
+                "get_deposit",
+                Some(json!({ "account_id": "vault.near", "token_id": "wrap.near" })),
+                json!("10000000000000000000000000000"),
+            )
+            .with_view(
+                REF,
+                "get_deposit",
+                Some(json!({ "account_id": "vault.near", "token_id": "usdt.tether-token.near" })),
+                json!("20000000000"),
+            )
+            .with_view("wrap.near", "ft_metadata", None, json!({ "symbol": "wNEAR", "decimals": 24 }))
+            .with_view(
+                "usdt.tether-token.near",
+                "ft_metadata",
+                None,
+                json!({ "symbol": "USDt", "decimals": 6 }),
+            )
+    }
+
+    fn ref_finance(rpc: Arc<RecordedRpc>) -> RefFinance {
+        let tokens = Arc::new(TokenBook::default());
+        tokens.set_prices(HashMap::from([
+            ("wrap.near".to_string(), 2.0),
+            ("usdt.tether-token.near".to_string(), 1.0),
+        ]));
+        RefFinance::new(REF, rpc, tokens).with_tracked_pools(vec![79])
+    }
+
+    #[tokio::test]
+    async fn maps_ref_pools_into_metrics() {
+        let rpc = Arc::new(recorded_ref());
+        let pools = ref_finance(rpc).get_pools().await.unwrap();
+
+        assert_eq!(pools.len(), 1);
+        let pool = &pools[0];
+        assert_eq!(pool.pool_id, "79");
+        assert_eq!(pool.protocol, "ref_finance");
+        assert_eq!(
+            pool.token_pair,
+            ("wrap.near".to_string(), "usdt.tether-token.near".to_string())
+        );
+        // 1M NEAR at $2 + 2M USDT at $1
+        assert_eq!(pool.tvl, 4_000_000);
+        // No earlier volume snapshot to measure fees against
+        assert_eq!(pool.apy, None);
+        assert!(pool.tags.contains(&"near_usdt".to_string()));
+        assert!(pool.tags.contains(&"near_stable".to_string()));
+    }
+
+    #[tokio::test]
+    async fn skips_pools_that_fail_to_load() {
+        // Pool 80 has no recorded response
+        let rpc = Arc::new(recorded_ref());
+        let pools = ref_finance(rpc)
+            .with_tracked_pools(vec![80, 79])
+            .get_pools()
+            .await
+            .unwrap();
+
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0].pool_id, "79");
+    }
+
+    #[tokio::test]
+    async fn skips_pools_with_unpriced_tokens() {
+        let rpc = Arc::new(recorded_ref());
+        let tokens = Arc::new(TokenBook::default());
+        let pools = RefFinance::new(REF, rpc, tokens)
+            .with_tracked_pools(vec![79])
+            .get_pools()
+            .await
+            .unwrap();
+
+        assert!(pools.is_empty());
+    }
+
+    #[test]
+    fn loads_prices_from_indexer_feed() {
+        let tokens = TokenBook::default();
+        let priced = tokens
+            .set_prices_from_feed(json!({
+                "wrap.near": { "price": "2.15", "symbol": "wNEAR", "decimal": 24 },
+                "usdt.tether-token.near": { "price": "1.0", "symbol": "USDt", "decimal": 6 },
+                "broken.near": { "price": "N/A", "symbol": "BRK", "decimal": 18 }
+            }))
+            .unwrap();
+
+        assert_eq!(priced, 2);
+        assert_eq!(tokens.price("wrap.near"), Some(2.15));
+        assert_eq!(tokens.price("broken.near"), None);
+    }
+
+    #[tokio::test]
+    async fn reads_signer_pool_shares() {
+        let rpc = Arc::new(recorded_ref());
+        let balance = ref_finance(rpc).get_pool_balance("79").await.unwrap();
+        assert_eq!(balance, U128(5000));
+    }
+
+    #[tokio::test]
+    async fn deposit_transfers_tokens_then_adds_liquidity() {
+        let rpc = Arc::new(recorded_ref());
+        ref_finance(rpc.clone())
+            .deposit("79", U128(1_000_000_000_000_000_000_000_000), 0.5)
+            .await
+            .unwrap();
+
+        let calls = rpc.calls();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].contract_id, "wrap.near");
+        assert_eq!(calls[1].contract_id, "usdt.tether-token.near");
+        assert_eq!(calls[1].args["amount"], json!("2000000"));
+        assert_eq!(calls[2].method, "add_liquidity");
+        assert_eq!(calls[2].args["pool_id"], json!(79));
+        // 0.5% below the transferred amounts
+        assert_eq!(calls[2].args["min_amounts"][1], json!("1990000"));
+    }
+
+    #[tokio::test]
+    async fn deposit_amount_covers_first_token_share_of_value() {
+        let rpc = Arc::new(recorded_ref());
+        // $2 of NEAR; the pool is half wNEAR by value, so half goes in as wNEAR
+        let amount = ref_finance(rpc).deposit_amount("79", U128(ONE_NEAR)).await.unwrap();
+        assert!((amount.0 as f64 / ONE_NEAR as f64 - 0.5).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn failed_add_liquidity_withdraws_transferred_tokens() {
+        let rpc = Arc::new(recorded_ref().with_failing_call(REF, "add_liquidity"));
+        let error = ref_finance(rpc.clone())
+            .deposit("79", U128(1_000_000_000_000_000_000_000_000), 0.5)
+            .await
+            .unwrap_err();
+
+        // Two transfers, the failed add_liquidity and two recovery withdrawals landed
+        let partial = error.downcast_ref::<PartialExecution>().unwrap();
+        assert_eq!(partial.outcomes.len(), 5);
+        let calls = rpc.calls();
+        assert_eq!(calls[2].method, "add_liquidity");
+        assert_eq!(calls[3].method, "withdraw");
+        assert_eq!(calls[3].args["token_id"], json!("wrap.near"));
+        assert_eq!(calls[4].args["token_id"], json!("usdt.tether-token.near"));
+        assert_eq!(calls[4].args["amount"], json!("2000000"));
+    }
+
+    #[tokio::test]
+    async fn withdraw_sets_min_amounts_within_slippage() {
+        let rpc = Arc::new(recorded_ref());
+        ref_finance(rpc.clone())
+            .withdraw("79", U128(10_000_000_000_000_000_000_000), 1.0)
+            .await
+            .unwrap();
+
+        let calls = rpc.calls();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].method, "remove_liquidity");
+        assert_eq!(calls[0].deposit, ONE_YOCTO);
+        // 1% of the pool, less 1% slippage
+        assert_eq!(calls[0].args["min_amounts"][1], json!("19800000000"));
     }
 
-    async fn deposit(&self, pool_id: &str, amount: U128) -> Result<(), Box<dyn Error>> {
-        // Implementation for deposits
-        todo!()
+    #[tokio::test]
+    async fn withdraw_moves_removed_tokens_out_of_the_ref_deposit() {
+        let rpc = Arc::new(recorded_ref());
+        let outcomes = ref_finance(rpc.clone())
+            .withdraw("79", U128(10_000_000_000_000_000_000_000), 1.0)
+            .await
+            .unwrap();
+
+        assert_eq!(outcomes.len(), 3);
+        let calls = rpc.calls();
+        assert_eq!(calls[1].method, "withdraw");
+        assert_eq!(calls[1].args["token_id"], json!("wrap.near"));
+        assert_eq!(calls[2].args["token_id"], json!("usdt.tether-token.near"));
+        assert_eq!(calls[2].args["amount"], json!("20000000000"));
+    }
+
+    const BURROW: &str = "contract.main.burrow.near";
+    const USDC: &str = "usdc.near";
+
+    fn recorded_burrow() -> RecordedRpc {
+        let asset = json!({
+            "supplied": { "shares": "0", "balance": "1000000000000000000000" },
+            "borrowed": { "shares": "0", "balance": "800000000000000000000" },
+            "reserved": "0",
+            "prot_fee": "0",
+            "last_update_timestamp": "0",
+            "config": {
+                "reserve_ratio": 2500,
+                "target_utilization": 8000,
+                "target_utilization_rate": "1000000000003593629036885046",
+                "max_utilization_rate": "1000000000039724853136740579",
+                "volatility_ratio": 9500,
+                "extra_decimals": 12,
+                "can_deposit": true,
+                "can_withdraw": true,
+                "can_use_as_collateral": true,
+                "can_borrow": true
+            }
+        });
+
+        RecordedRpc::new("vault.near")
+            .with_view(BURROW, "get_assets_paged", None, json!([[USDC, asset]]))
+            .with_view(BURROW, "get_asset", None, asset)
+            .with_view(USDC, "ft_metadata", None, json!({ "symbol": "USDC", "decimals": 6 }))
+    }
+
+    fn burrow(rpc: Arc<RecordedRpc>) -> Burrow {
+        let tokens = Arc::new(TokenBook::default());
+        tokens.set_prices(HashMap::from([(USDC.to_string(), 1.0), (WRAP_NEAR.to_string(), 2.0)]));
+        Burrow::new(BURROW, rpc, tokens)
+    }
+
+    #[tokio::test]
+    async fn derives_supply_apy_from_utilization() {
+        let rpc = Arc::new(recorded_burrow());
+        let markets = burrow(rpc).get_markets().await.unwrap();
+
+        assert_eq!(markets.len(), 1);
+        let market = &markets[0];
+        assert!((market.utilization - 0.8).abs() < 1e-9);
+        assert!((market.borrow_apr - 0.11333).abs() < 1e-4);
+        // 11.33% borrow APR * 80% utilization * 75% after reserves, compounded
+        assert!((market.supply_apy * 100.0 - 7.036).abs() < 1e-3);
+        assert_eq!(market.supplied_usd.round(), 1000.0);
+        assert!((market.risk_score() - 0.65).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn burrow_deposit_amount_is_in_token_decimals() {
+        let rpc = Arc::new(recorded_burrow());
+        // 1 NEAR at $2 buys 2 USDC
+        let amount = burrow(rpc).deposit_amount(USDC, U128(ONE_NEAR)).await.unwrap();
+        assert_eq!(amount, U128(2_000_000));
+    }
+
+    #[tokio::test]
+    async fn burrow_withdraw_uses_inner_decimals() {
+        let rpc = Arc::new(recorded_burrow());
+        burrow(rpc.clone()).withdraw(USDC, U128(5_000_000), 0.0).await.unwrap();
+
+        let calls = rpc.calls();
+        assert_eq!(calls[0].method, "execute");
+        assert_eq!(
+            calls[0].args["actions"][0]["Withdraw"]["max_amount"],
+            json!("5000000000000000000")
+        );
+    }
+    const LINEAR: &str = "linear-protocol.near";
+
+    fn recorded_linear() -> RecordedRpc {
+        RecordedRpc::new("vault.near")
+            .with_view(
+                LINEAR,
+                "get_summary",
+                None,
+                json!({
+                    "total_share_amount": "0",
+                    "total_staked_near_amount": "10000000000000000000000000000000",
+                    "ft_price": "1100000000000000000000000"
+                }),
+            )
+            .with_view(
+                LINEAR,
+                "get_account_details",
+                None,
+                json!({
+                    "account_id": "vault.near",
+                    "unstaked_balance": "0",
+                    "staked_balance": "2000000000000000000000000",
+                    "unstaked_available_epoch_height": 1204,
+                    "can_withdraw": false
+                }),
+            )
     }
 
-    async fn withdraw(&self, pool_id: &str, amount: U128) -> Result<(), Box<dyn Error>> {
-        // Implementation for withdrawals
-        todo!()
+    fn linear(rpc: Arc<RecordedRpc>) -> Linear {
+        let tokens = Arc::new(TokenBook::default());
+        tokens.set_prices(HashMap::from([(WRAP_NEAR.to_string(), 3.0)]));
+        Linear::new(LINEAR, rpc, tokens)
+    }
+
+    #[test]
+    fn annualizes_ft_price_growth() {
+        let day = 24 * 3600;
+        let linear = linear(Arc::new(recorded_linear())).with_price_history(vec![
+            PriceSnapshot { timestamp: 0, price: U128(1_000_000_000_000_000_000_000_000) },
+            PriceSnapshot { timestamp: 7 * day, price: U128(1_001_000_000_000_000_000_000_000) },
+        ]);
+
+        let expected = (1.001f64.powf(365.0 / 7.0) - 1.0) * 100.0;
+        assert!((linear.staking_apy().unwrap() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn needs_a_day_of_prices_for_apy() {
+        let linear = linear(Arc::new(recorded_linear())).with_price_history(vec![
+            PriceSnapshot { timestamp: 0, price: U128(1_000_000_000_000_000_000_000_000) },
+            PriceSnapshot { timestamp: 3600, price: U128(1_000_100_000_000_000_000_000_000) },
+        ]);
+
+        assert_eq!(linear.staking_apy(), None);
+    }
+
+    #[tokio::test]
+    async fn reports_tvl_and_records_price() {
+        let linear = linear(Arc::new(recorded_linear()));
+        let pools = linear.get_pools().await.unwrap();
+
+        // 10M NEAR staked at $3
+        assert_eq!(pools[0].tvl, 30_000_000);
+        assert_eq!(linear.price_history().len(), 1);
+        assert_eq!(linear.price_history()[0].price, U128(1_100_000_000_000_000_000_000_000));
+    }
+
+    #[tokio::test]
+    async fn persisted_prices_survive_a_restart() {
+        let path = std::env::temp_dir().join(format!("linear-prices-{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let first = linear(Arc::new(recorded_linear())).with_history_path(path);
+        first.get_pools().await.unwrap();
+
+        let restarted = linear(Arc::new(recorded_linear()))
+            .with_price_history(Linear::load_price_history(path).unwrap());
+        assert_eq!(restarted.price_history(), first.price_history());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unstake_tracks_delayed_withdrawal() {
+        let rpc = Arc::new(recorded_linear());
+        let linear = linear(rpc.clone());
+        linear.withdraw(Linear::POOL_ID, U128(ONE_NEAR), 0.0).await.unwrap();
+
+        assert_eq!(rpc.calls()[0].method, "unstake");
+        let pending = linear.pending_withdrawals();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].available_epoch, Some(1204));
+
+        // Delay has not passed yet
+        assert_eq!(linear.claim_withdrawals().await.unwrap(), U128(0));
+        assert_eq!(linear.pending_withdrawals().len(), 1);
     }
 }
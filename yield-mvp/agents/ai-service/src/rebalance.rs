@@ -0,0 +1,178 @@
+use near_sdk::json_types::U128;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use crate::protocols::{estimated_gas, DeFiProtocol, PartialExecution};
+use crate::registry::ProtocolRegistry;
+use crate::rpc::{GasUsage, TxOutcome};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LegAction {
+    Withdraw,
+    Deposit,
+}
+
+/// A single withdraw or deposit against one pool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebalanceLeg {
+    pub action: LegAction,
+    pub protocol: String,
+    pub pool_id: String,
+    /// Value moved, in yoctoNEAR.
+    pub amount: U128,
+    /// Share of the current position to withdraw; only set on withdraw legs.
+    pub position_fraction: Option<f64>,
+    /// Prepaid gas for the leg's transactions.
+    pub estimated_gas: u64,
+    /// Maximum accepted slippage, in percent.
+    pub max_slippage: f64,
+}
+
+impl RebalanceLeg {
+    /// Builds a leg, filling in the protocol's gas estimate.
+    pub fn new(
+        action: LegAction,
+        protocol: &str,
+        pool_id: &str,
+        amount: U128,
+        position_fraction: Option<f64>,
+        max_slippage: f64,
+    ) -> Self {
+        Self {
+            action,
+            protocol: protocol.to_string(),
+            pool_id: pool_id.to_string(),
+            amount,
+            position_fraction,
+            estimated_gas: estimated_gas(protocol, action),
+            max_slippage,
+        }
+    }
+}
+
+/// Transactions sent for one executed leg.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegOutcome {
+    pub leg: RebalanceLeg,
+    pub transactions: Vec<TxOutcome>,
+    pub gas: GasUsage,
+}
+
+/// Outcome of an executed plan, with the gas actually burnt across all legs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutionReport {
+    pub legs: Vec<LegOutcome>,
+    pub gas: GasUsage,
+}
+
+/// Ordered legs moving a portfolio to its target allocation: all withdrawals first,
+/// so deposits are funded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RebalancePlan {
+    pub legs: Vec<RebalanceLeg>,
+    pub total_gas: u64,
+    pub created_at: i64,
+}
+
+impl RebalancePlan {
+    pub fn new(mut legs: Vec<RebalanceLeg>) -> Self {
+        // Stable sort keeps the caller's order within each action
+        legs.sort_by_key(|leg| match leg.action {
+            LegAction::Withdraw => 0,
+            LegAction::Deposit => 1,
+        });
+
+        Self {
+            total_gas: legs.iter().map(|leg| leg.estimated_gas).sum(),
+            legs,
+            created_at: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.legs.is_empty()
+    }
+
+    /// Executes the legs in order through the registered adapters, stopping at the
+    /// first failure, and reports the gas each leg burnt. Withdrawals are sized from
+    /// the live position and deposits converted from yoctoNEAR, so each adapter
+    /// receives its own unit (LP shares, tokens or NEAR). A failure after any
+    /// transaction landed is returned as a `PartialExecution` holding all of them.
+    pub async fn execute(&self, registry: &ProtocolRegistry) -> Result<ExecutionReport, Box<dyn Error>> {
+        let mut report = ExecutionReport::default();
+
+        for (index, leg) in self.legs.iter().enumerate() {
+            let result = match registry.get(&leg.protocol) {
+                Some(adapter) => execute_leg(adapter, leg).await,
+                None => Err(format!("No adapter registered for {}", leg.protocol).into()),
+            };
+
+            let transactions = match result {
+                Ok(transactions) => transactions,
+                Err(e) => {
+                    let error = format!(
+                        "Rebalance leg {} ({:?} {} {}) failed: {}",
+                        index, leg.action, leg.protocol, leg.pool_id, e
+                    );
+                    let mut outcomes: Vec<TxOutcome> =
+                        report.legs.into_iter().flat_map(|leg| leg.transactions).collect();
+                    outcomes.extend(PartialExecution::outcomes_of(e.as_ref()));
+                    if outcomes.is_empty() {
+                        return Err(error.into());
+                    }
+                    return Err(Box::new(PartialExecution { outcomes, error }));
+                }
+            };
+
+            let gas = GasUsage::from_outcomes(&transactions);
+            report.gas = report.gas.add(gas);
+            report.legs.push(LegOutcome {
+                leg: leg.clone(),
+                transactions,
+                gas,
+            });
+        }
+
+        Ok(report)
+    }
+}
+
+async fn execute_leg(adapter: &dyn DeFiProtocol, leg: &RebalanceLeg) -> Result<Vec<TxOutcome>, Box<dyn Error>> {
+    match leg.action {
+        LegAction::Withdraw => {
+            let balance = adapter.get_pool_balance(&leg.pool_id).await?;
+            let amount = position_share(balance, leg.position_fraction.unwrap_or(1.0));
+            adapter.withdraw(&leg.pool_id, amount, leg.max_slippage).await
+        }
+        LegAction::Deposit => {
+            let amount = adapter.deposit_amount(&leg.pool_id, leg.amount).await?;
+            adapter.deposit(&leg.pool_id, amount, leg.max_slippage).await
+        }
+    }
+}
+
+/// `fraction` of `balance` in whole basis points, computed in integer math so it
+/// never exceeds `balance`. A fraction of 1 or more is the exact balance.
+fn position_share(balance: U128, fraction: f64) -> U128 {
+    const BPS: u128 = 10_000;
+    if fraction >= 1.0 {
+        return balance;
+    }
+    let bps = ((fraction.max(0.0) * BPS as f64).round() as u128).min(BPS);
+    U128(balance.0 / BPS * bps + balance.0 % BPS * bps / BPS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_share_never_exceeds_the_balance() {
+        // f64 can't represent this balance, so `balance as f64 * 1.0` rounds above it
+        let balance = U128(u128::MAX - 1);
+        assert_eq!(position_share(balance, 1.0), balance);
+        assert!(position_share(balance, 0.99999).0 <= balance.0);
+        assert_eq!(position_share(U128(1_000_000), 0.4), U128(400_000));
+        assert_eq!(position_share(U128(1_000_000), 0.0), U128(0));
+    }
+}
@@ -0,0 +1,177 @@
+use futures::future::join_all;
+use std::sync::Arc;
+use crate::config::{PoolMetrics, RiskLevels, SourceConfig, YieldSourcesConfig};
+use crate::protocols::{Burrow, DeFiProtocol, Linear, RefFinance, TokenBook};
+use crate::rpc::{NearRpc, TxOutcome};
+
+struct RegisteredProtocol {
+    name: String,
+    adapter: Box<dyn DeFiProtocol>,
+    risk_levels: RiskLevels,
+}
+
+/// Enabled protocol adapters built from the `yield_sources` section of the workflow config.
+pub struct ProtocolRegistry {
+    protocols: Vec<RegisteredProtocol>,
+}
+
+impl ProtocolRegistry {
+    pub fn new() -> Self {
+        Self {
+            protocols: Vec::new(),
+        }
+    }
+
+    pub fn from_config(config: &YieldSourcesConfig, rpc: Arc<dyn NearRpc>, tokens: Arc<TokenBook>) -> Self {
+        let mut registry = Self::new();
+
+        for (name, source) in config.enabled_sources() {
+            match build_adapter(name, source, rpc.clone(), tokens.clone()) {
+                Some(adapter) => registry.register(name, adapter, source.risk_levels.clone()),
+                None => log::warn!("No adapter available for yield source '{}', skipping", name),
+            }
+        }
+
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, adapter: Box<dyn DeFiProtocol>, risk_levels: RiskLevels) {
+        self.protocols.retain(|p| p.name != name);
+        self.protocols.push(RegisteredProtocol {
+            name: name.to_string(),
+            adapter,
+            risk_levels,
+        });
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn DeFiProtocol> {
+        self.protocols
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.adapter.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.protocols.iter().map(|p| p.name.clone()).collect()
+    }
+
+    /// Queries every registered protocol concurrently and tags each pool with its
+    /// configured risk bucket. A failing protocol is logged and left out.
+    pub async fn get_all_pools(&self) -> Vec<PoolMetrics> {
+        let results = join_all(self.protocols.iter().map(|p| p.adapter.get_pools())).await;
+
+        let mut pools = Vec::new();
+        for (protocol, result) in self.protocols.iter().zip(results) {
+            match result {
+                Ok(protocol_pools) => {
+                    for mut pool in protocol_pools {
+                        pool.risk_bucket = protocol.risk_levels.bucket_for(&pool);
+                        pools.push(pool);
+                    }
+                }
+                Err(e) => log::warn!("Failed to fetch pools from {}: {}", protocol.name, e),
+            }
+        }
+        pools
+    }
+
+    /// Settles every protocol's delayed withdrawals and returns the transactions
+    /// sent. A failing protocol is logged and retried next time.
+    pub async fn settle_withdrawals(&self) -> Vec<TxOutcome> {
+        let mut outcomes = Vec::new();
+        for protocol in &self.protocols {
+            match protocol.adapter.settle_withdrawals().await {
+                Ok(sent) => outcomes.extend(sent),
+                Err(e) => log::warn!("Failed to settle {} withdrawals: {}", protocol.name, e),
+            }
+        }
+        outcomes
+    }
+}
+
+fn build_adapter(
+    name: &str,
+    source: &SourceConfig,
+    rpc: Arc<dyn NearRpc>,
+    tokens: Arc<TokenBook>,
+) -> Option<Box<dyn DeFiProtocol>> {
+    let contract_id = source.contract_id.as_deref()?;
+
+    match name {
+        "ref_finance" => Some(Box::new(
+            RefFinance::new(contract_id, rpc, tokens).with_tracked_pools(source.tracked_pools.clone()),
+        )),
+        "burrow" => Some(Box::new(Burrow::new(contract_id, rpc, tokens))),
+        "linear" => {
+            let mut linear = Linear::new(contract_id, rpc, tokens);
+            if let Some(path) = &source.history_path {
+                match Linear::load_price_history(path) {
+                    Ok(snapshots) => linear = linear.with_price_history(snapshots),
+                    Err(e) => log::warn!("Failed to load LiNEAR price history {}: {}", path, e),
+                }
+                linear = linear.with_history_path(path);
+            }
+            Some(Box::new(linear))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RiskBucket;
+    use crate::rpc::RecordedRpc;
+
+    const SOURCES: &str = r#"
+staking:
+  min_validator_fee: 2
+  max_validator_fee: 20
+  liquid:
+    linear:
+      contract_id: "linear-protocol.near"
+      risk_levels:
+        low: ["linear"]
+defi:
+  aurora_pools:
+    risk_levels:
+      low: ["usdc_usdt"]
+  burrow:
+    enabled: false
+    contract_id: "contract.main.burrow.near"
+"#;
+
+    #[test]
+    fn builds_only_enabled_sources_with_adapters() {
+        let config: YieldSourcesConfig = serde_yaml::from_str(SOURCES).unwrap();
+        let registry = ProtocolRegistry::from_config(
+            &config,
+            Arc::new(RecordedRpc::new("vault.near")),
+            Arc::new(TokenBook::default()),
+        );
+
+        assert_eq!(registry.names(), vec!["linear".to_string()]);
+        assert!(registry.get("linear").is_some());
+        assert!(registry.get("burrow").is_none());
+    }
+
+    #[tokio::test]
+    async fn tags_pools_with_configured_bucket() {
+        let config: YieldSourcesConfig = serde_yaml::from_str(SOURCES).unwrap();
+        let rpc = RecordedRpc::new("vault.near").with_view(
+            "linear-protocol.near",
+            "get_summary",
+            None,
+            serde_json::json!({
+                "total_staked_near_amount": "0",
+                "ft_price": "1000000000000000000000000"
+            }),
+        );
+        let registry =
+            ProtocolRegistry::from_config(&config, Arc::new(rpc), Arc::new(TokenBook::default()));
+
+        let pools = registry.get_all_pools().await;
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0].risk_bucket, Some(RiskBucket::Low));
+    }
+}
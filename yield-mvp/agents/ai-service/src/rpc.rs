@@ -0,0 +1,371 @@
+use async_trait::async_trait;
+use near_crypto::InMemorySigner;
+use near_jsonrpc_client::{methods, JsonRpcClient};
+use near_jsonrpc_primitives::types::query::QueryResponseKind;
+use near_primitives::transaction::{Action, FunctionCallAction, Transaction};
+use near_primitives::types::{BlockReference, Finality, FunctionArgs};
+use near_primitives::views::{ExecutionStatusView, FinalExecutionStatus, QueryRequest};
+use near_sdk::json_types::U128;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Result of a change call, as reported by the transaction outcome.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxOutcome {
+    pub transaction_hash: String,
+    pub gas_burnt: u64,
+    pub tokens_burnt: u128,
+    pub result: Value,
+}
+
+/// A transaction that landed but failed, itself or in one of its receipts. It
+/// still burnt gas, so the outcome is kept for accounting.
+#[derive(Debug)]
+pub struct TxFailure {
+    pub outcome: TxOutcome,
+    pub error: String,
+}
+
+impl fmt::Display for TxFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (tx {})", self.error, self.outcome.transaction_hash)
+    }
+}
+
+impl Error for TxFailure {}
+
+/// Gas burnt and NEAR spent on it across one or more transactions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct GasUsage {
+    pub gas_burnt: u64,
+    /// yoctoNEAR paid for the burnt gas.
+    pub tokens_burnt: U128,
+}
+
+impl GasUsage {
+    pub fn from_outcomes<'a>(outcomes: impl IntoIterator<Item = &'a TxOutcome>) -> Self {
+        outcomes.into_iter().fold(Self::default(), |total, outcome| GasUsage {
+            gas_burnt: total.gas_burnt + outcome.gas_burnt,
+            tokens_burnt: U128(total.tokens_burnt.0 + outcome.tokens_burnt),
+        })
+    }
+
+    pub fn add(self, other: GasUsage) -> Self {
+        GasUsage {
+            gas_burnt: self.gas_burnt + other.gas_burnt,
+            tokens_burnt: U128(self.tokens_burnt.0 + other.tokens_burnt.0),
+        }
+    }
+
+    /// NEAR spent, as a float.
+    pub fn near_spent(&self) -> f64 {
+        self.tokens_burnt.0 as f64 / 1e24
+    }
+}
+
+/// Minimal view/change interface over NEAR JSON-RPC used by the protocol adapters.
+#[async_trait]
+pub trait NearRpc: Send + Sync {
+    /// Account that signs change calls and owns positions.
+    fn account_id(&self) -> &str;
+
+    async fn view(&self, contract_id: &str, method: &str, args: Value) -> Result<Value, Box<dyn Error>>;
+
+    /// Sends a change call and waits for it to be final. A call that landed but
+    /// failed is returned as a boxed `TxFailure` carrying its outcome.
+    async fn call(
+        &self,
+        contract_id: &str,
+        method: &str,
+        args: Value,
+        deposit: u128,
+        gas: u64,
+    ) -> Result<TxOutcome, Box<dyn Error>>;
+}
+
+/// Times a call is re-signed with a fresh nonce after `InvalidNonce`.
+const MAX_NONCE_RETRIES: usize = 3;
+
+pub struct JsonRpcNear {
+    client: JsonRpcClient,
+    signer: InMemorySigner,
+    /// Held from fetching the nonce until the transaction is final, so concurrent
+    /// calls from this process never sign with the same nonce.
+    signing: tokio::sync::Mutex<()>,
+}
+
+impl JsonRpcNear {
+    pub fn new(node_url: &str, signer: InMemorySigner) -> Self {
+        Self {
+            client: JsonRpcClient::connect(node_url),
+            signer,
+            signing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Signs with the key in a NEAR credentials file, as written by `near login`.
+    pub fn from_credentials_file(node_url: &str, path: &str) -> Result<Self, Box<dyn Error>> {
+        let signer = InMemorySigner::from_file(std::path::Path::new(path))?;
+        Ok(Self::new(node_url, signer))
+    }
+}
+
+#[async_trait]
+impl NearRpc for JsonRpcNear {
+    fn account_id(&self) -> &str {
+        self.signer.account_id.as_str()
+    }
+
+    async fn view(&self, contract_id: &str, method: &str, args: Value) -> Result<Value, Box<dyn Error>> {
+        let response = self
+            .client
+            .call(methods::query::RpcQueryRequest {
+                block_reference: BlockReference::Finality(Finality::Final),
+                request: QueryRequest::CallFunction {
+                    account_id: contract_id.parse()?,
+                    method_name: method.to_string(),
+                    args: FunctionArgs::from(serde_json::to_vec(&args)?),
+                },
+            })
+            .await?;
+
+        match response.kind {
+            QueryResponseKind::CallResult(result) => Ok(serde_json::from_slice(&result.result)?),
+            _ => Err(format!("Unexpected response to view call {}.{}", contract_id, method).into()),
+        }
+    }
+
+    async fn call(
+        &self,
+        contract_id: &str,
+        method: &str,
+        args: Value,
+        deposit: u128,
+        gas: u64,
+    ) -> Result<TxOutcome, Box<dyn Error>> {
+        let receiver_id: near_primitives::types::AccountId = contract_id.parse()?;
+        let args = serde_json::to_vec(&args)?;
+        let _signing = self.signing.lock().await;
+
+        let mut retries = 0;
+        let outcome = loop {
+            let access_key = self
+                .client
+                .call(methods::query::RpcQueryRequest {
+                    block_reference: BlockReference::latest(),
+                    request: QueryRequest::ViewAccessKey {
+                        account_id: self.signer.account_id.clone(),
+                        public_key: self.signer.public_key.clone(),
+                    },
+                })
+                .await?;
+
+            let nonce = match access_key.kind {
+                QueryResponseKind::AccessKey(key) => key.nonce,
+                _ => return Err("Failed to fetch signer access key".into()),
+            };
+
+            let transaction = Transaction {
+                signer_id: self.signer.account_id.clone(),
+                public_key: self.signer.public_key.clone(),
+                nonce: nonce + 1,
+                receiver_id: receiver_id.clone(),
+                block_hash: access_key.block_hash,
+                actions: vec![Action::FunctionCall(FunctionCallAction {
+                    method_name: method.to_string(),
+                    args: args.clone(),
+                    gas,
+                    deposit,
+                })],
+            };
+
+            match self
+                .client
+                .call(methods::broadcast_tx_commit::RpcBroadcastTxCommitRequest {
+                    signed_transaction: transaction.sign(&self.signer),
+                })
+                .await
+            {
+                Ok(outcome) => break outcome,
+                // Another signer using the same key got there first; re-read the nonce
+                Err(e) if retries < MAX_NONCE_RETRIES && is_invalid_nonce(&e) => {
+                    retries += 1;
+                    log::warn!("{}.{} hit InvalidNonce, retrying ({}/{})", contract_id, method, retries, MAX_NONCE_RETRIES);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        let receipts = outcome.receipts_outcome.iter().map(|r| &r.outcome);
+        let gas_burnt = outcome.transaction_outcome.outcome.gas_burnt
+            + receipts.clone().map(|o| o.gas_burnt).sum::<u64>();
+        let tokens_burnt = outcome.transaction_outcome.outcome.tokens_burnt
+            + receipts.map(|o| o.tokens_burnt).sum::<u128>();
+        let mut tx_outcome = TxOutcome {
+            transaction_hash: outcome.transaction.hash.to_string(),
+            gas_burnt,
+            tokens_burnt,
+            result: Value::Null,
+        };
+
+        // A receipt can fail while the transaction itself succeeds, e.g. a cross-contract
+        // call whose callback swallowed the error
+        let failure = match &outcome.status {
+            FinalExecutionStatus::Failure(err) => Some(format!("{:?}", err)),
+            _ => outcome.receipts_outcome.iter().find_map(|r| match &r.outcome.status {
+                ExecutionStatusView::Failure(err) => Some(format!("{:?}", err)),
+                _ => None,
+            }),
+        };
+        if let Some(err) = failure {
+            return Err(Box::new(TxFailure {
+                outcome: tx_outcome,
+                error: format!("{}.{} failed: {}", contract_id, method, err),
+            }));
+        }
+
+        if let FinalExecutionStatus::SuccessValue(bytes) = &outcome.status {
+            tx_outcome.result = serde_json::from_slice(bytes).unwrap_or(Value::Null);
+        }
+        Ok(tx_outcome)
+    }
+}
+
+fn is_invalid_nonce(error: &impl std::fmt::Debug) -> bool {
+    format!("{:?}", error).contains("InvalidNonce")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedView {
+    pub contract_id: String,
+    pub method: String,
+    /// When set, the response is only returned for calls with exactly these args.
+    #[serde(default)]
+    pub args: Option<Value>,
+    pub response: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedCall {
+    pub contract_id: String,
+    pub method: String,
+    pub args: Value,
+    pub deposit: u128,
+    pub gas: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Recording {
+    account_id: String,
+    views: Vec<RecordedView>,
+}
+
+/// Replays recorded view responses and records change calls instead of sending them.
+pub struct RecordedRpc {
+    account_id: String,
+    views: Vec<RecordedView>,
+    /// (contract id, method) pairs whose calls are recorded and then fail.
+    failing_calls: Vec<(String, String)>,
+    calls: Mutex<Vec<RecordedCall>>,
+}
+
+impl RecordedRpc {
+    pub fn new(account_id: &str) -> Self {
+        Self {
+            account_id: account_id.to_string(),
+            views: Vec::new(),
+            failing_calls: Vec::new(),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Loads a recording of the form `{"account_id": ..., "views": [...]}`.
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        let recording: Recording = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let mut rpc = Self::new(&recording.account_id);
+        rpc.views = recording.views;
+        Ok(rpc)
+    }
+
+    pub fn with_view(mut self, contract_id: &str, method: &str, args: Option<Value>, response: Value) -> Self {
+        self.views.push(RecordedView {
+            contract_id: contract_id.to_string(),
+            method: method.to_string(),
+            args,
+            response,
+        });
+        self
+    }
+
+    /// Makes calls to `method` on `contract_id` land and fail, as a `TxFailure`.
+    pub fn with_failing_call(mut self, contract_id: &str, method: &str) -> Self {
+        self.failing_calls.push((contract_id.to_string(), method.to_string()));
+        self
+    }
+
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl NearRpc for RecordedRpc {
+    fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    async fn view(&self, contract_id: &str, method: &str, args: Value) -> Result<Value, Box<dyn Error>> {
+        self.views
+            .iter()
+            .find(|v| {
+                v.contract_id == contract_id
+                    && v.method == method
+                    && v.args.as_ref().map_or(true, |a| *a == args)
+            })
+            .map(|v| v.response.clone())
+            .ok_or_else(|| format!("No recorded response for {}.{} {}", contract_id, method, args).into())
+    }
+
+    async fn call(
+        &self,
+        contract_id: &str,
+        method: &str,
+        args: Value,
+        deposit: u128,
+        gas: u64,
+    ) -> Result<TxOutcome, Box<dyn Error>> {
+        let mut calls = self.calls.lock().unwrap();
+        calls.push(RecordedCall {
+            contract_id: contract_id.to_string(),
+            method: method.to_string(),
+            args,
+            deposit,
+            gas,
+        });
+        if self
+            .failing_calls
+            .iter()
+            .any(|(c, m)| c == contract_id && m == method)
+        {
+            return Err(Box::new(TxFailure {
+                outcome: TxOutcome {
+                    transaction_hash: format!("recorded-{}", calls.len()),
+                    gas_burnt: 0,
+                    tokens_burnt: 0,
+                    result: Value::Null,
+                },
+                error: format!("{}.{} failed", contract_id, method),
+            }));
+        }
+
+        Ok(TxOutcome {
+            transaction_hash: format!("recorded-{}", calls.len()),
+            gas_burnt: 0,
+            tokens_burnt: 0,
+            result: Value::Null,
+        })
+    }
+}
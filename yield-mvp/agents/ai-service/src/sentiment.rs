@@ -1,8 +1,16 @@
+use async_trait::async_trait;
+use futures::future::join_all;
 use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
+use std::collections::BTreeMap;
 use std::error::Error;
-use reqwest::Client;
-use rust_bert::pipeline::sentiment::SentimentModel;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::anomaly::{AnomalyConfig, AnomalyDetector, AnomalyKind, Observation};
+use crate::classifier::{default_model_dir, SentimentClassifier};
+use crate::cryptopanic::{news_score, CryptoPanicClient, CryptoPanicSource};
+use crate::sentiment_store::{SentimentStore, SentimentWindowStats};
+use crate::tweet_filter::{FilterStats, Tweet, TweetFilter};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SentimentData {
@@ -10,6 +18,23 @@ pub struct SentimentData {
     pub confidence: f32,
     pub sources: Vec<String>,
     pub timestamp: i64,
+    /// Per-source result behind `score`, including sources that failed.
+    #[serde(default)]
+    pub breakdown: Vec<SourceBreakdown>,
+    /// What the tweet filter removed, when a Twitter reading is included.
+    #[serde(default)]
+    pub filter_stats: Option<FilterStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceBreakdown {
+    pub source: String,
+    pub weight: f32,
+    /// `None` when the source failed or timed out.
+    pub score: Option<f32>,
+    pub confidence: f32,
+    pub latency_ms: u64,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,10 +45,69 @@ pub struct NewsItem {
     pub sentiment: f32,
 }
 
+/// A score on the 0-100 scale with the source's own confidence in it (0-1).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SourceReading {
+    pub score: f32,
+    pub confidence: f32,
+    #[serde(default)]
+    pub filter_stats: Option<FilterStats>,
+}
+
+/// A signal feeding the combined sentiment score.
+#[async_trait]
+pub trait SentimentSource: Send + Sync {
+    fn name(&self) -> &str;
+    async fn fetch(&self) -> Result<SourceReading, Box<dyn Error>>;
+}
+
+/// Weight and timeout for one registered source.
+#[derive(Debug, Clone, Copy)]
+pub struct SourceSettings {
+    pub weight: f32,
+    pub timeout: Duration,
+}
+
+impl Default for SourceSettings {
+    fn default() -> Self {
+        Self {
+            weight: 1.0,
+            timeout: Duration::from_secs(default_source_timeout()),
+        }
+    }
+}
+
+/// Per-source overrides from `scraping.sources` in the workflow config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentimentSourceConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub weight: f32,
+    #[serde(default = "default_source_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_source_timeout() -> u64 {
+    10
+}
+
+struct RegisteredSource {
+    source: Box<dyn SentimentSource>,
+    settings: SourceSettings,
+}
+
 pub struct SentimentAnalyzer {
-    model: SentimentModel,
-    twitter_client: Client,
-    cryptopanic_client: Client,
+    sources: Vec<RegisteredSource>,
+    /// Also used directly by the workflow's fallback path.
+    classifier: Option<Arc<SentimentClassifier>>,
+    tweet_filter: Arc<TweetFilter>,
+    news: Option<Arc<CryptoPanicClient>>,
+    history: Option<Arc<dyn SentimentStore>>,
+    anomalies: Mutex<AnomalyDetector>,
     alert_tx: broadcast::Sender<SentimentAlert>,
 }
 
@@ -39,126 +123,276 @@ pub enum SentimentAlert {
         new_score: f32,
         change_percentage: f32,
     },
+    /// The reading looks manipulated or broken; rebalances should wait until `active_until`.
+    AnomalyDetected {
+        anomaly: AnomalyKind,
+        score: f32,
+        active_until: i64,
+    },
 }
 
 impl SentimentAnalyzer {
+    /// Analyzer with the built-in Twitter (0.4) and CryptoPanic (0.3) sources. Twitter is
+    /// skipped when no model is cached in `default_model_dir()`, CryptoPanic when
+    /// `CRYPTOPANIC_API_KEY` is unset. The on-chain source needs a metrics
+    /// provider, so callers register `onchain::OnChainSource` themselves.
     pub async fn new(alert_tx: broadcast::Sender<SentimentAlert>) -> Result<Self, Box<dyn Error>> {
-        Ok(Self {
-            model: SentimentModel::new(Default::default())?,
-            twitter_client: Client::new(),
-            cryptopanic_client: Client::new(),
+        let mut analyzer = Self::empty(alert_tx);
+        let model_dir = default_model_dir();
+        match SentimentClassifier::load(&model_dir) {
+            Ok(classifier) => analyzer.set_classifier(Arc::new(classifier)),
+            Err(e) => log::warn!(
+                "No sentiment model in {}: {}; Twitter sentiment disabled",
+                model_dir.display(),
+                e
+            ),
+        }
+        match CryptoPanicClient::from_env() {
+            Some(client) => analyzer.set_news_client(Arc::new(client)),
+            None => log::warn!("CRYPTOPANIC_API_KEY not set; CryptoPanic sentiment disabled"),
+        }
+        Ok(analyzer)
+    }
+
+    /// Analyzer with no sources registered.
+    pub fn empty(alert_tx: broadcast::Sender<SentimentAlert>) -> Self {
+        Self {
+            sources: Vec::new(),
+            classifier: None,
+            tweet_filter: Arc::new(TweetFilter::default()),
+            news: None,
+            history: None,
+            anomalies: Mutex::new(AnomalyDetector::new(AnomalyConfig::default())),
             alert_tx,
-        })
+        }
     }
 
-    pub async fn analyze_sentiment(&self) -> Result<SentimentData, Box<dyn Error>> {
-        let mut sources = Vec::new();
-        let mut total_score = 0.0;
-        let mut total_weight = 0.0;
+    pub fn set_anomaly_config(&mut self, config: AnomalyConfig) {
+        self.anomalies = Mutex::new(AnomalyDetector::new(config));
+    }
 
-        // Analyze Twitter sentiment via Masa
-        if let Ok(twitter_data) = self.get_twitter_sentiment().await {
-            total_score += twitter_data.score * 0.4; // 40% weight
-            total_weight += 0.4;
-            sources.push("Twitter".to_string());
+    /// Runs the anomaly detector on `observation` and raises an alert for each anomaly found.
+    pub fn check_anomalies(&self, observation: &Observation) -> Vec<AnomalyKind> {
+        let mut detector = self.anomalies.lock().unwrap();
+        let anomalies = detector.observe(observation);
+        if let Some(active_until) = detector.active_until() {
+            for anomaly in &anomalies {
+                log::warn!("Sentiment anomaly: {:?}", anomaly);
+                let _ = self.alert_tx.send(SentimentAlert::AnomalyDetected {
+                    anomaly: anomaly.clone(),
+                    score: observation.score as f32,
+                    active_until,
+                });
+            }
         }
+        anomalies
+    }
 
-        // Analyze CryptoPanic news
-        if let Ok(news_data) = self.get_cryptopanic_sentiment().await {
-            total_score += news_data.score * 0.3; // 30% weight
-            total_weight += 0.3;
-            sources.push("CryptoPanic".to_string());
-        }
+    /// Whether a recent anomaly should still hold back rebalances at `now`.
+    pub fn anomaly_active(&self, now: i64) -> bool {
+        self.anomalies.lock().unwrap().is_active(now)
+    }
+
+    /// Records every computed reading in `store` and serves historical queries from it.
+    pub fn set_history(&mut self, store: Arc<dyn SentimentStore>) {
+        self.history = Some(store);
+    }
 
-        // Analyze on-chain metrics
-        if let Ok(chain_data) = self.get_onchain_sentiment().await {
-            total_score += chain_data.score * 0.3; // 30% weight
-            total_weight += 0.3;
-            sources.push("On-chain".to_string());
+    /// Appends `data` to the history store, if one is set. Failures are logged, not returned.
+    pub fn record(&self, data: &SentimentData) {
+        if let Some(store) = &self.history {
+            if let Err(e) = store.append(data) {
+                log::warn!("Failed to record sentiment reading: {}", e);
+            }
         }
+    }
 
-        let final_score = if total_weight > 0.0 {
-            total_score / total_weight
-        } else {
-            50.0 // Neutral score if no data available
-        };
+    /// Summary of the readings recorded over the last `window`, or `None` without any.
+    pub async fn historical_stats(&self, window: Duration) -> Result<Option<SentimentWindowStats>, Box<dyn Error>> {
+        let store = self.history.as_ref().ok_or("No sentiment history store configured")?;
+        let since = chrono::Utc::now().timestamp() - window.as_secs() as i64;
+        Ok(SentimentWindowStats::from_readings(&store.load_since(since)?))
+    }
 
-        Ok(SentimentData {
-            score: final_score,
-            confidence: total_weight,
-            sources,
-            timestamp: chrono::Utc::now().timestamp(),
-        })
+    /// Confidence-weighted average score over the last `window`.
+    pub async fn get_historical_average(&self, window: Duration) -> Result<f64, Box<dyn Error>> {
+        self.historical_stats(window)
+            .await?
+            .map(|stats| stats.average)
+            .ok_or_else(|| "No sentiment history in window".into())
     }
 
-    async fn get_twitter_sentiment(&self) -> Result<SentimentData, Box<dyn Error>> {
-        let tweets = self.fetch_near_tweets().await?;
-        let mut total_score = 0.0;
+    /// Uses `classifier` for tweet scoring and registers it as the "twitter" source.
+    pub fn set_classifier(&mut self, classifier: Arc<SentimentClassifier>) {
+        self.register_source(
+            Box::new(TwitterSource::new(classifier.clone(), self.tweet_filter.clone())),
+            SourceSettings { weight: 0.4, ..Default::default() },
+        );
+        self.classifier = Some(classifier);
+    }
 
-        for tweet in tweets {
-            let sentiment = self.model.predict(&[&tweet])?;
-            total_score += match sentiment[0] {
-                rust_bert::pipelines::sentiment::Sentiment::Positive => 75.0,
-                rust_bert::pipelines::sentiment::Sentiment::Negative => 25.0,
-            };
+    /// Replaces the filter applied to tweets before scoring.
+    pub fn set_tweet_filter(&mut self, filter: TweetFilter) {
+        self.tweet_filter = Arc::new(filter);
+        if let Some(classifier) = self.classifier.clone() {
+            let settings = self
+                .sources
+                .iter()
+                .find(|s| s.source.name() == "twitter")
+                .map_or_else(|| SourceSettings { weight: 0.4, ..Default::default() }, |s| s.settings);
+            self.register_source(
+                Box::new(TwitterSource::new(classifier, self.tweet_filter.clone())),
+                settings,
+            );
+        }
+    }
+
+    /// Recent NEAR tweets, or nothing when no classifier is available to score them.
+    pub async fn get_near_tweets(&self) -> Result<Vec<Tweet>, Box<dyn Error>> {
+        if self.classifier.is_none() {
+            return Ok(Vec::new());
         }
+        fetch_near_tweets().await
+    }
 
-        Ok(SentimentData {
-            score: total_score / tweets.len() as f32,
-            confidence: 0.8,
-            sources: vec!["Twitter".to_string()],
-            timestamp: chrono::Utc::now().timestamp(),
-        })
+    /// Average sentiment (0-100) of the given tweets after filtering and weighting.
+    pub async fn analyze_tweets(&self, tweets: &[Tweet]) -> Result<f64, Box<dyn Error>> {
+        let classifier = self.classifier.as_ref().ok_or("No sentiment model loaded")?;
+        let reading = score_tweets(classifier, &self.tweet_filter, tweets.to_vec())?;
+        Ok(f64::from(reading.score))
     }
 
-    async fn get_cryptopanic_sentiment(&self) -> Result<SentimentData, Box<dyn Error>> {
-        let news = self.fetch_cryptopanic_news().await?;
-        let mut total_score = 0.0;
+    /// Uses `client` for the fallback news queries and registers it as the "cryptopanic" source.
+    pub fn set_news_client(&mut self, client: Arc<CryptoPanicClient>) {
+        self.register_source(
+            Box::new(CryptoPanicSource::new(client.clone())),
+            SourceSettings { weight: 0.3, ..Default::default() },
+        );
+        self.news = Some(client);
+    }
 
-        for item in news {
-            total_score += item.sentiment;
+    /// Recent NEAR/AURORA news, or nothing when no CryptoPanic client is configured.
+    pub async fn get_cryptopanic_news(&self) -> Result<Vec<NewsItem>, Box<dyn Error>> {
+        match &self.news {
+            Some(client) => client.fetch_news().await,
+            None => Ok(Vec::new()),
         }
+    }
 
-        Ok(SentimentData {
-            score: total_score / news.len() as f32,
-            confidence: 0.7,
-            sources: vec!["CryptoPanic".to_string()],
-            timestamp: chrono::Utc::now().timestamp(),
-        })
+    /// Average sentiment (0-100) of the given news items.
+    pub async fn analyze_news(&self, news: &[NewsItem]) -> Result<f64, Box<dyn Error>> {
+        news_score(news)
+            .map(f64::from)
+            .ok_or_else(|| "No news to analyze".into())
     }
 
-    async fn get_onchain_sentiment(&self) -> Result<SentimentData, Box<dyn Error>> {
-        // Analyze TVL changes, transaction volume, etc.
-        // This is a placeholder implementation
-        Ok(SentimentData {
-            score: 65.0,
-            confidence: 0.9,
-            sources: vec!["On-chain".to_string()],
-            timestamp: chrono::Utc::now().timestamp(),
-        })
+    /// Adds a source, replacing any registered under the same name.
+    pub fn register_source(&mut self, source: Box<dyn SentimentSource>, settings: SourceSettings) {
+        self.sources.retain(|s| s.source.name() != source.name());
+        self.sources.push(RegisteredSource { source, settings });
     }
 
-    async fn fetch_near_tweets(&self) -> Result<Vec<String>, Box<dyn Error>> {
-        // Implement Twitter API call via Masa
-        Ok(vec!["Sample NEAR tweet".to_string()])
+    /// Applies configured weights and timeouts by source name and drops disabled sources.
+    pub fn configure_sources(&mut self, config: &BTreeMap<String, SentimentSourceConfig>) {
+        self.sources.retain(|s| config.get(s.source.name()).map_or(true, |c| c.enabled));
+        for registered in &mut self.sources {
+            if let Some(c) = config.get(registered.source.name()) {
+                registered.settings = SourceSettings {
+                    weight: c.weight,
+                    timeout: Duration::from_secs(c.timeout_secs),
+                };
+            }
+        }
     }
 
-    async fn fetch_cryptopanic_news(&self) -> Result<Vec<NewsItem>, Box<dyn Error>> {
-        // Implement CryptoPanic API call
-        Ok(vec![NewsItem {
-            title: "Sample NEAR news".to_string(),
-            url: "https://example.com".to_string(),
-            source: "CryptoPanic".to_string(),
-            sentiment: 65.0,
-        }])
+    /// Queries every source concurrently and combines the readings, each weighted by
+    /// its configured weight times its own confidence. Sources that fail or time out
+    /// drop out; the reported confidence is the weighted confidence actually obtained
+    /// as a share of the total configured weight.
+    pub async fn analyze_sentiment(&self) -> Result<SentimentData, Box<dyn Error>> {
+        let results = join_all(self.sources.iter().map(|registered| async move {
+            let started = Instant::now();
+            let result = match tokio::time::timeout(registered.settings.timeout, registered.source.fetch()).await {
+                Ok(Ok(reading)) => Ok(reading),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(_) => Err(format!("timed out after {:?}", registered.settings.timeout)),
+            };
+            (result, started.elapsed())
+        }))
+        .await;
+
+        let mut sources = Vec::new();
+        let mut breakdown = Vec::with_capacity(results.len());
+        let mut total_score = 0.0;
+        let mut obtained_weight = 0.0;
+        let mut configured_weight = 0.0;
+        let mut filter_stats: Option<FilterStats> = None;
+
+        for (registered, (result, elapsed)) in self.sources.iter().zip(results) {
+            let name = registered.source.name().to_string();
+            let weight = registered.settings.weight;
+            configured_weight += weight;
+
+            let entry = match result {
+                Ok(reading) => {
+                    let confidence = reading.confidence.clamp(0.0, 1.0);
+                    total_score += reading.score * weight * confidence;
+                    obtained_weight += weight * confidence;
+                    sources.push(name.clone());
+                    if let Some(stats) = reading.filter_stats {
+                        filter_stats = Some(filter_stats.map_or(stats, |total| total.add(&stats)));
+                    }
+                    SourceBreakdown {
+                        source: name,
+                        weight,
+                        score: Some(reading.score),
+                        confidence,
+                        latency_ms: elapsed.as_millis() as u64,
+                        error: None,
+                    }
+                }
+                Err(error) => {
+                    log::warn!("Sentiment source {} failed: {}", name, error);
+                    SourceBreakdown {
+                        source: name,
+                        weight,
+                        score: None,
+                        confidence: 0.0,
+                        latency_ms: elapsed.as_millis() as u64,
+                        error: Some(error),
+                    }
+                }
+            };
+            breakdown.push(entry);
+        }
+
+        let final_score = if obtained_weight > 0.0 {
+            total_score / obtained_weight
+        } else {
+            50.0 // Neutral score if no data available
+        };
+
+        let data = SentimentData {
+            score: final_score,
+            confidence: if configured_weight > 0.0 { obtained_weight / configured_weight } else { 0.0 },
+            sources,
+            timestamp: chrono::Utc::now().timestamp(),
+            breakdown,
+            filter_stats,
+        };
+        if !data.sources.is_empty() {
+            self.check_anomalies(&Observation::from_sentiment(&data));
+            self.record(&data);
+        }
+        Ok(data)
     }
 
     pub fn start_monitoring(&self, threshold: f32) {
         let alert_tx = self.alert_tx.clone();
-        
+
         tokio::spawn(async move {
             let mut last_score = 50.0;
-            
+
             loop {
                 if let Ok(sentiment) = self.analyze_sentiment().await {
                     // Check threshold breach
@@ -188,3 +422,192 @@ impl SentimentAnalyzer {
         });
     }
 }
+
+/// Recent tweets about NEAR.
+pub async fn fetch_near_tweets() -> Result<Vec<Tweet>, Box<dyn Error>> {
+    // Implement Twitter API call via Masa
+    Ok(vec![Tweet {
+        id: "1".to_string(),
+        text: "Sample NEAR tweet".to_string(),
+        author: "near".to_string(),
+        followers: 1000,
+        account_age_days: 365,
+        verified: false,
+        likes: 0,
+        retweets: 0,
+        replies: 0,
+    }])
+}
+
+/// Filters `tweets` and scores the rest, weighted by author reputation and engagement.
+fn score_tweets(
+    classifier: &SentimentClassifier,
+    filter: &TweetFilter,
+    tweets: Vec<Tweet>,
+) -> Result<SourceReading, Box<dyn Error>> {
+    let (kept, stats) = filter.filter(tweets);
+    let texts: Vec<String> = kept.iter().map(|t| t.tweet.text.clone()).collect();
+    let weights: Vec<f32> = kept.iter().map(|t| t.weight).collect();
+
+    let mut reading = classifier
+        .analyze_weighted(&texts, &weights)?
+        .ok_or_else(|| format!("No tweets left after filtering {} received", stats.received))?;
+    reading.filter_stats = Some(stats);
+    Ok(reading)
+}
+
+/// Tweets about NEAR (via Masa), filtered and classified by the local sentiment model.
+pub struct TwitterSource {
+    classifier: Arc<SentimentClassifier>,
+    filter: Arc<TweetFilter>,
+}
+
+impl TwitterSource {
+    pub fn new(classifier: Arc<SentimentClassifier>, filter: Arc<TweetFilter>) -> Self {
+        Self { classifier, filter }
+    }
+}
+
+#[async_trait]
+impl SentimentSource for TwitterSource {
+    fn name(&self) -> &str {
+        "twitter"
+    }
+
+    async fn fetch(&self) -> Result<SourceReading, Box<dyn Error>> {
+        let tweets = fetch_near_tweets().await?;
+        score_tweets(&self.classifier, &self.filter, tweets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedSource {
+        name: &'static str,
+        reading: Option<SourceReading>,
+        delay: Duration,
+    }
+
+    impl FixedSource {
+        fn new(name: &'static str, score: f32, confidence: f32) -> Self {
+            Self {
+                name,
+                reading: Some(SourceReading { score, confidence, filter_stats: None }),
+                delay: Duration::ZERO,
+            }
+        }
+
+        fn failing(name: &'static str) -> Self {
+            Self {
+                name,
+                reading: None,
+                delay: Duration::ZERO,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl SentimentSource for FixedSource {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn fetch(&self) -> Result<SourceReading, Box<dyn Error>> {
+            tokio::time::sleep(self.delay).await;
+            self.reading.ok_or_else(|| "source unavailable".into())
+        }
+    }
+
+    fn weight(weight: f32) -> SourceSettings {
+        SourceSettings { weight, ..Default::default() }
+    }
+
+    fn analyzer() -> SentimentAnalyzer {
+        SentimentAnalyzer::empty(broadcast::channel(16).0)
+    }
+
+    #[tokio::test]
+    async fn weights_readings_by_weight_and_confidence() {
+        let mut analyzer = analyzer();
+        analyzer.register_source(Box::new(FixedSource::new("a", 80.0, 1.0)), weight(0.6));
+        analyzer.register_source(Box::new(FixedSource::new("b", 20.0, 0.5)), weight(0.4));
+
+        let sentiment = analyzer.analyze_sentiment().await.unwrap();
+        // (80 * 0.6 + 20 * 0.2) / 0.8
+        assert!((sentiment.score - 65.0).abs() < 1e-4);
+        assert!((sentiment.confidence - 0.8).abs() < 1e-6);
+        assert_eq!(sentiment.sources, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn failed_and_slow_sources_drop_out_and_lower_confidence() {
+        let mut analyzer = analyzer();
+        analyzer.register_source(Box::new(FixedSource::new("a", 70.0, 1.0)), weight(0.5));
+        analyzer.register_source(Box::new(FixedSource::failing("b")), weight(0.3));
+        analyzer.register_source(
+            Box::new(FixedSource {
+                delay: Duration::from_secs(5),
+                ..FixedSource::new("c", 10.0, 1.0)
+            }),
+            SourceSettings { weight: 0.2, timeout: Duration::from_millis(20) },
+        );
+
+        let sentiment = analyzer.analyze_sentiment().await.unwrap();
+        assert_eq!(sentiment.score, 70.0);
+        assert!((sentiment.confidence - 0.5).abs() < 1e-6);
+        assert_eq!(sentiment.breakdown[1].error.as_deref(), Some("source unavailable"));
+        assert!(sentiment.breakdown[2].error.as_deref().unwrap().starts_with("timed out"));
+        assert!(sentiment.breakdown[2].score.is_none());
+    }
+
+    #[tokio::test]
+    async fn config_overrides_weights_and_disables_sources() {
+        let mut analyzer = analyzer();
+        analyzer.register_source(Box::new(FixedSource::new("a", 80.0, 1.0)), weight(0.5));
+        analyzer.register_source(Box::new(FixedSource::new("b", 20.0, 1.0)), weight(0.5));
+
+        let config: BTreeMap<String, SentimentSourceConfig> = serde_yaml::from_str(
+            "a: { weight: 1.0 }\nb: { enabled: false, weight: 0.5 }",
+        )
+        .unwrap();
+        analyzer.configure_sources(&config);
+
+        let sentiment = analyzer.analyze_sentiment().await.unwrap();
+        assert_eq!(sentiment.score, 80.0);
+        assert_eq!(sentiment.confidence, 1.0);
+        assert_eq!(sentiment.breakdown.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn reports_tweet_filter_stats() {
+        let stats = FilterStats { received: 10, spam: 3, kept: 7, ..Default::default() };
+        let mut twitter = FixedSource::new("twitter", 60.0, 1.0);
+        twitter.reading.as_mut().unwrap().filter_stats = Some(stats);
+
+        let mut analyzer = analyzer();
+        analyzer.register_source(Box::new(twitter), weight(0.5));
+        analyzer.register_source(Box::new(FixedSource::new("news", 40.0, 1.0)), weight(0.5));
+
+        let sentiment = analyzer.analyze_sentiment().await.unwrap();
+        assert_eq!(sentiment.filter_stats, Some(stats));
+    }
+
+    #[tokio::test]
+    async fn alerts_when_sources_disagree() {
+        let (alert_tx, mut alerts) = broadcast::channel(16);
+        let mut analyzer = SentimentAnalyzer::empty(alert_tx);
+        analyzer.register_source(Box::new(FixedSource::new("twitter", 90.0, 1.0)), weight(0.5));
+        analyzer.register_source(Box::new(FixedSource::new("onchain", 35.0, 1.0)), weight(0.5));
+
+        let sentiment = analyzer.analyze_sentiment().await.unwrap();
+        assert!(analyzer.anomaly_active(sentiment.timestamp));
+        match alerts.try_recv().unwrap() {
+            SentimentAlert::AnomalyDetected { anomaly: AnomalyKind::SourceDisagreement { spread, .. }, .. } => {
+                assert_eq!(spread, 55.0)
+            }
+            other => panic!("unexpected alert: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::sync::Mutex;
+use crate::sentiment::SentimentData;
+
+const DAY_SECS: f64 = 86_400.0;
+/// Slope (points per day) below which the trend reads as flat.
+const TREND_THRESHOLD: f64 = 1.0;
+
+/// Time series of every computed `SentimentData`.
+pub trait SentimentStore: Send + Sync {
+    fn append(&self, data: &SentimentData) -> Result<(), Box<dyn Error>>;
+
+    /// Readings at or after `since` (unix seconds), oldest first.
+    fn load_since(&self, since: i64) -> Result<Vec<SentimentData>, Box<dyn Error>>;
+
+    /// Deletes readings older than `before` and returns how many were removed.
+    fn prune(&self, before: i64) -> Result<usize, Box<dyn Error>>;
+}
+
+/// Summary of the readings in a window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SentimentWindowStats {
+    pub samples: usize,
+    pub start: i64,
+    pub end: i64,
+    /// Confidence-weighted mean score; a plain mean if every confidence is zero.
+    pub average: f64,
+    pub p10: f64,
+    pub median: f64,
+    pub p90: f64,
+    /// Least-squares slope of the score, in points per day.
+    pub trend_per_day: f64,
+}
+
+impl SentimentWindowStats {
+    pub fn from_readings(readings: &[SentimentData]) -> Option<Self> {
+        let first = readings.first()?;
+        let last = readings.last()?;
+
+        let total_confidence: f64 = readings.iter().map(|r| r.confidence as f64).sum();
+        let average = if total_confidence > 0.0 {
+            readings
+                .iter()
+                .map(|r| r.score as f64 * r.confidence as f64)
+                .sum::<f64>()
+                / total_confidence
+        } else {
+            readings.iter().map(|r| r.score as f64).sum::<f64>() / readings.len() as f64
+        };
+
+        let mut sorted: Vec<f64> = readings.iter().map(|r| r.score as f64).collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        Some(Self {
+            samples: readings.len(),
+            start: first.timestamp,
+            end: last.timestamp,
+            average,
+            p10: percentile(&sorted, 0.1),
+            median: percentile(&sorted, 0.5),
+            p90: percentile(&sorted, 0.9),
+            trend_per_day: trend_slope(readings),
+        })
+    }
+
+    /// "rising", "falling" or "neutral".
+    pub fn trend_label(&self) -> &'static str {
+        if self.trend_per_day > TREND_THRESHOLD {
+            "rising"
+        } else if self.trend_per_day < -TREND_THRESHOLD {
+            "falling"
+        } else {
+            "neutral"
+        }
+    }
+}
+
+/// Linearly interpolated percentile of an ascending, non-empty slice; `p` in [0, 1].
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+fn trend_slope(readings: &[SentimentData]) -> f64 {
+    let n = readings.len() as f64;
+    let origin = readings[0].timestamp;
+    let days: Vec<f64> = readings
+        .iter()
+        .map(|r| (r.timestamp - origin) as f64 / DAY_SECS)
+        .collect();
+    let mean_x = days.iter().sum::<f64>() / n;
+    let mean_y = readings.iter().map(|r| r.score as f64).sum::<f64>() / n;
+
+    let mut covariance = 0.0;
+    let mut variance = 0.0;
+    for (x, reading) in days.iter().zip(readings) {
+        covariance += (x - mean_x) * (reading.score as f64 - mean_y);
+        variance += (x - mean_x).powi(2);
+    }
+    if variance > 0.0 {
+        covariance / variance
+    } else {
+        0.0
+    }
+}
+
+/// Default store: a single SQLite file.
+pub struct SqliteSentimentStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteSentimentStore {
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, Box<dyn Error>> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, Box<dyn Error>> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sentiment_readings (
+                id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp  INTEGER NOT NULL,
+                score      REAL NOT NULL,
+                confidence REAL NOT NULL,
+                sources    TEXT NOT NULL,
+                breakdown  TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS sentiment_readings_by_time
+                ON sentiment_readings (timestamp);",
+        )?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl SentimentStore for SqliteSentimentStore {
+    fn append(&self, data: &SentimentData) -> Result<(), Box<dyn Error>> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO sentiment_readings (timestamp, score, confidence, sources, breakdown)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                data.timestamp,
+                data.score as f64,
+                data.confidence as f64,
+                serde_json::to_string(&data.sources)?,
+                serde_json::to_string(&data.breakdown)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn load_since(&self, since: i64) -> Result<Vec<SentimentData>, Box<dyn Error>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, score, confidence, sources, breakdown
+             FROM sentiment_readings WHERE timestamp >= ?1 ORDER BY timestamp, id",
+        )?;
+        let rows = stmt
+            .query_map(params![since], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut readings = Vec::with_capacity(rows.len());
+        for (timestamp, score, confidence, sources, breakdown) in rows {
+            readings.push(SentimentData {
+                score: score as f32,
+                confidence: confidence as f32,
+                sources: serde_json::from_str(&sources)?,
+                timestamp,
+                breakdown: serde_json::from_str(&breakdown)?,
+                filter_stats: None,
+            });
+        }
+        Ok(readings)
+    }
+
+    fn prune(&self, before: i64) -> Result<usize, Box<dyn Error>> {
+        Ok(self
+            .conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM sentiment_readings WHERE timestamp < ?1", params![before])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sentiment::{SentimentAnalyzer, SourceBreakdown};
+    use std::sync::Arc;
+
+    fn reading(timestamp: i64, score: f32, confidence: f32) -> SentimentData {
+        SentimentData {
+            score,
+            confidence,
+            sources: vec!["twitter".to_string()],
+            timestamp,
+            breakdown: Vec::new(),
+            filter_stats: None,
+        }
+    }
+
+    #[test]
+    fn round_trips_and_prunes_readings() {
+        let store = SqliteSentimentStore::in_memory().unwrap();
+        let mut first = reading(100, 60.0, 0.8);
+        first.breakdown.push(SourceBreakdown {
+            source: "twitter".to_string(),
+            weight: 0.4,
+            score: Some(60.0),
+            confidence: 0.8,
+            latency_ms: 120,
+            error: None,
+        });
+        store.append(&first).unwrap();
+        store.append(&reading(200, 40.0, 0.5)).unwrap();
+
+        let loaded = store.load_since(0).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].sources, vec!["twitter"]);
+        assert_eq!(loaded[0].breakdown[0].latency_ms, 120);
+        assert_eq!(store.load_since(150).unwrap().len(), 1);
+
+        assert_eq!(store.prune(150).unwrap(), 1);
+        assert_eq!(store.load_since(0).unwrap()[0].timestamp, 200);
+    }
+
+    #[test]
+    fn summarizes_a_window() {
+        let day = DAY_SECS as i64;
+        let readings: Vec<SentimentData> = (0..5)
+            .map(|i| reading(i * day, 40.0 + 5.0 * i as f32, if i == 4 { 0.0 } else { 1.0 }))
+            .collect();
+        let stats = SentimentWindowStats::from_readings(&readings).unwrap();
+
+        assert_eq!(stats.samples, 5);
+        // The zero-confidence 60 doesn't count toward the average
+        assert!((stats.average - 47.5).abs() < 1e-9);
+        assert!((stats.median - 50.0).abs() < 1e-9);
+        assert!((stats.p10 - 42.0).abs() < 1e-9);
+        assert!((stats.p90 - 58.0).abs() < 1e-9);
+        assert!((stats.trend_per_day - 5.0).abs() < 1e-9);
+        assert_eq!(stats.trend_label(), "rising");
+
+        assert!(SentimentWindowStats::from_readings(&[]).is_none());
+        let flat = SentimentWindowStats::from_readings(&readings[..1]).unwrap();
+        assert_eq!(flat.trend_label(), "neutral");
+    }
+
+    #[tokio::test]
+    async fn analyzer_averages_recorded_history() {
+        let store = Arc::new(SqliteSentimentStore::in_memory().unwrap());
+        let mut analyzer = SentimentAnalyzer::empty(tokio::sync::broadcast::channel(16).0);
+        analyzer.set_history(store.clone());
+
+        let now = chrono::Utc::now().timestamp();
+        analyzer.record(&reading(now - 7200, 30.0, 1.0));
+        analyzer.record(&reading(now - 60, 70.0, 1.0));
+        analyzer.record(&reading(now - 3 * 86_400, 90.0, 1.0));
+
+        let average = analyzer
+            .get_historical_average(std::time::Duration::from_secs(86_400))
+            .await
+            .unwrap();
+        assert!((average - 50.0).abs() < 1e-9);
+
+        // A run with no source data is not history
+        analyzer.analyze_sentiment().await.unwrap();
+        assert_eq!(store.load_since(now - 86_400).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use crate::config::{SmoothingConfig, VolumeThresholds};
+
+const HOUR_SECS: i64 = 3600;
+const DAY_SECS: i64 = 24 * HOUR_SECS;
+
+/// Which smoothed value a signal carries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmoothingMethod {
+    /// Plain average of the samples in the window.
+    #[default]
+    Window,
+    /// Time-weighted exponential moving average with a half-window time constant.
+    Ema,
+}
+
+/// One raw sentiment reading; `volume` is the number of tweets behind it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SentimentSample {
+    pub timestamp: i64,
+    pub score: f64,
+    #[serde(default)]
+    pub volume: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SmoothedPoint {
+    pub timestamp: i64,
+    pub window_average: f64,
+    pub ema: f64,
+}
+
+/// A smoothed score that passed the sample and volume gates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SentimentSignal {
+    pub timestamp: i64,
+    /// `window_average` or `ema`, depending on the configured method.
+    pub score: f64,
+    pub raw: f64,
+    pub window_average: f64,
+    pub ema: f64,
+    pub samples: usize,
+    pub volume_24h: u64,
+    pub volume_hourly: u64,
+}
+
+/// Why no signal was emitted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum SignalGate {
+    TooFewSamples { samples: usize, required: usize },
+    LowDailyVolume { volume: u64, required: u64 },
+    LowHourlyVolume { volume: u64, required: u64 },
+}
+
+impl fmt::Display for SignalGate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignalGate::TooFewSamples { samples, required } => {
+                write!(f, "{} samples in window, {} required", samples, required)
+            }
+            SignalGate::LowDailyVolume { volume, required } => {
+                write!(f, "{} tweets in 24h, {} required", volume, required)
+            }
+            SignalGate::LowHourlyVolume { volume, required } => {
+                write!(f, "{} tweets in the last hour, {} required", volume, required)
+            }
+        }
+    }
+}
+
+/// Rolling buffer of raw sentiment readings and their smoothed values. Keeps
+/// whichever is longer of the smoothing window and the 24h volume window.
+pub struct SentimentBuffer {
+    window_secs: i64,
+    min_samples: usize,
+    volume: VolumeThresholds,
+    method: SmoothingMethod,
+    raw: VecDeque<SentimentSample>,
+    smoothed: VecDeque<SmoothedPoint>,
+}
+
+impl SentimentBuffer {
+    pub fn new(smoothing: &SmoothingConfig, volume: &VolumeThresholds) -> Self {
+        Self {
+            window_secs: (smoothing.window_size as i64 * HOUR_SECS).max(1),
+            min_samples: smoothing.min_samples,
+            volume: volume.clone(),
+            method: smoothing.method,
+            raw: VecDeque::new(),
+            smoothed: VecDeque::new(),
+        }
+    }
+
+    /// Adds a reading. Readings older than the latest one are ignored.
+    pub fn push(&mut self, sample: SentimentSample) {
+        if self.raw.back().map_or(false, |last| sample.timestamp < last.timestamp) {
+            return;
+        }
+        self.raw.push_back(sample);
+
+        let retention = self.window_secs.max(DAY_SECS);
+        while self.raw.front().map_or(false, |s| sample.timestamp - s.timestamp > retention) {
+            self.raw.pop_front();
+        }
+        while self.smoothed.front().map_or(false, |s| sample.timestamp - s.timestamp > retention) {
+            self.smoothed.pop_front();
+        }
+
+        let in_window: Vec<f64> = self
+            .samples_since(sample.timestamp - self.window_secs)
+            .map(|s| s.score)
+            .collect();
+        let window_average = in_window.iter().sum::<f64>() / in_window.len() as f64;
+
+        let ema = match self.smoothed.back() {
+            Some(previous) => {
+                let dt = (sample.timestamp - previous.timestamp) as f64;
+                let alpha = 1.0 - (-2.0 * dt / self.window_secs as f64).exp();
+                previous.ema + alpha * (sample.score - previous.ema)
+            }
+            None => sample.score,
+        };
+
+        self.smoothed.push_back(SmoothedPoint {
+            timestamp: sample.timestamp,
+            window_average,
+            ema,
+        });
+    }
+
+    pub fn raw_series(&self) -> Vec<SentimentSample> {
+        self.raw.iter().copied().collect()
+    }
+
+    pub fn smoothed_series(&self) -> Vec<SmoothedPoint> {
+        self.smoothed.iter().copied().collect()
+    }
+
+    /// The smoothed score as of `now`, or why there isn't enough data to trust one.
+    pub fn signal(&self, now: i64) -> Result<SentimentSignal, SignalGate> {
+        let samples = self.samples_since(now - self.window_secs).count();
+        let (latest, point) = match (self.raw.back(), self.smoothed.back()) {
+            (Some(latest), Some(point)) if samples > 0 && samples >= self.min_samples => (latest, point),
+            _ => {
+                return Err(SignalGate::TooFewSamples {
+                    samples,
+                    required: self.min_samples.max(1),
+                })
+            }
+        };
+
+        let volume_24h: u64 = self.samples_since(now - DAY_SECS).map(|s| s.volume).sum();
+        if volume_24h < self.volume.min_24h {
+            return Err(SignalGate::LowDailyVolume {
+                volume: volume_24h,
+                required: self.volume.min_24h,
+            });
+        }
+        let volume_hourly: u64 = self.samples_since(now - HOUR_SECS).map(|s| s.volume).sum();
+        if volume_hourly < self.volume.min_hourly {
+            return Err(SignalGate::LowHourlyVolume {
+                volume: volume_hourly,
+                required: self.volume.min_hourly,
+            });
+        }
+
+        Ok(SentimentSignal {
+            timestamp: point.timestamp,
+            score: match self.method {
+                SmoothingMethod::Window => point.window_average,
+                SmoothingMethod::Ema => point.ema,
+            },
+            raw: latest.score,
+            window_average: point.window_average,
+            ema: point.ema,
+            samples,
+            volume_24h,
+            volume_hourly,
+        })
+    }
+
+    fn samples_since(&self, since: i64) -> impl Iterator<Item = &SentimentSample> {
+        self.raw.iter().filter(move |s| s.timestamp > since)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(min_samples: usize, min_24h: u64, min_hourly: u64, method: SmoothingMethod) -> SentimentBuffer {
+        SentimentBuffer::new(
+            &SmoothingConfig { window_size: 12, min_samples, method },
+            &VolumeThresholds { min_24h, min_hourly },
+        )
+    }
+
+    fn sample(hour: i64, score: f64, volume: u64) -> SentimentSample {
+        SentimentSample { timestamp: hour * HOUR_SECS, score, volume }
+    }
+
+    #[test]
+    fn averages_over_the_window_and_smooths_jumps() {
+        let mut buffer = buffer(1, 0, 0, SmoothingMethod::Window);
+        for hour in 0..12 {
+            buffer.push(sample(hour, 50.0, 10));
+        }
+        buffer.push(sample(12, 90.0, 10));
+
+        let signal = buffer.signal(12 * HOUR_SECS).unwrap();
+        assert_eq!(signal.raw, 90.0);
+        // The sample at hour 0 has left the 12h window
+        assert!((signal.window_average - (11.0 * 50.0 + 90.0) / 12.0).abs() < 1e-9);
+        // One hour is a sixth of the EMA time constant
+        let alpha = 1.0 - (-1.0f64 / 6.0).exp();
+        assert!((signal.ema - (50.0 + alpha * 40.0)).abs() < 1e-9);
+        assert_eq!(signal.score, signal.window_average);
+
+        assert_eq!(buffer.raw_series().len(), 13);
+        assert_eq!(buffer.smoothed_series().len(), 13);
+    }
+
+    #[test]
+    fn gates_on_samples_and_volume() {
+        let mut buffer = buffer(3, 100, 20, SmoothingMethod::Ema);
+        assert_eq!(buffer.signal(0), Err(SignalGate::TooFewSamples { samples: 0, required: 3 }));
+
+        buffer.push(sample(0, 60.0, 30));
+        buffer.push(sample(1, 60.0, 30));
+        assert_eq!(
+            buffer.signal(HOUR_SECS),
+            Err(SignalGate::TooFewSamples { samples: 2, required: 3 })
+        );
+
+        buffer.push(sample(2, 60.0, 10));
+        assert_eq!(
+            buffer.signal(2 * HOUR_SECS),
+            Err(SignalGate::LowDailyVolume { volume: 70, required: 100 })
+        );
+
+        buffer.push(sample(3, 60.0, 40));
+        assert!(buffer.signal(3 * HOUR_SECS).is_ok());
+
+        buffer.push(sample(4, 60.0, 5));
+        assert_eq!(
+            buffer.signal(4 * HOUR_SECS),
+            Err(SignalGate::LowHourlyVolume { volume: 5, required: 20 })
+        );
+
+        buffer.push(sample(5, 60.0, 40));
+        let signal = buffer.signal(5 * HOUR_SECS).unwrap();
+        assert_eq!(signal.score, signal.ema);
+        assert_eq!(signal.volume_24h, 155);
+    }
+
+    #[test]
+    fn stale_samples_do_not_count() {
+        let mut buffer = buffer(2, 0, 0, SmoothingMethod::Window);
+        buffer.push(sample(0, 40.0, 0));
+        buffer.push(sample(1, 45.0, 0));
+        assert!(buffer.signal(HOUR_SECS).is_ok());
+        assert!(buffer.signal(20 * HOUR_SECS).is_err());
+
+        // Out-of-order readings are dropped
+        buffer.push(sample(0, 90.0, 0));
+        assert_eq!(buffer.raw_series().len(), 2);
+    }
+}
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+const SPAM_PHRASES: &[&str] = &[
+    "giveaway", "airdrop", "dm me", "dm for", "free near", "claim your", "claim now",
+    "whitelist", "presale", "100x", "1000x", "promo code", "retweet to win", "rt to win",
+    "follow and rt", "follow & rt", "like and retweet", "send near", "guaranteed",
+];
+const MAX_HASHTAGS: usize = 5;
+const MAX_CASHTAGS: usize = 3;
+/// Followers at which reputation saturates.
+const REPUTABLE_FOLLOWERS: f32 = 10_000.0;
+const MIN_REPUTATION: f32 = 0.2;
+
+/// A tweet with the author and engagement data the filter needs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tweet {
+    pub id: String,
+    pub text: String,
+    pub author: String,
+    #[serde(default)]
+    pub followers: u64,
+    #[serde(default)]
+    pub account_age_days: u32,
+    #[serde(default)]
+    pub verified: bool,
+    #[serde(default)]
+    pub likes: u64,
+    #[serde(default)]
+    pub retweets: u64,
+    #[serde(default)]
+    pub replies: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TweetFilterConfig {
+    pub min_followers: u64,
+    pub min_account_age_days: u32,
+    /// Word-set Jaccard similarity at or above which a tweet counts as a near-duplicate.
+    pub near_duplicate_similarity: f32,
+}
+
+impl Default for TweetFilterConfig {
+    fn default() -> Self {
+        Self {
+            min_followers: 50,
+            min_account_age_days: 30,
+            near_duplicate_similarity: 0.8,
+        }
+    }
+}
+
+/// How many tweets each filtering step removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterStats {
+    pub received: usize,
+    pub duplicates: usize,
+    pub near_duplicates: usize,
+    pub spam: usize,
+    pub low_reputation: usize,
+    pub kept: usize,
+}
+
+impl FilterStats {
+    pub fn add(&self, other: &FilterStats) -> FilterStats {
+        FilterStats {
+            received: self.received + other.received,
+            duplicates: self.duplicates + other.duplicates,
+            near_duplicates: self.near_duplicates + other.near_duplicates,
+            spam: self.spam + other.spam,
+            low_reputation: self.low_reputation + other.low_reputation,
+            kept: self.kept + other.kept,
+        }
+    }
+}
+
+/// A tweet that passed the filter and how much it should count.
+#[derive(Debug, Clone)]
+pub struct WeightedTweet {
+    pub tweet: Tweet,
+    pub weight: f32,
+}
+
+pub struct TweetFilter {
+    config: TweetFilterConfig,
+}
+
+impl TweetFilter {
+    pub fn new(config: TweetFilterConfig) -> Self {
+        Self { config }
+    }
+
+    /// Drops low-reputation authors, spam, and exact or near copies of earlier
+    /// tweets (retweets, copy-paste bot rings), then weights the rest by author
+    /// reputation and engagement.
+    pub fn filter(&self, tweets: Vec<Tweet>) -> (Vec<WeightedTweet>, FilterStats) {
+        let mut stats = FilterStats {
+            received: tweets.len(),
+            ..Default::default()
+        };
+        let mut seen = HashSet::new();
+        let mut kept_words: Vec<HashSet<String>> = Vec::new();
+        let mut kept = Vec::new();
+
+        for tweet in tweets {
+            if !self.is_reputable(&tweet) {
+                stats.low_reputation += 1;
+                continue;
+            }
+            if is_spam(&tweet.text) {
+                stats.spam += 1;
+                continue;
+            }
+
+            let normalized = normalize(&tweet.text);
+            if !seen.insert(normalized.clone()) {
+                stats.duplicates += 1;
+                continue;
+            }
+            let words: HashSet<String> = normalized.split(' ').map(str::to_string).collect();
+            if kept_words
+                .iter()
+                .any(|other| jaccard(&words, other) >= self.config.near_duplicate_similarity)
+            {
+                stats.near_duplicates += 1;
+                continue;
+            }
+            kept_words.push(words);
+
+            let weight = reputation(&tweet) * engagement(&tweet);
+            kept.push(WeightedTweet { tweet, weight });
+        }
+
+        stats.kept = kept.len();
+        (kept, stats)
+    }
+
+    fn is_reputable(&self, tweet: &Tweet) -> bool {
+        tweet.verified
+            || (tweet.followers >= self.config.min_followers
+                && tweet.account_age_days >= self.config.min_account_age_days)
+    }
+}
+
+impl Default for TweetFilter {
+    fn default() -> Self {
+        Self::new(TweetFilterConfig::default())
+    }
+}
+
+fn is_spam(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    let hashtags = lower.split_whitespace().filter(|w| w.starts_with('#')).count();
+    let cashtags = lower.split_whitespace().filter(|w| w.starts_with('$')).count();
+    hashtags > MAX_HASHTAGS
+        || cashtags > MAX_CASHTAGS
+        || SPAM_PHRASES.iter().any(|phrase| lower.contains(phrase))
+}
+
+/// Lowercased words without links, mentions, the retweet marker or punctuation.
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .filter(|w| !w.starts_with("http") && !w.starts_with('@') && !w.eq_ignore_ascii_case("rt"))
+        .map(|w| {
+            w.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '#' || *c == '$')
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+/// 0.2-1.0 on a log scale of followers; verified accounts get full weight.
+fn reputation(tweet: &Tweet) -> f32 {
+    if tweet.verified {
+        return 1.0;
+    }
+    let score = (1.0 + tweet.followers as f32).ln() / (1.0 + REPUTABLE_FOLLOWERS).ln();
+    score.clamp(MIN_REPUTATION, 1.0)
+}
+
+/// 1 for no engagement, growing logarithmically; retweets count double.
+fn engagement(tweet: &Tweet) -> f32 {
+    1.0 + ((tweet.likes + 2 * tweet.retweets + tweet.replies) as f32).ln_1p()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tweet(id: &str, text: &str, followers: u64) -> Tweet {
+        Tweet {
+            id: id.to_string(),
+            text: text.to_string(),
+            author: format!("user_{}", id),
+            followers,
+            account_age_days: 365,
+            verified: false,
+            likes: 0,
+            retweets: 0,
+            replies: 0,
+        }
+    }
+
+    #[test]
+    fn removes_duplicates_spam_and_unknown_accounts() {
+        let mut fresh = tweet("6", "NEAR sharding upgrade looks great", 5_000);
+        fresh.account_age_days = 3;
+        let mut verified = tweet("7", "Validators are upgrading nodes today", 10);
+        verified.verified = true;
+
+        let (kept, stats) = TweetFilter::default().filter(vec![
+            tweet("1", "NEAR sharding upgrade looks great https://t.co/abc", 500),
+            tweet("2", "RT @near: NEAR sharding upgrade looks great!", 500),
+            tweet("3", "near sharding upgrade looks great today", 500),
+            tweet("4", "Huge NEAR giveaway, RT to win and DM me", 20_000),
+            tweet("5", "Bearish on NEAR this week", 3),
+            fresh,
+            verified,
+            tweet("8", "#near #aurora #defi #yield #crypto #web3 #moon", 800),
+        ]);
+
+        assert_eq!(
+            stats,
+            FilterStats {
+                received: 8,
+                duplicates: 1,
+                near_duplicates: 1,
+                spam: 2,
+                low_reputation: 2,
+                kept: 2,
+            }
+        );
+        let ids: Vec<&str> = kept.iter().map(|t| t.tweet.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "7"]);
+    }
+
+    #[test]
+    fn weights_by_reputation_and_engagement() {
+        let quiet = tweet("1", "NEAR looks fine", 100);
+        let mut popular = tweet("2", "Aurora volumes are up", 100);
+        popular.likes = 50;
+        popular.retweets = 25;
+        let mut big = tweet("3", "Staking yields holding steady", 1_000_000);
+        big.account_age_days = 2_000;
+
+        let (kept, _) = TweetFilter::default().filter(vec![quiet, popular, big]);
+        assert!(kept[1].weight > kept[0].weight * 3.0);
+        assert_eq!(kept[2].weight, 1.0);
+        assert!(kept[0].weight >= MIN_REPUTATION);
+    }
+}
@@ -1,94 +1,303 @@
 use near_sdk::json_types::U128;
 use serde::{Deserialize, Serialize};
-use tokio::time::{Duration, interval};
+use tokio::time::Duration;
 use std::sync::Arc;
 use std::collections::HashMap;
+use crate::benchmark::MarketSnapshot;
+use crate::config::{PoolMetrics, PortfolioAllocation, SentimentThresholds, WorkflowConfig};
+use crate::ledger::{DryRunLedger, DryRunRpc};
+use crate::onchain::{IndexerDump, OnChainSource};
+use crate::optimizer::{YieldOptimizer, WALLET};
+use crate::performance::PerformanceTracker;
+use crate::protocols::{PartialExecution, TokenBook, WRAP_NEAR};
+use crate::registry::ProtocolRegistry;
+use crate::rpc::{GasUsage, JsonRpcNear, NearRpc};
+use crate::sentiment::{SentimentAnalyzer, SourceSettings};
+use crate::sentiment_store::SqliteSentimentStore;
+use crate::smoothing::{SentimentBuffer, SentimentSample};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct WorkflowConfig {
-    pub scraping: ScrapingConfig,
-    pub fallback: FallbackConfig,
-    pub rebalancing: RebalancingConfig,
-    pub safety: SafetyConfig,
+/// Strategy id the live portfolio is tracked under in the `PerformanceTracker`.
+pub const STRATEGY_ID: &str = "workflow";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketRegime {
+    Emergency,
+    Bearish,
+    Neutral,
+    Bullish,
+}
+
+/// Maps a 0-100 sentiment score onto the strategy regime.
+pub fn classify_sentiment(score: f64, thresholds: &SentimentThresholds, emergency_threshold: u8) -> MarketRegime {
+    if score <= emergency_threshold as f64 {
+        return MarketRegime::Emergency;
+    }
+
+    let score = score as u8;
+    if score <= thresholds.bearish {
+        MarketRegime::Bearish
+    } else if score >= thresholds.bullish {
+        MarketRegime::Bullish
+    } else {
+        MarketRegime::Neutral
+    }
+}
+
+/// Target percentage per yield bucket for a regime.
+pub fn regime_allocation(regime: MarketRegime) -> HashMap<String, u8> {
+    match regime {
+        // Emergency and bearish: Move to staking and stable pools
+        MarketRegime::Emergency | MarketRegime::Bearish => HashMap::from([
+            ("staking".to_string(), 50),
+            ("stable_pools".to_string(), 50),
+        ]),
+        // Neutral: Balanced approach
+        MarketRegime::Neutral => HashMap::from([
+            ("staking".to_string(), 30),
+            ("stable_pools".to_string(), 40),
+            ("near_pools".to_string(), 30),
+        ]),
+        // Bullish: Higher risk tolerance
+        MarketRegime::Bullish => HashMap::from([
+            ("staking".to_string(), 20),
+            ("near_pools".to_string(), 50),
+            ("aurora_pools".to_string(), 30),
+        ]),
+    }
+}
+
+/// Pool tags (see the adapters' `get_pools`) that place a pool in each
+/// `regime_allocation` bucket. A tag equal to the bucket name always matches.
+const BUCKET_TAGS: &[(&str, &[&str])] = &[
+    ("staking", &["staking"]),
+    ("stable_pools", &["stable_pool", "usdc", "usdt", "dai"]),
+    ("near_pools", &["near_stable", "near_meta", "near"]),
+    ("aurora_pools", &["aurora"]),
+];
+
+/// Whether `pool` belongs to the `regime_allocation` bucket `bucket`.
+pub fn in_bucket(bucket: &str, pool: &PoolMetrics) -> bool {
+    let tags = BUCKET_TAGS
+        .iter()
+        .find(|(name, _)| *name == bucket)
+        .map_or(&[][..], |(_, tags)| *tags);
+    pool.tags.iter().any(|tag| tag == bucket || tags.contains(&tag.as_str()))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MarketSentiment {
     pub score: f64,
+    /// Comma-separated sources that answered, or `historical` for the fallback.
     pub source: String,
     pub timestamp: u64,
+    /// Tweets kept by the filter behind the score; zero without a Twitter reading.
+    #[serde(default)]
+    pub volume: u64,
 }
 
 pub struct WorkflowManager {
     config: WorkflowConfig,
     sentiment_analyzer: Arc<SentimentAnalyzer>,
-    portfolio_manager: Arc<PortfolioManager>,
-    near_client: Arc<NearClient>,
+    /// Shared with the API server, which also feeds it posted samples.
+    sentiment_buffer: Arc<tokio::sync::RwLock<SentimentBuffer>>,
+    /// Signs for the account holding the positions; records instead in dry-run mode.
+    rpc: Arc<dyn NearRpc>,
+    registry: Arc<ProtocolRegistry>,
+    /// Prices the adapters value pools with; refreshed every tick.
+    tokens: Arc<TokenBook>,
+    optimizer: Arc<tokio::sync::Mutex<YieldOptimizer>>,
+    performance: Arc<tokio::sync::RwLock<PerformanceTracker>>,
+    /// Set in dry-run mode; every on-chain action goes here instead.
+    ledger: Option<Arc<DryRunLedger>>,
 }
 
 impl WorkflowManager {
-    pub async fn new(config_path: &str) -> anyhow::Result<Self> {
-        let config = load_config(config_path)?;
+    /// Builds the workflow on the optimizer, tracker, sentiment buffer and dry-run ledger
+    /// the API server also serves from. With a ledger, every change call goes to it
+    /// instead of the chain.
+    pub async fn new(
+        config: WorkflowConfig,
+        optimizer: Arc<tokio::sync::Mutex<YieldOptimizer>>,
+        performance: Arc<tokio::sync::RwLock<PerformanceTracker>>,
+        sentiment_buffer: Arc<tokio::sync::RwLock<SentimentBuffer>>,
+        ledger: Option<Arc<DryRunLedger>>,
+    ) -> anyhow::Result<Self> {
+        let (alert_tx, _) = tokio::sync::broadcast::channel(100);
+        let mut sentiment_analyzer = SentimentAnalyzer::new(alert_tx)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to initialize sentiment analyzer: {}", e))?;
+        if let Some(path) = &config.scraping.onchain.indexer_dump {
+            sentiment_analyzer.register_source(
+                Box::new(OnChainSource::new(
+                    Box::new(IndexerDump::new(path)),
+                    config.scraping.onchain.lookback_hours,
+                )),
+                SourceSettings { weight: 0.3, ..Default::default() },
+            );
+        }
+        sentiment_analyzer.configure_sources(&config.scraping.sources);
+        sentiment_analyzer.set_anomaly_config(config.safety.anomaly.clone());
+        let history = SqliteSentimentStore::open(&config.fallback.history_path)
+            .map_err(|e| anyhow::anyhow!("Failed to open sentiment history {}: {}", config.fallback.history_path, e))?;
+        sentiment_analyzer.set_history(Arc::new(history));
+
+        let credentials_path = config
+            .execution
+            .credentials_path
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("execution.credentials_path is not set"))?;
+        let mut rpc: Arc<dyn NearRpc> = Arc::new(
+            JsonRpcNear::from_credentials_file(&config.execution.rpc_url, credentials_path)
+                .map_err(|e| anyhow::anyhow!("Failed to load NEAR credentials {}: {}", credentials_path, e))?,
+        );
+        if let Some(ledger) = &ledger {
+            log::warn!("Dry-run mode: on-chain actions will be recorded, not sent");
+            rpc = Arc::new(DryRunRpc::new(rpc, ledger.clone()));
+        }
+        let tokens = Arc::new(TokenBook::default());
+        let registry = ProtocolRegistry::from_config(&config.yield_sources, rpc.clone(), tokens.clone());
+        log::info!("Tracking yield sources: {:?}", registry.names());
+
         Ok(Self {
             config,
-            sentiment_analyzer: Arc::new(SentimentAnalyzer::new()),
-            portfolio_manager: Arc::new(PortfolioManager::new()),
-            near_client: Arc::new(NearClient::new()),
+            sentiment_analyzer: Arc::new(sentiment_analyzer),
+            sentiment_buffer,
+            rpc,
+            registry: Arc::new(registry),
+            tokens,
+            optimizer,
+            performance,
+            ledger,
         })
     }
 
+    /// Refreshes token prices, then the optimizer's view of every registered pool.
+    /// A failed price fetch keeps the last known prices.
+    pub async fn refresh_pools(&self) {
+        if let Err(e) = self.tokens.refresh_prices(&self.config.yield_sources.price_feed).await {
+            log::warn!("Failed to refresh token prices: {}", e);
+        }
+        self.optimizer.lock().await.refresh_pools(&self.registry).await;
+    }
+
+    /// Records NEAR's price and the tracked staking and stablecoin lending APYs, which
+    /// the benchmarks and the volatility check are measured from.
+    async fn record_market(&self) {
+        let near_price = match self.tokens.near_price() {
+            Some(price) => price,
+            None => {
+                log::warn!("No NEAR price, skipping market snapshot");
+                return;
+            }
+        };
+        let pools = self.optimizer.lock().await.pools();
+
+        let mut performance = self.performance.write().await;
+        match MarketSnapshot::from_pools(chrono::Utc::now(), near_price, &pools, performance.latest_market()) {
+            Some(snapshot) => performance.record_market(snapshot),
+            None => log::info!("No market snapshot yet, staking or stablecoin APY unknown"),
+        }
+    }
+
+    /// Runs a tick right away, then one per `default_interval`, or per
+    /// `volatile_interval` after a tick that saw NEAR move past the volatility threshold.
     pub async fn start(&self) -> anyhow::Result<()> {
-        let mut interval = interval(Duration::from_secs(
-            self.config.scraping.default_interval
-        ));
+        let mut period = Duration::ZERO;
 
         loop {
-            interval.tick().await;
+            tokio::time::sleep(period).await;
             
-            // 1. Check NEAR price volatility
+            // 1. Check NEAR price volatility, which sets the wait before the next tick
             let price_change = self.check_price_volatility().await?;
-            if price_change.abs() >= self.config.scraping.price_volatility_threshold {
-                interval = interval(Duration::from_secs(
+            period = Duration::from_secs(
+                if price_change.abs() >= self.config.scraping.price_volatility_threshold {
                     self.config.scraping.volatile_interval
-                ));
+                } else {
+                    self.config.scraping.default_interval
+                },
+            );
+
+            // 2. Refresh pool data the allocation is optimized over and record the
+            // market it implies, then claim any withdrawals settled since the last tick
+            self.refresh_pools().await;
+            self.record_market().await;
+            let claimed = self.registry.settle_withdrawals().await;
+            if !claimed.is_empty() {
+                log::info!(
+                    "Settled {} withdrawals, {} NEAR spent on gas",
+                    claimed.len(),
+                    GasUsage::from_outcomes(&claimed).near_spent()
+                );
             }
 
-            // 2. Gather sentiment data
-            let sentiment = self.gather_sentiment_data().await?;
-            
-            // 3. Apply strategy based on sentiment
-            self.apply_strategy(&sentiment).await?;
+            // 3. Take this tick's sentiment reading
+            let sentiment = match self.gather_sentiment_data().await {
+                Ok(sentiment) => sentiment,
+                Err(e) => {
+                    log::warn!("No sentiment reading this tick: {}", e);
+                    continue;
+                }
+            };
+
+            // 4. Apply strategy based on the smoothed sentiment, if there's enough data
+            let signal = {
+                let mut buffer = self.sentiment_buffer.write().await;
+                buffer.push(SentimentSample {
+                    timestamp: sentiment.timestamp as i64,
+                    score: sentiment.score,
+                    volume: sentiment.volume,
+                });
+                buffer.signal(sentiment.timestamp as i64)
+            };
+            // The gates and the anomaly detector only hold back a regular rebalance; an
+            // emergency reading always reaches the safety strategy. Failures are retried
+            // on the next tick.
+            let result = if self.regime(sentiment.score) == MarketRegime::Emergency {
+                self.apply_safety_strategy(&sentiment).await
+            } else {
+                match signal {
+                    Ok(_) if self.sentiment_analyzer.anomaly_active(sentiment.timestamp as i64) => {
+                        log::warn!("Holding allocation, sentiment anomaly active");
+                        Ok(())
+                    }
+                    Ok(signal) => {
+                        self.apply_strategy(&MarketSentiment {
+                            score: signal.score,
+                            ..sentiment
+                        })
+                        .await
+                    }
+                    Err(gate) => {
+                        log::info!("Holding allocation, sentiment signal gated: {}", gate);
+                        Ok(())
+                    }
+                }
+            };
+            if let Err(e) = result {
+                log::error!("Failed to apply strategy: {}", e);
+            }
             
-            // 4. Monitor and adjust
+            // 5. Monitor and adjust
             self.monitor_performance().await?;
         }
     }
 
+    /// One combined reading across every configured source, which the analyzer also
+    /// records and checks for anomalies. Falls back to the historical average when no
+    /// source answered.
     async fn gather_sentiment_data(&self) -> anyhow::Result<MarketSentiment> {
-        // Try Twitter first
-        let tweets = self.sentiment_analyzer.get_near_tweets().await?;
-        
-        if tweets.len() >= self.config.scraping.minimum_tweets {
-            let score = self.sentiment_analyzer.analyze_tweets(&tweets).await?;
-            return Ok(MarketSentiment {
-                score,
-                source: "twitter".to_string(),
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)?
-                    .as_secs(),
-            });
-        }
+        let reading = self.sentiment_analyzer
+            .analyze_sentiment()
+            .await
+            .map_err(|e| anyhow::anyhow!("Sentiment analysis failed: {}", e))?;
 
-        // Fallback to CryptoPanic
-        let news = self.sentiment_analyzer.get_cryptopanic_news().await?;
-        if !news.is_empty() {
-            let score = self.sentiment_analyzer.analyze_news(&news).await?;
+        if !reading.sources.is_empty() {
             return Ok(MarketSentiment {
-                score,
-                source: "cryptopanic".to_string(),
-                timestamp: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)?
-                    .as_secs(),
+                score: reading.score as f64,
+                source: reading.sources.join(","),
+                timestamp: reading.timestamp as u64,
+                volume: reading.filter_stats.map_or(0, |stats| stats.kept as u64),
             });
         }
 
@@ -97,97 +306,231 @@ impl WorkflowManager {
             .get_historical_average(Duration::from_secs(
                 self.config.fallback.historical_window
             ))
-            .await?;
+            .await
+            .map_err(|e| anyhow::anyhow!("No historical sentiment to fall back on: {}", e))?;
             
         Ok(MarketSentiment {
             score: historical_score,
             source: "historical".to_string(),
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)?
-                .as_secs(),
+            timestamp: reading.timestamp as u64,
+            volume: 0,
         })
     }
 
+    fn regime(&self, score: f64) -> MarketRegime {
+        classify_sentiment(
+            score,
+            &self.config.scraping.sentiment_thresholds,
+            self.config.safety.emergency_threshold,
+        )
+    }
+
     async fn apply_strategy(&self, sentiment: &MarketSentiment) -> anyhow::Result<()> {
-        let current_allocation = self.portfolio_manager.get_allocation().await?;
-        
+        let regime = self.regime(sentiment.score);
+
         // Apply AI safety strategy
-        if sentiment.score <= self.config.safety.emergency_threshold as f64 {
-            self.apply_safety_strategy().await?;
+        if regime == MarketRegime::Emergency {
+            self.apply_safety_strategy(sentiment).await?;
             return Ok(());
         }
 
         // Regular strategy application
-        match sentiment.score as u8 {
-            0..=25 => {
-                // Bearish: Move to staking and stable pools
-                self.portfolio_manager
-                    .rebalance(HashMap::from([
-                        ("staking".to_string(), 50),
-                        ("stable_pools".to_string(), 50),
-                    ]))
-                    .await?;
-            }
-            26..=74 => {
-                // Neutral: Balanced approach
-                self.portfolio_manager
-                    .rebalance(HashMap::from([
-                        ("staking".to_string(), 30),
-                        ("stable_pools".to_string(), 40),
-                        ("near_pools".to_string(), 30),
-                    ]))
-                    .await?;
+        self.rebalance(regime_allocation(regime), sentiment).await
+    }
+
+    async fn apply_safety_strategy(&self, sentiment: &MarketSentiment) -> anyhow::Result<()> {
+        // Emergency reallocation
+        self.rebalance(regime_allocation(MarketRegime::Emergency), sentiment).await?;
+
+        let proxy_id = match &self.config.contracts.proxy_deployment.contract_id {
+            Some(proxy_id) => proxy_id.clone(),
+            None => {
+                log::warn!("No safety proxy configured, emergency handled by reallocation only");
+                return Ok(());
             }
-            75..=100 => {
-                // Bullish: Higher risk tolerance
-                self.portfolio_manager
-                    .rebalance(HashMap::from([
-                        ("staking".to_string(), 20),
-                        ("near_pools".to_string(), 50),
-                        ("aurora_pools".to_string(), 30),
-                    ]))
-                    .await?;
+        };
+        let proxy_deployed = self
+            .rpc
+            .view(&proxy_id, "get_safety_parameters", serde_json::json!({}))
+            .await
+            .is_ok();
+
+        let contracts = &self.config.contracts.proxy_deployment;
+        if !proxy_deployed {
+            // Deploying needs the contract owner's keys, so it stays a manual step
+            match &self.ledger {
+                Some(ledger) => ledger.record(
+                    "SafetyProxy",
+                    &proxy_id,
+                    "deploy",
+                    serde_json::json!({ "initial_stake": contracts.initial_stake }),
+                    0,
+                    contracts.max_gas,
+                )?,
+                None => return Err(anyhow::anyhow!("Safety proxy {} is not deployed", proxy_id)),
             }
         }
 
+        // Recorded by the dry-run RPC in dry-run mode
+        self.rpc
+            .call(
+                &proxy_id,
+                "execute_strategy",
+                serde_json::json!({ "sentiment_score": sentiment.score as u8 }),
+                0,
+                contracts.max_gas,
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("Safety proxy execute_strategy failed: {}", e))?;
+
         Ok(())
     }
 
-    async fn apply_safety_strategy(&self) -> anyhow::Result<()> {
-        // Emergency reallocation
-        self.portfolio_manager
-            .rebalance(HashMap::from([
-                ("staking".to_string(), 50),
-                ("stable_pools".to_string(), 50),
-            ]))
-            .await?;
-
-        // Deploy safety proxy contract if not already deployed
-        if !self.near_client.check_proxy_deployed().await? {
-            self.near_client.deploy_safety_proxy().await?;
+    /// Plans and executes the move to `targets`. In dry-run mode the adapters' calls
+    /// land in the ledger through the dry-run RPC, while positions are read live.
+    async fn rebalance(&self, targets: HashMap<String, u8>, sentiment: &MarketSentiment) -> anyhow::Result<()> {
+        let target = self.target_allocations(&targets).await;
+        let (current, portfolio_value) = self.current_allocations().await?;
+        if portfolio_value.0 == 0 {
+            log::info!("No funds to rebalance");
+            return Ok(());
         }
+        let plan = self
+            .optimizer
+            .lock()
+            .await
+            .execute_rebalance(&current, &target, portfolio_value)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to plan rebalance: {}", e))?;
+        if plan.is_empty() {
+            log::info!("Allocation within the rebalance threshold");
+            return Ok(());
+        }
+
+        let (gas, result) = match plan.execute(&self.registry).await {
+            Ok(report) => (report.gas, Ok(report.legs.len())),
+            Err(e) => {
+                let gas = e
+                    .downcast_ref::<PartialExecution>()
+                    .map_or_else(GasUsage::default, |partial| GasUsage::from_outcomes(&partial.outcomes));
+                (gas, Err(anyhow::anyhow!("Rebalance failed: {}", e)))
+            }
+        };
 
+        // A failure before any transaction landed changed nothing worth recording
+        if result.is_ok() || gas != GasUsage::default() {
+            if let Err(e) = self.record_rebalance(portfolio_value, gas, sentiment.score).await {
+                log::error!("Failed to record rebalance performance: {}", e);
+            }
+        }
+
+        let legs = result?;
+        log::info!("Rebalanced in {} legs, {} NEAR spent on gas", legs, gas.near_spent());
+        Ok(())
+    }
+
+    /// Records an executed rebalance under `STRATEGY_ID`, valued in USD at the book's
+    /// NEAR price. Position values exclude the NEAR balance the gas was paid from, so
+    /// they are gross of gas and the tracker deducts `gas` exactly once.
+    async fn record_rebalance(&self, value_before: U128, gas: GasUsage, sentiment_score: f64) -> anyhow::Result<()> {
+        let near_price = self
+            .tokens
+            .near_price()
+            .ok_or_else(|| anyhow::anyhow!("No NEAR price to value the portfolio"))?;
+        let (_, value_after) = self.current_allocations().await?;
+        let to_usd = |value: U128| value.0 as f64 / 1e24 * near_price;
+
+        let now = chrono::Utc::now();
+        let mut performance = self.performance.write().await;
+        performance.register_strategy(STRATEGY_ID, to_usd(value_before), now);
+        performance.record_action(
+            STRATEGY_ID,
+            now,
+            to_usd(value_after),
+            sentiment_score as f32,
+            "rebalance".to_string(),
+            gas,
+        );
         Ok(())
     }
 
+    /// The signer's open positions in every known pool plus its idle wNEAR, as shares
+    /// of their combined value, and that value in yoctoNEAR. Native NEAR pays for gas
+    /// and isn't counted.
+    async fn current_allocations(&self) -> anyhow::Result<(Vec<PortfolioAllocation>, U128)> {
+        let pools = self.optimizer.lock().await.pools();
+        let mut positions = Vec::new();
+        for pool in pools {
+            let adapter = match self.registry.get(&pool.protocol) {
+                Some(adapter) => adapter,
+                None => continue,
+            };
+            let value = adapter
+                .position_value(&pool.pool_id)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to value {} pool {}: {}", pool.protocol, pool.pool_id, e))?;
+            if value.0 > 0 {
+                positions.push((pool.pool_id, pool.protocol, pool.apy.unwrap_or(0.0), pool.risk_score, value.0));
+            }
+        }
+
+        let idle: U128 = serde_json::from_value(
+            self.rpc
+                .view(WRAP_NEAR, "ft_balance_of", serde_json::json!({ "account_id": self.rpc.account_id() }))
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to read the wNEAR balance: {}", e))?,
+        )?;
+        if idle.0 > 0 {
+            positions.push((WRAP_NEAR.to_string(), WALLET.to_string(), 0.0, 1.0, idle.0));
+        }
+
+        let total: u128 = positions.iter().map(|(.., value)| value).sum();
+        let allocations = positions
+            .into_iter()
+            .map(|(pool_id, protocol, expected_apy, risk_score, value)| PortfolioAllocation {
+                pool_id,
+                protocol,
+                allocation_percentage: value as f64 / total as f64,
+                expected_apy,
+                risk_score,
+            })
+            .collect();
+        Ok((allocations, U128(total)))
+    }
+
+    /// Splits each bucket's share of `targets` across that bucket's pools as the
+    /// optimizer recommends, capping pools once across the whole portfolio.
+    async fn target_allocations(&self, targets: &HashMap<String, u8>) -> Vec<PortfolioAllocation> {
+        self.optimizer.lock().await.optimize_buckets(targets, in_bucket).await
+    }
+
     async fn monitor_performance(&self) -> anyhow::Result<()> {
-        let performance = self.portfolio_manager.calculate_performance().await?;
+        let performance = match self.performance.read().await.get_strategy_stats(STRATEGY_ID) {
+            Some(performance) => performance,
+            None => return Ok(()),
+        };
         
         // Log performance metrics
         log::info!("Strategy Performance: {:#?}", performance);
         
         // Adjust allocation based on performance
+        let mut optimizer = self.optimizer.lock().await;
         if performance.apy > 12.0 {  // If performing well, allow more risk
-            self.portfolio_manager.increase_risk_tolerance().await?;
+            optimizer.increase_risk_tolerance();
         } else if performance.apy < 5.0 {  // If performing poorly, reduce risk
-            self.portfolio_manager.decrease_risk_tolerance().await?;
+            optimizer.decrease_risk_tolerance();
         }
 
         Ok(())
     }
 
     async fn check_price_volatility(&self) -> anyhow::Result<f64> {
-        let price_change = self.near_client.get_price_change_24h().await?;
+        let price_change = self
+            .performance
+            .read()
+            .await
+            .near_price_change(chrono::Utc::now(), chrono::Duration::hours(24))
+            .unwrap_or(0.0);
         
         // Log volatility
         log::info!("NEAR 24h price change: {}%", price_change);
@@ -1,7 +1,71 @@
+use near_contract_standards::fungible_token::metadata::{
+    FungibleTokenMetadata, FungibleTokenMetadataProvider, FT_METADATA_SPEC,
+};
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_contract_standards::fungible_token::FungibleToken;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::UnorderedMap;
-use near_sdk::json_types::U128;
-use near_sdk::{env, near_bindgen, AccountId, Balance, PanicOnDefault};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::json_types::{ValidAccountId, U128};
+use near_sdk::{
+    assert_one_yocto, env, ext_contract, near_bindgen, AccountId, Balance, Gas, PanicOnDefault,
+    Promise, PromiseOrValue, PromiseResult,
+};
+use uint::construct_uint;
+
+construct_uint! {
+    /// 256-bit unsigned integer, for share math that would overflow u128.
+    pub struct U256(4);
+}
+
+/// One whole share; shares use the same 24 decimals as NEAR.
+pub const SHARE_UNIT: Balance = 1_000_000_000_000_000_000_000_000;
+
+const DEFAULT_MIN_REBALANCE_INTERVAL_SECS: u64 = 3600;
+const MAX_REBALANCE_INTERVAL_SECS: u64 = 30 * 24 * 3600;
+/// Basis points, like `PoolInfo::apy`.
+const DEFAULT_MIN_APY_IMPROVEMENT: u64 = 50;
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+const GAS_FOR_FT_TRANSFER: Gas = 10_000_000_000_000;
+const GAS_FOR_RESOLVE_WITHDRAW: Gas = 10_000_000_000_000;
+const GAS_FOR_POOL_CALL: Gas = 20_000_000_000_000;
+/// Gas a rebalance callback burns itself, on top of whatever it attaches to the
+/// promises it chains.
+const GAS_FOR_CALLBACK_EXECUTION: Gas = 10_000_000_000_000;
+const GAS_FOR_ON_POOL_RESTORED: Gas = GAS_FOR_CALLBACK_EXECUTION;
+/// May chain the rollback deposit into the old pool and `on_pool_restored`.
+const GAS_FOR_ON_POOL_DEPOSITED: Gas = GAS_FOR_POOL_CALL + GAS_FOR_ON_POOL_RESTORED + GAS_FOR_CALLBACK_EXECUTION;
+/// Chains the deposit into the new pool and `on_pool_deposited`.
+const GAS_FOR_ON_POOL_WITHDRAWN: Gas = GAS_FOR_POOL_CALL + GAS_FOR_ON_POOL_DEPOSITED + GAS_FOR_CALLBACK_EXECUTION;
+/// Chains the withdrawal from the old pool and `on_pool_withdrawn`.
+const GAS_FOR_ON_POOL_POSITION: Gas = GAS_FOR_POOL_CALL + GAS_FOR_ON_POOL_WITHDRAWN + GAS_FOR_CALLBACK_EXECUTION;
+/// Only sends the withdrawn NEAR on to the depositor.
+const GAS_FOR_ON_WITHDRAW_FROM_POOL: Gas = GAS_FOR_CALLBACK_EXECUTION;
+
+#[ext_contract(ext_fungible_token)]
+pub trait FungibleTokenContract {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+/// Interface every pool contract exposes: `deposit` takes the attached NEAR,
+/// `withdraw` sends `amount` back to the caller and returns what it sent, and
+/// `get_position` reports an account's principal plus accrued yield.
+#[ext_contract(ext_pool)]
+pub trait YieldPool {
+    fn deposit(&mut self);
+    fn withdraw(&mut self, amount: U128) -> U128;
+    fn get_position(&self, account_id: AccountId) -> U128;
+}
+
+#[ext_contract(ext_self)]
+pub trait SelfCallbacks {
+    fn on_token_withdrawn(&mut self, account_id: AccountId, token_id: AccountId, amount: U128) -> U128;
+    fn on_pool_position(&mut self, from: String, to: String);
+    fn on_pool_withdrawn(&mut self, from: String, to: String);
+    fn on_pool_deposited(&mut self, from: String, to: String, amount: U128, withdrew: bool);
+    fn on_pool_restored(&mut self, pool_id: String, amount: U128);
+    fn on_withdraw_from_pool(&mut self, account_id: AccountId, amount: U128, burned: U128, shortfall: U128);
+}
 
 #[derive(BorshDeserialize, BorshSerialize)]
 pub enum RiskLevel {
@@ -30,8 +94,27 @@ pub struct AutoRebalanceAgent {
     pub min_apy: u64,
     pub pools: UnorderedMap<String, PoolInfo>,
     pub current_pool: String,
+    /// yoctoNEAR deployed in `current_pool`; the rest of `total_balance` is idle here.
+    pub pool_balance: Balance,
+    /// Set while a rebalance's, or a withdrawal's, pool promises are in flight.
+    pub rebalancing: bool,
+    /// Accounts allowed to trigger rebalances; managed by the owner.
+    pub keepers: UnorderedSet<AccountId>,
+    pub min_rebalance_interval_secs: u64,
+    /// APY, in basis points, a pool must beat the current one by before a switch.
+    pub min_apy_improvement: u64,
+    /// Block timestamp (ns) of the last rebalance that completed.
+    pub last_rebalance_at: u64,
+    /// Underlying yoctoNEAR held for depositors, including yield.
     pub total_balance: Balance,
-    pub balances: UnorderedMap<AccountId, Balance>,
+    /// Vault shares as a NEP-141 token; its total supply is the total share count.
+    pub token: FungibleToken,
+    pub metadata: LazyOption<FungibleTokenMetadata>,
+    /// NEP-141 tokens accepted through `ft_on_transfer`.
+    pub token_whitelist: UnorderedSet<AccountId>,
+    /// Deposited amount per (account, token).
+    pub token_balances: LookupMap<(AccountId, AccountId), Balance>,
+    pub token_totals: UnorderedMap<AccountId, Balance>,
 }
 
 #[near_bindgen]
@@ -45,8 +128,18 @@ impl AutoRebalanceAgent {
             min_apy: args.min_apy,
             pools: UnorderedMap::new(b"p"),
             current_pool: "default_pool".to_string(),
+            pool_balance: 0,
+            rebalancing: false,
+            keepers: UnorderedSet::new(b"k".to_vec()),
+            min_rebalance_interval_secs: DEFAULT_MIN_REBALANCE_INTERVAL_SECS,
+            min_apy_improvement: DEFAULT_MIN_APY_IMPROVEMENT,
+            last_rebalance_at: 0,
             total_balance: 0,
-            balances: UnorderedMap::new(b"b"),
+            token: FungibleToken::new(b"s".to_vec()),
+            metadata: LazyOption::new(b"m".to_vec(), Some(&share_metadata())),
+            token_whitelist: UnorderedSet::new(b"w".to_vec()),
+            token_balances: LookupMap::new(b"tb".to_vec()),
+            token_totals: UnorderedMap::new(b"tt".to_vec()),
         }
     }
 
@@ -61,6 +154,36 @@ impl AutoRebalanceAgent {
         self.min_apy
     }
 
+    pub fn add_keeper(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        assert!(self.keepers.insert(&account_id), "Already a keeper");
+        env::log(format!("keeper_added: {}", account_id).as_bytes());
+    }
+
+    pub fn remove_keeper(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        assert!(self.keepers.remove(&account_id), "Not a keeper");
+        env::log(format!("keeper_removed: {}", account_id).as_bytes());
+    }
+
+    pub fn get_keepers(&self) -> Vec<AccountId> {
+        self.keepers.to_vec()
+    }
+
+    pub fn set_rebalance_policy(&mut self, min_interval_secs: u64, min_apy_improvement: u64) {
+        self.assert_owner();
+        assert!(min_apy_improvement <= 10000, "APY improvement cannot exceed maximum value");
+        assert!(min_interval_secs <= MAX_REBALANCE_INTERVAL_SECS, "Rebalance interval too long");
+        self.min_rebalance_interval_secs = min_interval_secs;
+        self.min_apy_improvement = min_apy_improvement;
+        env::log(format!("rebalance_policy_updated: {} {}", min_interval_secs, min_apy_improvement).as_bytes());
+    }
+
+    /// (minimum seconds between rebalances, minimum APY improvement in basis points)
+    pub fn get_rebalance_policy(&self) -> (u64, u64) {
+        (self.min_rebalance_interval_secs, self.min_apy_improvement)
+    }
+
     pub fn add_pool(&mut self, pool_id: String, info: PoolInfo) {
         self.assert_owner();
         assert!(!self.pools.get(&pool_id).is_some(), "Pool already exists");
@@ -83,7 +206,15 @@ impl AutoRebalanceAgent {
         self.current_pool.clone()
     }
 
+    /// Keeper entry point: switches to the best pool if it beats the current one by
+    /// `min_apy_improvement` and the rebalance interval has passed.
     pub fn check_and_rebalance(&mut self) {
+        self.assert_keeper();
+        if self.rebalancing || !self.rebalance_interval_elapsed() {
+            env::log(b"rebalance_cooldown");
+            return;
+        }
+
         let mut highest_apy = self.min_apy;
         let mut best_pool = self.current_pool.clone();
 
@@ -94,49 +225,431 @@ impl AutoRebalanceAgent {
             }
         }
 
-        if best_pool != self.current_pool {
+        if best_pool != self.current_pool && self.beats_current_pool(highest_apy) {
             self.rebalance_to_pool(&best_pool);
         } else {
             env::log(b"no_rebalance_needed");
         }
     }
 
+    pub fn get_pool_balance(&self) -> U128 {
+        U128(self.pool_balance)
+    }
+
+    pub fn is_rebalancing(&self) -> bool {
+        self.rebalancing
+    }
+
+    /// Moves the vault's funds to `pool_id`: withdraws the whole position, yield
+    /// included, from the current pool, then deposits everything into the new one. `current_pool` only changes once the
+    /// deposit succeeds; see the `on_pool_*` callbacks for the failure paths.
     pub fn rebalance_to_pool(&mut self, pool_id: &String) {
-        assert!(self.pools.get(pool_id).is_some(), "Pool not found");
-        self.current_pool = pool_id.clone();
-        env::log(format!("rebalanced_to_pool: {}", pool_id).as_bytes());
+        self.assert_keeper();
+        let target = self.pools.get(pool_id).expect("Pool not found");
+        assert!(!self.rebalancing, "Rebalance already in progress");
+        assert!(pool_id != &self.current_pool, "Already in this pool");
+        assert!(self.rebalance_interval_elapsed(), "Rebalance interval not elapsed");
+        assert!(self.beats_current_pool(target.apy), "APY improvement below minimum");
+
+        let from = self.current_pool.clone();
+        if self.pool_balance > 0 && self.pools.get(&from).is_some() {
+            self.rebalancing = true;
+            env::log(format!("rebalance_started: {} -> {}", from, pool_id).as_bytes());
+            ext_pool::get_position(env::current_account_id(), &from, 0, GAS_FOR_POOL_CALL).then(
+                ext_self::on_pool_position(
+                    from,
+                    pool_id.clone(),
+                    &env::current_account_id(),
+                    0,
+                    GAS_FOR_ON_POOL_POSITION,
+                ),
+            );
+        } else if self.total_balance > 0 {
+            self.rebalancing = true;
+            env::log(format!("rebalance_started: {} -> {}", from, pool_id).as_bytes());
+            self.deposit_into_pool(from, pool_id.clone(), false);
+        } else {
+            // Nothing to move
+            self.current_pool = pool_id.clone();
+            self.last_rebalance_at = env::block_timestamp();
+            env::log(format!("rebalanced_to_pool: {}", pool_id).as_bytes());
+        }
+    }
+
+    /// Withdraws the position `from` reported, or ends the rebalance in `from` if
+    /// the pool couldn't report it.
+    #[private]
+    pub fn on_pool_position(&mut self, from: String, to: String) {
+        match promise_result_u128() {
+            Some(position) => {
+                ext_pool::withdraw(position, &from, 0, GAS_FOR_POOL_CALL).then(ext_self::on_pool_withdrawn(
+                    from,
+                    to,
+                    &env::current_account_id(),
+                    0,
+                    GAS_FOR_ON_POOL_WITHDRAWN,
+                ));
+            }
+            None => {
+                self.rebalancing = false;
+                env::log(format!("rebalance_failed: position of {}", from).as_bytes());
+            }
+        }
+    }
+
+    /// Books the amount `from` actually sent back, yield included, and deposits it
+    /// into `to`, or ends the rebalance in `from` if the withdrawal failed.
+    #[private]
+    pub fn on_pool_withdrawn(&mut self, from: String, to: String) {
+        match promise_result_u128() {
+            Some(received) => {
+                self.total_balance = self.total_balance - self.pool_balance + received.0;
+                self.pool_balance = 0;
+                env::log(format!("pool_withdrawn: {} {}", from, received.0).as_bytes());
+                self.deposit_into_pool(from, to, true);
+            }
+            _ => {
+                self.rebalancing = false;
+                env::log(format!("rebalance_failed: withdraw from {}", from).as_bytes());
+            }
+        }
+    }
+
+    /// Commits `to` as the current pool. On failure the refunded funds go back into
+    /// `from` if they were `withdrew` from it, and otherwise stay idle in the vault.
+    #[private]
+    pub fn on_pool_deposited(&mut self, from: String, to: String, amount: U128, withdrew: bool) {
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                self.current_pool = to.clone();
+                self.pool_balance = amount.0;
+                self.rebalancing = false;
+                self.last_rebalance_at = env::block_timestamp();
+                env::log(format!("rebalanced_to_pool: {}", to).as_bytes());
+            }
+            _ => {
+                if withdrew && self.pools.get(&from).is_some() {
+                    env::log(format!("rebalance_failed: deposit into {}, rolling back", to).as_bytes());
+                    ext_pool::deposit(&from, amount.0, GAS_FOR_POOL_CALL).then(ext_self::on_pool_restored(
+                        from,
+                        amount,
+                        &env::current_account_id(),
+                        0,
+                        GAS_FOR_ON_POOL_RESTORED,
+                    ));
+                } else {
+                    self.rebalancing = false;
+                    env::log(format!("rebalance_failed: deposit into {}, funds idle", to).as_bytes());
+                }
+            }
+        }
+    }
+
+    /// Ends a rolled-back rebalance. If even the restoring deposit failed, the funds
+    /// stay idle in the vault until the next rebalance.
+    #[private]
+    pub fn on_pool_restored(&mut self, pool_id: String, amount: U128) {
+        self.rebalancing = false;
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                self.pool_balance = amount.0;
+                env::log(format!("rebalance_rolled_back: {}", pool_id).as_bytes());
+            }
+            _ => env::log(format!("rebalance_rollback_failed: {} funds idle", pool_id).as_bytes()),
+        }
+    }
+
+    /// Recovery for a rebalance whose callbacks never ran (e.g. out of gas): clears
+    /// `rebalancing` and records where the funds actually are, as checked by the
+    /// owner against the pools.
+    pub fn reset_rebalancing(&mut self, current_pool: String, pool_balance: U128) {
+        self.assert_owner();
+        assert!(self.rebalancing, "No rebalance in progress");
+        assert!(pool_balance.0 <= self.total_balance, "Pool balance exceeds total balance");
+        assert!(
+            pool_balance.0 == 0 || self.pools.get(&current_pool).is_some(),
+            "Pool not found"
+        );
+        self.current_pool = current_pool;
+        self.pool_balance = pool_balance.0;
+        self.rebalancing = false;
+        env::log(format!("rebalancing_reset: {} {}", self.current_pool, self.pool_balance).as_bytes());
     }
 
+    /// Mints shares for the attached deposit at the current price per share. The
+    /// caller must have registered storage for the share token (NEP-145).
     #[payable]
-    pub fn deposit(&mut self) {
+    pub fn deposit(&mut self) -> U128 {
+        assert!(!self.rebalancing, "Rebalance in progress");
         let account_id = env::predecessor_account_id();
-        let deposit = env::attached_deposit();
-        
-        let balance = self.balances.get(&account_id).unwrap_or(0);
-        self.balances.insert(&account_id, &(balance + deposit));
-        self.total_balance += deposit;
+        let amount = env::attached_deposit();
+        assert!(
+            self.token.accounts.contains_key(&account_id),
+            "Account is not registered, call storage_deposit first"
+        );
+        let minted = self.convert_to_shares(amount);
+        assert!(minted > 0, "Deposit too small to mint shares");
+
+        self.token.internal_deposit(&account_id, minted);
+        self.total_balance += amount;
+        env::log(format!("deposit: {} {} shares: {}", account_id, amount, minted).as_bytes());
+        U128(minted)
     }
 
-    pub fn withdraw(&mut self, amount: U128) {
+    /// Burns enough shares, rounded up, to withdraw `amount` of the underlying.
+    /// Idle funds are sent right away; any shortfall is first pulled out of
+    /// `current_pool`, and `on_withdraw_from_pool` pays out or undoes the withdrawal.
+    pub fn withdraw(&mut self, amount: U128) -> U128 {
+        assert!(!self.rebalancing, "Rebalance in progress");
         let account_id = env::predecessor_account_id();
         let amount: u128 = amount.into();
-        let balance = self.balances.get(&account_id).expect("No balance found");
-        assert!(balance >= amount, "Insufficient balance");
-        
-        self.balances.insert(&account_id, &(balance - amount));
+        assert!(amount > 0, "Withdrawal amount must be positive");
+        let burned = self.preview_withdraw(U128(amount)).0;
+        assert!(self.shares_of(&account_id) >= burned, "Insufficient balance");
+        let shortfall = amount.saturating_sub(self.idle_balance());
+        assert!(shortfall <= self.pool_balance, "Insufficient liquidity");
+
+        self.token.internal_withdraw(&account_id, burned);
         self.total_balance -= amount;
-        
-        Promise::new(account_id).transfer(amount);
+        env::log(format!("withdraw: {} {} shares: {}", account_id, amount, burned).as_bytes());
+
+        if shortfall == 0 {
+            Promise::new(account_id).transfer(amount);
+        } else {
+            self.rebalancing = true;
+            self.pool_balance -= shortfall;
+            ext_pool::withdraw(U128(shortfall), &self.current_pool, 0, GAS_FOR_POOL_CALL).then(
+                ext_self::on_withdraw_from_pool(
+                    account_id,
+                    U128(amount),
+                    U128(burned),
+                    U128(shortfall),
+                    &env::current_account_id(),
+                    0,
+                    GAS_FOR_ON_WITHDRAW_FROM_POOL,
+                ),
+            );
+        }
+        U128(burned)
+    }
+
+    /// Sends a withdrawal that needed funds from `current_pool` once the pool paid
+    /// out, or restores the burned shares and balances if it didn't.
+    #[private]
+    pub fn on_withdraw_from_pool(&mut self, account_id: AccountId, amount: U128, burned: U128, shortfall: U128) {
+        self.rebalancing = false;
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                Promise::new(account_id).transfer(amount.0);
+            }
+            _ => {
+                self.pool_balance += shortfall.0;
+                self.total_balance += amount.0;
+                // The account may have unregistered once its shares were gone
+                if !self.token.accounts.contains_key(&account_id) {
+                    self.token.internal_register_account(&account_id);
+                }
+                self.token.internal_deposit(&account_id, burned.0);
+                env::log(format!("withdraw_failed: {} {} shares: {}", account_id, amount.0, burned.0).as_bytes());
+            }
+        }
+    }
+
+    pub fn add_token(&mut self, token_id: AccountId) {
+        self.assert_owner();
+        assert!(self.token_whitelist.insert(&token_id), "Token already whitelisted");
+        env::log(format!("token_whitelisted: {}", token_id).as_bytes());
+    }
+
+    /// Stops accepting `token_id`; existing balances can still be withdrawn.
+    pub fn remove_token(&mut self, token_id: AccountId) {
+        self.assert_owner();
+        assert!(self.token_whitelist.remove(&token_id), "Token not whitelisted");
+        env::log(format!("token_removed: {}", token_id).as_bytes());
+    }
+
+    pub fn get_tokens(&self) -> Vec<AccountId> {
+        self.token_whitelist.to_vec()
+    }
+
+    pub fn get_token_balance(&self, account_id: AccountId, token_id: AccountId) -> U128 {
+        U128(self.token_balances.get(&(account_id, token_id)).unwrap_or(0))
     }
 
+    pub fn get_token_total(&self, token_id: AccountId) -> U128 {
+        U128(self.token_totals.get(&token_id).unwrap_or(0))
+    }
+
+    /// Sends `amount` of `token_id` back to the caller with `ft_transfer`. The balance
+    /// is debited up front and restored by `on_token_withdrawn` if the transfer fails.
+    /// Requires exactly one yoctoNEAR, which is forwarded to `ft_transfer`.
+    #[payable]
+    pub fn withdraw_token(&mut self, token_id: AccountId, amount: U128) -> Promise {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let amount: u128 = amount.into();
+        assert!(amount > 0, "Withdrawal amount must be positive");
+        let balance = self.token_balances.get(&(account_id.clone(), token_id.clone())).unwrap_or(0);
+        assert!(balance >= amount, "Insufficient token balance");
+
+        self.internal_debit_token(&account_id, &token_id, amount);
+        env::log(format!("token_withdraw: {} {} {}", account_id, token_id, amount).as_bytes());
+
+        ext_fungible_token::ft_transfer(
+            account_id.clone(),
+            U128(amount),
+            None,
+            &token_id,
+            1,
+            GAS_FOR_FT_TRANSFER,
+        )
+        .then(ext_self::on_token_withdrawn(
+            account_id,
+            token_id,
+            U128(amount),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_WITHDRAW,
+        ))
+    }
+
+    /// Returns the amount actually withdrawn: all of it, or zero after restoring the
+    /// balance of a failed transfer.
+    #[private]
+    pub fn on_token_withdrawn(&mut self, account_id: AccountId, token_id: AccountId, amount: U128) -> U128 {
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => amount,
+            _ => {
+                self.internal_credit_token(&account_id, &token_id, amount.0);
+                env::log(format!("token_withdraw_failed: {} {} {}", account_id, token_id, amount.0).as_bytes());
+                U128(0)
+            }
+        }
+    }
+
+    /// Adds harvested yield to the vault without minting shares, raising the price per share.
+    #[payable]
+    pub fn harvest(&mut self) {
+        self.assert_owner();
+        let amount = env::attached_deposit();
+        assert!(self.token.total_supply > 0, "No shares to distribute yield to");
+        self.total_balance += amount;
+        env::log(format!("yield_harvested: {}", amount).as_bytes());
+    }
+
+    /// Underlying value of `account_id`'s shares.
     pub fn get_balance(&self, account_id: AccountId) -> U128 {
-        U128(self.balances.get(&account_id).unwrap_or(0))
+        U128(self.convert_to_assets(self.shares_of(&account_id)))
+    }
+
+    pub fn get_shares(&self, account_id: AccountId) -> U128 {
+        U128(self.shares_of(&account_id))
     }
 
     pub fn get_total_balance(&self) -> U128 {
         U128(self.total_balance)
     }
 
+    pub fn get_total_shares(&self) -> U128 {
+        U128(self.token.total_supply)
+    }
+
+    /// Underlying value of one whole share.
+    pub fn price_per_share(&self) -> U128 {
+        U128(self.convert_to_assets(SHARE_UNIT))
+    }
+
+    /// Shares a deposit of `amount` would mint now.
+    pub fn preview_deposit(&self, amount: U128) -> U128 {
+        U128(self.convert_to_shares(amount.0))
+    }
+
+    /// Shares a withdrawal of `amount` would burn now.
+    pub fn preview_withdraw(&self, amount: U128) -> U128 {
+        if self.token.total_supply == 0 || self.total_balance == 0 {
+            return amount;
+        }
+        U128(mul_div(amount.0, self.token.total_supply, self.total_balance, true))
+    }
+
+    fn convert_to_shares(&self, amount: Balance) -> Balance {
+        if self.token.total_supply == 0 || self.total_balance == 0 {
+            return amount;
+        }
+        mul_div(amount, self.token.total_supply, self.total_balance, false)
+    }
+
+    fn convert_to_assets(&self, shares: Balance) -> Balance {
+        if self.token.total_supply == 0 {
+            return shares;
+        }
+        mul_div(shares, self.total_balance, self.token.total_supply, false)
+    }
+
+    /// yoctoNEAR held by the vault itself rather than deployed in a pool.
+    fn idle_balance(&self) -> Balance {
+        self.total_balance.saturating_sub(self.pool_balance)
+    }
+
+    /// Deposits every idle yoctoNEAR into `to`, then resolves with `on_pool_deposited`.
+    /// `withdrew` records whether the funds were just pulled out of `from`.
+    fn deposit_into_pool(&mut self, from: String, to: String, withdrew: bool) {
+        let amount = self.idle_balance();
+        ext_pool::deposit(&to, amount, GAS_FOR_POOL_CALL).then(ext_self::on_pool_deposited(
+            from,
+            to,
+            U128(amount),
+            withdrew,
+            &env::current_account_id(),
+            0,
+            GAS_FOR_ON_POOL_DEPOSITED,
+        ));
+    }
+
+    fn internal_credit_token(&mut self, account_id: &AccountId, token_id: &AccountId, amount: Balance) {
+        let key = (account_id.clone(), token_id.clone());
+        let balance = self.token_balances.get(&key).unwrap_or(0);
+        self.token_balances.insert(&key, &(balance + amount));
+        let total = self.token_totals.get(token_id).unwrap_or(0);
+        self.token_totals.insert(token_id, &(total + amount));
+    }
+
+    fn internal_debit_token(&mut self, account_id: &AccountId, token_id: &AccountId, amount: Balance) {
+        let key = (account_id.clone(), token_id.clone());
+        let balance = self.token_balances.get(&key).unwrap_or(0);
+        self.token_balances.insert(&key, &(balance - amount));
+        let total = self.token_totals.get(token_id).unwrap_or(0);
+        self.token_totals.insert(token_id, &(total - amount));
+    }
+
+    fn shares_of(&self, account_id: &AccountId) -> Balance {
+        self.token.accounts.get(account_id).unwrap_or(0)
+    }
+
+    fn rebalance_interval_elapsed(&self) -> bool {
+        self.last_rebalance_at == 0
+            || env::block_timestamp()
+                >= self
+                    .last_rebalance_at
+                    .saturating_add(self.min_rebalance_interval_secs.saturating_mul(NANOS_PER_SEC))
+    }
+
+    /// Whether `apy` clears the current pool's APY plus the hysteresis margin. Always
+    /// true while the funds aren't in a known pool.
+    fn beats_current_pool(&self, apy: u64) -> bool {
+        match self.pools.get(&self.current_pool) {
+            Some(current) => apy >= current.apy.saturating_add(self.min_apy_improvement),
+            None => true,
+        }
+    }
+
+    fn assert_keeper(&self) {
+        assert!(
+            self.keepers.contains(&env::predecessor_account_id()),
+            "Only keepers can call this method"
+        );
+    }
+
     fn assert_owner(&self) {
         assert_eq!(
             env::predecessor_account_id(),
@@ -146,10 +659,79 @@ impl AutoRebalanceAgent {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::testing_env;
-    use std::convert::TryFrom;
+impl AutoRebalanceAgent {
+    /// Shares burned by the token (e.g. sent to a deleted account) leave their
+    /// underlying in the vault for the remaining holders.
+    fn on_tokens_burned(&mut self, account_id: AccountId, amount: Balance) {
+        env::log(format!("shares_burned: {} {}", account_id, amount).as_bytes());
+    }
+
+    fn on_account_closed(&mut self, account_id: AccountId, balance: Balance) {
+        env::log(format!("account_closed: {} shares_burned: {}", account_id, balance).as_bytes());
+    }
 }
+
+near_contract_standards::impl_fungible_token_core!(AutoRebalanceAgent, token, on_tokens_burned);
+near_contract_standards::impl_fungible_token_storage!(AutoRebalanceAgent, token, on_account_closed);
+
+#[near_bindgen]
+impl FungibleTokenMetadataProvider for AutoRebalanceAgent {
+    fn ft_metadata(&self) -> FungibleTokenMetadata {
+        self.metadata.get().unwrap()
+    }
+}
+
+/// Credits tokens sent with `ft_transfer_call` to the sender. Tokens that aren't
+/// whitelisted, or come from senders that haven't paid for storage with
+/// `storage_deposit`, are rejected, so the token contract refunds them.
+#[near_bindgen]
+impl FungibleTokenReceiver for AutoRebalanceAgent {
+    fn ft_on_transfer(&mut self, sender_id: ValidAccountId, amount: U128, _msg: String) -> PromiseOrValue<U128> {
+        let token_id = env::predecessor_account_id();
+        assert!(self.token_whitelist.contains(&token_id), "Token not whitelisted");
+        assert!(amount.0 > 0, "Deposit amount must be positive");
+        let sender_id: AccountId = sender_id.into();
+        assert!(
+            self.token.accounts.contains_key(&sender_id),
+            "Account is not registered, call storage_deposit first"
+        );
+
+        self.internal_credit_token(&sender_id, &token_id, amount.0);
+        env::log(format!("token_deposit: {} {} {}", sender_id, token_id, amount.0).as_bytes());
+        PromiseOrValue::Value(U128(0))
+    }
+}
+
+fn share_metadata() -> FungibleTokenMetadata {
+    FungibleTokenMetadata {
+        spec: FT_METADATA_SPEC.to_string(),
+        name: "Yield Vault Share".to_string(),
+        symbol: "yvNEAR".to_string(),
+        icon: None,
+        reference: None,
+        reference_hash: None,
+        decimals: 24,
+    }
+}
+
+/// The `U128` returned by promise 0, or `None` if it failed or returned something else.
+fn promise_result_u128() -> Option<U128> {
+    match env::promise_result(0) {
+        PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<U128>(&value).ok(),
+        _ => None,
+    }
+}
+
+/// `a * b / denominator` without intermediate overflow, rounded down or up.
+fn mul_div(a: u128, b: u128, denominator: u128, round_up: bool) -> u128 {
+    let denominator = U256::from(denominator);
+    let product = U256::from(a) * U256::from(b);
+    let mut result = product / denominator;
+    if round_up && !(product % denominator).is_zero() {
+        result += U256::one();
+    }
+    result.as_u128()
+}
+
+#[cfg(test)]
+mod tests;
@@ -1,7 +1,8 @@
 use near_sdk::test_utils::{accounts, VMContextBuilder};
-use near_sdk::{testing_env, AccountId, Balance, Gas, Promise};
+use near_sdk::{testing_env, AccountId, Balance, Gas, Promise, PromiseResult, RuntimeFeesConfig, VMConfig};
 use near_sdk::json_types::U128;
 use near_sdk::env;
+use std::collections::HashMap;
 
 use crate::*;
 
@@ -16,6 +17,43 @@ pub fn setup_contract() -> (AutoRebalanceAgent, AccountId) {
     (contract, owner)
 }
 
+/// Keeper account used by the rebalancing tests; distinct from the owner.
+fn keeper() -> AccountId {
+    accounts(5)
+}
+
+fn add_keeper(contract: &mut AutoRebalanceAgent, owner: &AccountId) {
+    testing_env!(get_context(owner.clone()).build());
+    contract.add_keeper(keeper());
+}
+
+fn register(contract: &mut AutoRebalanceAgent, account_id: &AccountId) {
+    contract.token.internal_register_account(account_id);
+}
+
+/// Context for a `#[private]` callback whose single promise resolved with `result`.
+fn callback_context(result: PromiseResult) {
+    callback_context_with_gas(result, 10u64.pow(18));
+}
+
+/// Like `callback_context`, but with only `prepaid_gas` attached to the callback.
+fn callback_context_with_gas(result: PromiseResult, prepaid_gas: u64) {
+    let mut context = get_context(accounts(0));
+    context.prepaid_gas(prepaid_gas);
+    testing_env!(
+        context.build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        HashMap::default(),
+        vec![result]
+    );
+}
+
+/// A promise that returned `amount` as a JSON `U128`, like a pool's `withdraw`.
+fn returned(amount: Balance) -> PromiseResult {
+    PromiseResult::Successful(near_sdk::serde_json::to_vec(&U128(amount)).unwrap())
+}
+
 fn get_context(predecessor_account_id: AccountId) -> VMContextBuilder {
     let mut builder = VMContextBuilder::new();
     builder
@@ -87,7 +125,8 @@ fn test_min_apy_update_emits_event() {
 #[test]
 fn test_auto_compound_gas_usage() {
     let (mut contract, owner) = setup_contract();
-    let context = get_context(owner);
+    add_keeper(&mut contract, &owner);
+    let context = get_context(keeper());
     testing_env!(context.build());
     
     let initial_gas = env::used_gas();
@@ -162,6 +201,8 @@ fn test_check_and_rebalance() {
     });
 
     // Test rebalancing logic
+    contract.add_keeper(keeper());
+    testing_env!(get_context(keeper()).build());
     contract.check_and_rebalance();
     
     // Should move to the higher APY pool
@@ -171,6 +212,7 @@ fn test_check_and_rebalance() {
 #[test]
 fn test_deposit_and_withdrawal() {
     let (mut contract, owner) = setup_contract();
+    register(&mut contract, &owner);
     let mut context = get_context(owner.clone());
     context.attached_deposit(NEAR);
     testing_env!(context.build());
@@ -201,6 +243,8 @@ fn test_multiple_users() {
     let (mut contract, _) = setup_contract();
     let user1 = accounts(1);
     let user2 = accounts(2);
+    register(&mut contract, &user1);
+    register(&mut contract, &user2);
 
     // User 1 deposit
     let mut context = get_context(user1.clone());
@@ -220,6 +264,491 @@ fn test_multiple_users() {
     assert_eq!(contract.get_total_balance(), U128(NEAR * 3));
 }
 
+#[test]
+fn test_yield_raises_share_price() {
+    let (mut contract, owner) = setup_contract();
+    let user1 = accounts(1);
+    let user2 = accounts(2);
+    register(&mut contract, &user1);
+    register(&mut contract, &user2);
+
+    let mut context = get_context(user1.clone());
+    context.attached_deposit(NEAR);
+    testing_env!(context.build());
+    assert_eq!(contract.deposit(), U128(NEAR));
+    assert_eq!(contract.price_per_share(), U128(NEAR));
+
+    // The pool doubled the vault's NEAR
+    let mut context = get_context(owner);
+    context.attached_deposit(NEAR);
+    testing_env!(context.build());
+    contract.harvest();
+    assert_eq!(contract.price_per_share(), U128(NEAR * 2));
+    assert_eq!(contract.get_balance(user1.clone()), U128(NEAR * 2));
+
+    // Later depositors get fewer shares per NEAR
+    assert_eq!(contract.preview_deposit(U128(NEAR * 2)), U128(NEAR));
+    let mut context = get_context(user2.clone());
+    context.attached_deposit(NEAR * 2);
+    testing_env!(context.build());
+    contract.deposit();
+    assert_eq!(contract.get_shares(user2.clone()), U128(NEAR));
+    assert_eq!(contract.get_balance(user2), U128(NEAR * 2));
+    assert_eq!(contract.get_total_shares(), U128(NEAR * 2));
+    assert_eq!(contract.get_total_balance(), U128(NEAR * 4));
+}
+
+#[test]
+fn test_withdraw_burns_proportional_shares() {
+    let (mut contract, owner) = setup_contract();
+    let user = accounts(1);
+    register(&mut contract, &user);
+
+    let mut context = get_context(user.clone());
+    context.attached_deposit(NEAR * 3);
+    testing_env!(context.build());
+    contract.deposit();
+
+    let mut context = get_context(owner);
+    context.attached_deposit(NEAR);
+    testing_env!(context.build());
+    contract.harvest();
+
+    // 3 shares now back 4 NEAR, so 2 NEAR costs 1.5 shares
+    let context = get_context(user.clone());
+    testing_env!(context.build());
+    assert_eq!(contract.preview_withdraw(U128(NEAR * 2)), U128(NEAR * 3 / 2));
+    assert_eq!(contract.withdraw(U128(NEAR * 2)), U128(NEAR * 3 / 2));
+    assert_eq!(contract.get_shares(user.clone()), U128(NEAR * 3 / 2));
+    assert_eq!(contract.get_balance(user), U128(NEAR * 2));
+}
+
+#[test]
+fn test_shares_are_transferable() {
+    let (mut contract, _) = setup_contract();
+    let user1 = accounts(1);
+    let user2 = accounts(2);
+    register(&mut contract, &user1);
+    register(&mut contract, &user2);
+
+    let mut context = get_context(user1.clone());
+    context.attached_deposit(NEAR * 2);
+    testing_env!(context.build());
+    contract.deposit();
+
+    let mut context = get_context(user1.clone());
+    context.attached_deposit(1);
+    testing_env!(context.build());
+    contract.ft_transfer(user2.clone(), U128(NEAR / 2), None);
+
+    assert_eq!(contract.ft_balance_of(user1.clone()), U128(NEAR * 3 / 2));
+    assert_eq!(contract.get_balance(user2.clone()), U128(NEAR / 2));
+    assert_eq!(contract.ft_total_supply(), U128(NEAR * 2));
+    assert_eq!(contract.ft_metadata().decimals, 24);
+
+    // The new holder can redeem the transferred position
+    let context = get_context(user2.clone());
+    testing_env!(context.build());
+    contract.withdraw(U128(NEAR / 2));
+    assert_eq!(contract.get_shares(user2), U128(0));
+}
+
+#[test]
+#[should_panic(expected = "Account is not registered")]
+fn test_deposit_requires_storage_registration() {
+    let (mut contract, _) = setup_contract();
+    let mut context = get_context(accounts(1));
+    context.attached_deposit(NEAR);
+    testing_env!(context.build());
+
+    contract.deposit();
+}
+
+fn whitelist_token(contract: &mut AutoRebalanceAgent, owner: &AccountId, token: &AccountId) {
+    testing_env!(get_context(owner.clone()).build());
+    contract.add_token(token.clone());
+}
+
+#[test]
+fn test_token_deposit_via_ft_on_transfer() {
+    let (mut contract, owner) = setup_contract();
+    let usdc = accounts(3);
+    let user = accounts(1);
+    whitelist_token(&mut contract, &owner, &usdc);
+    register(&mut contract, &user);
+
+    // ft_transfer_call from the user makes the token contract call us
+    testing_env!(get_context(usdc.clone()).build());
+    let unused = contract.ft_on_transfer(user.clone(), U128(5_000_000), "".to_string());
+    assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+    contract.ft_on_transfer(user.clone(), U128(1_000_000), "".to_string());
+
+    assert_eq!(contract.get_token_balance(user.clone(), usdc.clone()), U128(6_000_000));
+    assert_eq!(contract.get_token_total(usdc.clone()), U128(6_000_000));
+    assert_eq!(contract.get_tokens(), vec![usdc]);
+}
+
+#[test]
+#[should_panic(expected = "Token not whitelisted")]
+fn test_rejects_unknown_tokens() {
+    let (mut contract, _) = setup_contract();
+    testing_env!(get_context(accounts(4)).build());
+
+    contract.ft_on_transfer(accounts(1), U128(1_000_000), "".to_string());
+}
+
+#[test]
+#[should_panic(expected = "Account is not registered")]
+fn test_token_deposit_requires_storage_registration() {
+    let (mut contract, owner) = setup_contract();
+    let usdc = accounts(3);
+    whitelist_token(&mut contract, &owner, &usdc);
+
+    // A dust transfer would otherwise create a balance entry nobody paid for
+    testing_env!(get_context(usdc).build());
+    contract.ft_on_transfer(accounts(1), U128(1), "".to_string());
+}
+
+#[test]
+fn test_failed_token_withdrawal_restores_balance() {
+    let (mut contract, owner) = setup_contract();
+    let usdc = accounts(3);
+    let user = accounts(1);
+    whitelist_token(&mut contract, &owner, &usdc);
+    register(&mut contract, &user);
+    testing_env!(get_context(usdc.clone()).build());
+    contract.ft_on_transfer(user.clone(), U128(5_000_000), "".to_string());
+
+    let mut context = get_context(user.clone());
+    context.attached_deposit(1);
+    testing_env!(context.build());
+    contract.withdraw_token(usdc.clone(), U128(2_000_000));
+    assert_eq!(contract.get_token_balance(user.clone(), usdc.clone()), U128(3_000_000));
+
+    // The ft_transfer failed, e.g. the user isn't registered with the token
+    callback_context(PromiseResult::Failed);
+    let withdrawn = contract.on_token_withdrawn(user.clone(), usdc.clone(), U128(2_000_000));
+    assert_eq!(withdrawn, U128(0));
+    assert_eq!(contract.get_token_balance(user, usdc.clone()), U128(5_000_000));
+    assert_eq!(contract.get_token_total(usdc), U128(5_000_000));
+}
+
+/// Contract with `NEAR * 3` deposited and deployed in "pool_a.near", and a better
+/// "pool_b.near" to move to.
+fn setup_deployed_vault() -> AutoRebalanceAgent {
+    let (mut contract, owner) = setup_contract();
+    let user = accounts(1);
+    register(&mut contract, &user);
+    let mut context = get_context(user);
+    context.attached_deposit(NEAR * 3);
+    testing_env!(context.build());
+    contract.deposit();
+
+    testing_env!(get_context(owner).build());
+    for (pool_id, apy) in [("pool_a.near", 800), ("pool_b.near", 1500)] {
+        contract.add_pool(pool_id.to_string(), PoolInfo {
+            apy,
+            tvl: U128(1_000_000),
+            risk_level: RiskLevel::Low,
+        });
+    }
+    contract.add_keeper(keeper());
+    contract.current_pool = "pool_a.near".to_string();
+    contract.pool_balance = NEAR * 3;
+    testing_env!(get_context(keeper()).build());
+    contract
+}
+
+#[test]
+fn test_rebalance_commits_after_withdraw_and_deposit() {
+    let mut contract = setup_deployed_vault();
+    contract.check_and_rebalance();
+    // Nothing is committed while the promises are in flight
+    assert!(contract.is_rebalancing());
+    assert_eq!(contract.get_current_pool(), "pool_a.near");
+
+    callback_context(returned(NEAR * 3));
+    contract.on_pool_position("pool_a.near".to_string(), "pool_b.near".to_string());
+    contract.on_pool_withdrawn("pool_a.near".to_string(), "pool_b.near".to_string());
+    assert_eq!(contract.get_pool_balance(), U128(0));
+    assert_eq!(contract.get_current_pool(), "pool_a.near");
+
+    contract.on_pool_deposited("pool_a.near".to_string(), "pool_b.near".to_string(), U128(NEAR * 3), true);
+    assert_eq!(contract.get_current_pool(), "pool_b.near");
+    assert_eq!(contract.get_pool_balance(), U128(NEAR * 3));
+    assert!(!contract.is_rebalancing());
+}
+
+#[test]
+fn test_rebalance_moves_accrued_yield() {
+    let mut contract = setup_deployed_vault();
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+
+    // pool_a reports principal plus 0.3 NEAR of yield and sends all of it back
+    callback_context(returned(NEAR * 33 / 10));
+    contract.on_pool_position("pool_a.near".to_string(), "pool_b.near".to_string());
+    contract.on_pool_withdrawn("pool_a.near".to_string(), "pool_b.near".to_string());
+    assert_eq!(contract.get_total_balance(), U128(NEAR * 33 / 10));
+    assert_eq!(contract.get_balance(accounts(1)), U128(NEAR * 33 / 10));
+
+    contract.on_pool_deposited("pool_a.near".to_string(), "pool_b.near".to_string(), U128(NEAR * 33 / 10), true);
+    assert_eq!(contract.get_pool_balance(), U128(NEAR * 33 / 10));
+}
+
+#[test]
+fn test_failed_pool_withdraw_keeps_current_pool() {
+    let mut contract = setup_deployed_vault();
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+
+    callback_context(returned(NEAR * 3));
+    contract.on_pool_position("pool_a.near".to_string(), "pool_b.near".to_string());
+    callback_context(PromiseResult::Failed);
+    contract.on_pool_withdrawn("pool_a.near".to_string(), "pool_b.near".to_string());
+    assert_eq!(contract.get_current_pool(), "pool_a.near");
+    assert_eq!(contract.get_pool_balance(), U128(NEAR * 3));
+    assert!(!contract.is_rebalancing());
+}
+
+#[test]
+fn test_failed_pool_deposit_rolls_back() {
+    let mut contract = setup_deployed_vault();
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+
+    callback_context(returned(NEAR * 3));
+    contract.on_pool_withdrawn("pool_a.near".to_string(), "pool_b.near".to_string());
+
+    // The new pool rejected the deposit; the funds go back into the old one
+    callback_context(PromiseResult::Failed);
+    contract.on_pool_deposited("pool_a.near".to_string(), "pool_b.near".to_string(), U128(NEAR * 3), true);
+    assert_eq!(contract.get_current_pool(), "pool_a.near");
+    assert!(contract.is_rebalancing());
+    assert!(env::logs().iter().any(|log| log.contains("rolling back")));
+
+    callback_context(PromiseResult::Successful(vec![]));
+    contract.on_pool_restored("pool_a.near".to_string(), U128(NEAR * 3));
+    assert_eq!(contract.get_pool_balance(), U128(NEAR * 3));
+    assert!(!contract.is_rebalancing());
+}
+
+#[test]
+fn test_rebalance_callbacks_fit_their_gas_budget() {
+    let mut contract = setup_deployed_vault();
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+
+    // Each callback gets exactly what its caller attaches and must still afford
+    // the promises it chains
+    callback_context_with_gas(returned(NEAR * 3), GAS_FOR_ON_POOL_POSITION);
+    contract.on_pool_position("pool_a.near".to_string(), "pool_b.near".to_string());
+
+    callback_context_with_gas(returned(NEAR * 3), GAS_FOR_ON_POOL_WITHDRAWN);
+    contract.on_pool_withdrawn("pool_a.near".to_string(), "pool_b.near".to_string());
+
+    callback_context_with_gas(PromiseResult::Failed, GAS_FOR_ON_POOL_DEPOSITED);
+    contract.on_pool_deposited("pool_a.near".to_string(), "pool_b.near".to_string(), U128(NEAR * 3), true);
+    assert!(env::logs().iter().any(|log| log.contains("rolling back")));
+}
+
+#[test]
+fn test_failed_deposit_of_idle_funds_keeps_them_idle() {
+    let mut contract = setup_deployed_vault();
+    contract.pool_balance = 0;
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+
+    // Nothing was withdrawn from pool_a, so nothing is put back there
+    callback_context(PromiseResult::Failed);
+    contract.on_pool_deposited("pool_a.near".to_string(), "pool_b.near".to_string(), U128(NEAR * 3), false);
+    assert!(!contract.is_rebalancing());
+    assert_eq!(contract.get_pool_balance(), U128(0));
+    assert!(env::logs().iter().any(|log| log.contains("funds idle")));
+}
+
+#[test]
+fn test_owner_resets_stuck_rebalance() {
+    let mut contract = setup_deployed_vault();
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+
+    // The callbacks never ran, but the owner finds the funds back in pool_a
+    testing_env!(get_context(accounts(0)).build());
+    contract.reset_rebalancing("pool_a.near".to_string(), U128(NEAR * 3));
+    assert!(!contract.is_rebalancing());
+    assert_eq!(contract.get_current_pool(), "pool_a.near");
+    assert_eq!(contract.get_pool_balance(), U128(NEAR * 3));
+}
+
+#[test]
+#[should_panic(expected = "Rebalance in progress")]
+fn test_withdraw_rejected_while_rebalancing() {
+    let mut contract = setup_deployed_vault();
+    contract.pool_balance = 0;
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+
+    testing_env!(get_context(accounts(1)).build());
+    contract.withdraw(U128(NEAR));
+}
+
+#[test]
+#[should_panic(expected = "Rebalance in progress")]
+fn test_deposit_rejected_while_rebalancing() {
+    let mut contract = setup_deployed_vault();
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+
+    let mut context = get_context(accounts(1));
+    context.attached_deposit(NEAR);
+    testing_env!(context.build());
+    contract.deposit();
+}
+
+#[test]
+fn test_withdraw_after_rebalance_pulls_from_pool() {
+    let mut contract = setup_deployed_vault();
+
+    // All 3 NEAR are deployed in pool_a, so the whole withdrawal comes from there
+    testing_env!(get_context(accounts(1)).build());
+    contract.withdraw(U128(NEAR));
+    assert!(contract.is_rebalancing());
+    assert_eq!(contract.get_pool_balance(), U128(NEAR * 2));
+    assert_eq!(contract.get_balance(accounts(1)), U128(NEAR * 2));
+
+    callback_context(PromiseResult::Successful(vec![]));
+    contract.on_withdraw_from_pool(accounts(1), U128(NEAR), U128(NEAR), U128(NEAR));
+    assert!(!contract.is_rebalancing());
+    assert_eq!(contract.get_total_balance(), U128(NEAR * 2));
+}
+
+#[test]
+fn test_failed_pool_withdrawal_restores_shares() {
+    let mut contract = setup_deployed_vault();
+    testing_env!(get_context(accounts(1)).build());
+    contract.withdraw(U128(NEAR));
+
+    callback_context(PromiseResult::Failed);
+    contract.on_withdraw_from_pool(accounts(1), U128(NEAR), U128(NEAR), U128(NEAR));
+    assert!(!contract.is_rebalancing());
+    assert_eq!(contract.get_pool_balance(), U128(NEAR * 3));
+    assert_eq!(contract.get_balance(accounts(1)), U128(NEAR * 3));
+    assert!(env::logs().iter().any(|log| log.contains("withdraw_failed")));
+}
+
+#[test]
+#[should_panic(expected = "Rebalance already in progress")]
+fn test_rejects_concurrent_rebalances() {
+    let mut contract = setup_deployed_vault();
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+}
+
+#[test]
+#[should_panic(expected = "Only keepers can call this method")]
+fn test_only_keepers_can_rebalance() {
+    let (mut contract, owner) = setup_contract();
+    add_keeper(&mut contract, &owner);
+
+    // Not even the owner
+    testing_env!(get_context(owner).build());
+    contract.check_and_rebalance();
+}
+
+#[test]
+fn test_rebalance_interval_enforced() {
+    let (mut contract, owner) = setup_contract();
+    add_keeper(&mut contract, &owner);
+    for (pool_id, apy) in [("pool_a.near", 800), ("pool_b.near", 1500)] {
+        contract.add_pool(pool_id.to_string(), PoolInfo {
+            apy,
+            tvl: U128(1_000_000),
+            risk_level: RiskLevel::Low,
+        });
+    }
+
+    let start = 1_000 * 1_000_000_000;
+    let mut context = get_context(keeper());
+    context.block_timestamp(start);
+    testing_env!(context.build());
+    contract.check_and_rebalance();
+    assert_eq!(contract.get_current_pool(), "pool_b.near");
+
+    testing_env!(get_context(owner).build());
+    contract.update_pool("pool_a.near".to_string(), PoolInfo {
+        apy: 3000,
+        tvl: U128(1_000_000),
+        risk_level: RiskLevel::Low,
+    });
+
+    // Half an hour later is too soon
+    let mut context = get_context(keeper());
+    context.block_timestamp(start + 1_800 * 1_000_000_000);
+    testing_env!(context.build());
+    contract.check_and_rebalance();
+    assert_eq!(contract.get_current_pool(), "pool_b.near");
+    assert!(env::logs().iter().any(|log| log.contains("rebalance_cooldown")));
+
+    let mut context = get_context(keeper());
+    context.block_timestamp(start + 3_600 * 1_000_000_000);
+    testing_env!(context.build());
+    contract.check_and_rebalance();
+    assert_eq!(contract.get_current_pool(), "pool_a.near");
+}
+
+#[test]
+fn test_failed_rebalance_does_not_start_cooldown() {
+    let mut contract = setup_deployed_vault();
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+    assert_eq!(contract.last_rebalance_at, 0);
+
+    callback_context(PromiseResult::Failed);
+    contract.on_pool_withdrawn("pool_a.near".to_string(), "pool_b.near".to_string());
+    assert_eq!(contract.last_rebalance_at, 0);
+
+    // The keeper can retry straight away, and only the successful attempt counts
+    let mut context = get_context(keeper());
+    context.block_timestamp(42);
+    testing_env!(context.build());
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+    let mut context = get_context(accounts(0));
+    context.block_timestamp(84);
+    testing_env!(
+        context.build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        HashMap::default(),
+        vec![PromiseResult::Successful(vec![])]
+    );
+    contract.on_pool_deposited("pool_a.near".to_string(), "pool_b.near".to_string(), U128(NEAR * 3), true);
+    assert_eq!(contract.last_rebalance_at, 84);
+}
+
+#[test]
+#[should_panic(expected = "Rebalance interval too long")]
+fn test_rebalance_interval_is_bounded() {
+    let (mut contract, owner) = setup_contract();
+    testing_env!(get_context(owner).build());
+    contract.set_rebalance_policy(u64::MAX, 100);
+}
+
+#[test]
+#[should_panic(expected = "APY improvement below minimum")]
+fn test_apy_hysteresis() {
+    let (mut contract, owner) = setup_contract();
+    add_keeper(&mut contract, &owner);
+    contract.set_rebalance_policy(0, 100);
+    for (pool_id, apy) in [("pool_a.near", 1000), ("pool_b.near", 1080)] {
+        contract.add_pool(pool_id.to_string(), PoolInfo {
+            apy,
+            tvl: U128(1_000_000),
+            risk_level: RiskLevel::Low,
+        });
+    }
+    contract.current_pool = "pool_a.near".to_string();
+
+    // 0.8% better isn't worth a switch under a 1% margin
+    testing_env!(get_context(keeper()).build());
+    contract.check_and_rebalance();
+    assert_eq!(contract.get_current_pool(), "pool_a.near");
+    assert!(env::logs().iter().any(|log| log.contains("no_rebalance_needed")));
+
+    contract.rebalance_to_pool(&"pool_b.near".to_string());
+}
+
 #[test]
 fn test_update_pool() {
     let (mut contract, owner) = setup_contract();